@@ -0,0 +1,449 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use failure::Error;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use tar::{Archive, Builder, EntryType, Header};
+use archive::{self, SourceFormat};
+
+const PASSWD: &str = "etc/passwd";
+const GROUP: &str = "etc/group";
+const SHADOW: &str = "etc/shadow";
+const GSHADOW: &str = "etc/gshadow";
+const ACCOUNT_FILES: [&str; 4] = [PASSWD, GROUP, SHADOW, GSHADOW];
+/// The longest user name that `useradd` accepts
+const MAX_USER_NAME_LEN: usize = 32;
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Turns `/etc/wsl.conf` into `etc/wsl.conf`. Returns `None` for a relative path, a path with
+/// `..` or `/`.
+fn guest_path(path: &str) -> Option<String> {
+    if !path.starts_with('/') {
+        return None;
+    }
+    let mut components = vec![];
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => return None,
+            _ => components.push(component),
+        }
+    }
+    if components.is_empty() {
+        None
+    } else {
+        Some(components.join("/"))
+    }
+}
+
+/// A file to add to a rootfs, given as `host_path:/guest/path[:mode[:uid:gid]]`.
+///
+/// `host_path` may have colons, as in `C:\wsl.conf:/etc/wsl.conf`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileAddition {
+    pub host_path: PathBuf,
+    /// The path in the rootfs without the leading `/`, such as `etc/wsl.conf`
+    pub guest_path: String,
+    /// 0o644 if it is not given
+    pub mode: u32,
+    /// root if it is not given
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl FromStr for FileAddition {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<FileAddition, Error> {
+        let parts = s.split(':').collect::<Vec<_>>();
+        // The fields after the guest path are numbers, so they tell where the guest path is.
+        for &len in &[3, 1, 0] {
+            if parts.len() < len + 2 {
+                continue;
+            }
+            let i = parts.len() - len - 1;
+            let guest = match guest_path(parts[i]) {
+                Some(guest) => guest,
+                None => continue,
+            };
+            let fields = &parts[i + 1..];
+            let mode = match fields.first() {
+                Some(mode) => match u32::from_str_radix(mode, 8) {
+                    Ok(mode) if mode <= 0o7777 => mode,
+                    _ => continue,
+                },
+                None => 0o644,
+            };
+            let (uid, gid) = if len == 3 {
+                match (fields[1].parse(), fields[2].parse()) {
+                    (Ok(uid), Ok(gid)) => (uid, gid),
+                    _ => continue,
+                }
+            } else {
+                (0, 0)
+            };
+            let host_path = parts[..i].join(":");
+            if host_path.is_empty() {
+                break;
+            }
+            return Ok(FileAddition {
+                host_path: PathBuf::from(host_path),
+                guest_path: guest,
+                mode,
+                uid,
+                gid,
+            });
+        }
+        Err(format_err!(
+            "\"{}\" is not host_path:/guest/path[:mode[:uid:gid]]",
+            s
+        ))
+    }
+}
+
+/// A user to add to a rootfs, given as `name:uid[:groups]` with comma-separated groups.
+///
+/// The user gets a primary group of the same name whose ID is `uid`, a home folder, and a locked
+/// password.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UserAddition {
+    pub name: String,
+    pub uid: u32,
+    pub groups: Vec<String>,
+}
+
+/// Checks that `name` is a user or group name that `useradd` accepts.
+pub fn validate_user_name(name: &str) -> Result<(), Error> {
+    let name_chars = name.strip_suffix('$').unwrap_or(name);
+    let valid = !name_chars.is_empty()
+        && name.len() <= MAX_USER_NAME_LEN
+        && !name_chars.starts_with(|c: char| c.is_ascii_digit() || c == '-')
+        && name_chars
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(format_err!(
+            "\"{}\" is not a user name of lowercase letters, digits, _ and -",
+            name
+        ))
+    }
+}
+
+impl FromStr for UserAddition {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<UserAddition, Error> {
+        let parts = s.split(':').collect::<Vec<_>>();
+        if parts.len() < 2 || parts.len() > 3 {
+            return Err(format_err!("\"{}\" is not name:uid[:groups]", s));
+        }
+        validate_user_name(parts[0])?;
+        let uid = parts[1]
+            .parse()
+            .map_err(|_| format_err!("\"{}\" is not a user ID", parts[1]))?;
+        let groups = parts
+            .get(2)
+            .map(|groups| {
+                groups
+                    .split(',')
+                    .filter(|group| !group.is_empty())
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        for group in &groups {
+            validate_user_name(group)?;
+        }
+        Ok(UserAddition {
+            name: parts[0].to_string(),
+            uid,
+            groups,
+        })
+    }
+}
+
+/// Files and users to add to a rootfs before it is registered.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Additions {
+    pub files: Vec<FileAddition>,
+    pub users: Vec<UserAddition>,
+}
+
+impl Additions {
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.users.is_empty()
+    }
+}
+
+/// Tells whether a line of `text` has `value` in the field `index`.
+fn has_field(text: &str, index: usize, value: &str) -> bool {
+    text.lines()
+        .any(|line| line.split(':').nth(index) == Some(value))
+}
+
+fn push_line(text: &mut String, line: &str) {
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text.push_str(line);
+    text.push('\n');
+}
+
+/// Adds `user` to the members of `group` in `text` of `/etc/group` or `/etc/gshadow`, which both
+/// have the members in the fourth field. Returns whether `group` is found.
+fn add_member(text: &mut String, group: &str, user: &str) -> bool {
+    let mut found = false;
+    let lines = text
+        .lines()
+        .map(|line| {
+            let mut fields = line.split(':').map(str::to_string).collect::<Vec<_>>();
+            if fields.len() < 4 || fields[0] != group {
+                return line.to_string();
+            }
+            found = true;
+            if !fields[3].split(',').any(|member| member == user) {
+                if !fields[3].is_empty() {
+                    fields[3].push(',');
+                }
+                fields[3].push_str(user);
+            }
+            fields.join(":")
+        })
+        .collect::<Vec<_>>();
+    *text = lines.join("\n") + "\n";
+    found
+}
+
+/// The files of a rootfs that have its users and groups.
+struct Accounts {
+    passwd: String,
+    group: Option<String>,
+    shadow: Option<String>,
+    gshadow: Option<String>,
+}
+
+impl Accounts {
+    fn add(&mut self, user: &UserAddition, shell: &str, days: u64) -> Result<(), Error> {
+        let (name, uid) = (&user.name, user.uid);
+        if has_field(&self.passwd, 0, name) {
+            return Err(format_err!("the user {} already exists", name));
+        }
+        if has_field(&self.passwd, 2, &uid.to_string()) {
+            return Err(format_err!("the user ID {} is already used", uid));
+        }
+        push_line(
+            &mut self.passwd,
+            &format!("{}:x:{}:{}::/home/{}:{}", name, uid, uid, name, shell),
+        );
+        if let Some(ref mut group) = self.group {
+            if has_field(group, 0, name) {
+                return Err(format_err!("the group {} already exists", name));
+            }
+            if has_field(group, 2, &uid.to_string()) {
+                return Err(format_err!("the group ID {} is already used", uid));
+            }
+            push_line(group, &format!("{}:x:{}:", name, uid));
+            for other in &user.groups {
+                if !add_member(group, other, name) {
+                    return Err(format_err!("the group {} cannot be found", other));
+                }
+            }
+        } else if !user.groups.is_empty() {
+            return Err(format_err!("/{} cannot be found", GROUP));
+        }
+        if let Some(ref mut shadow) = self.shadow {
+            push_line(shadow, &format!("{}:!:{}:0:99999:7:::", name, days));
+        }
+        if let Some(ref mut gshadow) = self.gshadow {
+            push_line(gshadow, &format!("{}:!::", name));
+            for other in &user.groups {
+                add_member(gshadow, other, name);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns a header of a new entry owned by `uid` and `gid`.
+fn new_header(entry_type: EntryType, mode: u32, uid: u32, gid: u32, mtime: u64) -> Header {
+    let mut header = Header::new_gnu();
+    header.set_entry_type(entry_type);
+    header.set_mode(mode);
+    header.set_uid(u64::from(uid));
+    header.set_gid(u64::from(gid));
+    header.set_mtime(mtime);
+    header.set_size(0);
+    header
+}
+
+/// Adds a folder owned by root for each parent of `path` that the archive does not have yet.
+fn add_parents<W: ::std::io::Write>(
+    builder: &mut Builder<W>,
+    seen: &mut HashSet<String>,
+    path: &str,
+    mtime: u64,
+) -> Result<(), Error> {
+    let mut parent = String::new();
+    for component in path.split('/').collect::<Vec<_>>().split_last().unwrap().1 {
+        if !parent.is_empty() {
+            parent.push('/');
+        }
+        parent.push_str(component);
+        if seen.insert(parent.clone()) {
+            let mut header = new_header(EntryType::Directory, 0o755, 0, 0, mtime);
+            builder.append_data(&mut header, &parent, &[][..])?;
+        }
+    }
+    Ok(())
+}
+
+/// Copies `src`, whose format is `format`, to a `.tar.gz` archive in `dest` with `additions`.
+///
+/// An added file replaces the file of the same path. Users are added to `/etc/passwd`,
+/// `/etc/group`, `/etc/shadow` and `/etc/gshadow`, whichever the archive has, or to the added
+/// file that replaces one of them.
+pub fn add_to_tar_gz<P, Q>(
+    src: P,
+    format: SourceFormat,
+    additions: &Additions,
+    dest: Q,
+) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let edits_accounts = !additions.users.is_empty();
+    let mut added = vec![];
+    for file in &additions.files {
+        let mut data = vec![];
+        File::open(&file.host_path)
+            .and_then(|mut host_file| host_file.read_to_end(&mut data))
+            .map_err(|e| format_err!("{}: {}", file.host_path.display(), e))?;
+        added.push((file, data));
+    }
+    let mut replaced = additions
+        .files
+        .iter()
+        .map(|file| file.guest_path.as_str())
+        .collect::<HashSet<_>>();
+    if edits_accounts {
+        replaced.extend(ACCOUNT_FILES.iter());
+    }
+    let mut accounts = vec![];
+    let mut seen = HashSet::new();
+    let mut builder = Builder::new(GzEncoder::new(File::create(dest)?, Compression::default()));
+    for entry in Archive::new(archive::open_tar(src, format)?).entries()? {
+        let mut entry = entry?;
+        let entry_type = entry.header().entry_type();
+        if entry_type == EntryType::XGlobalHeader {
+            continue;
+        }
+        let path = archive::normalize_path(&entry.path()?.to_string_lossy());
+        if path.is_empty() {
+            continue;
+        }
+        seen.insert(path.clone());
+        let mut header = entry.header().clone();
+        if replaced.contains(path.as_str()) {
+            let is_added = additions.files.iter().any(|file| file.guest_path == path);
+            if edits_accounts && ACCOUNT_FILES.contains(&&path[..]) && !is_added {
+                if !entry_type.is_file() {
+                    return Err(format_err!("/{} is not a regular file", path));
+                }
+                let mut text = String::new();
+                entry.read_to_string(&mut text)?;
+                accounts.push((path, header, text));
+            }
+            continue;
+        }
+        if entry_type.is_hard_link() || entry_type.is_symlink() {
+            let target = entry
+                .link_name()?
+                .ok_or_else(|| format_err!("{} does not have a link target", path))?
+                .into_owned();
+            builder.append_link(&mut header, &path, target)?;
+        } else {
+            header.set_size(entry.size());
+            builder.append_data(&mut header, &path, &mut entry)?;
+        }
+    }
+    // The added account files get the users too, so `add_users` writes them.
+    let (added_accounts, added): (Vec<_>, Vec<_>) = added.into_iter().partition(|(file, _)| {
+        edits_accounts && ACCOUNT_FILES.contains(&file.guest_path.as_str())
+    });
+    for (file, data) in added_accounts {
+        let text = String::from_utf8(data)
+            .map_err(|_| format_err!("{} is not UTF-8", file.host_path.display()))?;
+        add_parents(&mut builder, &mut seen, &file.guest_path, now)?;
+        let header = new_header(EntryType::Regular, file.mode, file.uid, file.gid, now);
+        accounts.push((file.guest_path.clone(), header, text));
+    }
+    if edits_accounts {
+        add_users(&mut builder, &mut seen, accounts, &additions.users, now)?;
+    }
+    for (file, data) in added {
+        add_parents(&mut builder, &mut seen, &file.guest_path, now)?;
+        let mut header = new_header(EntryType::Regular, file.mode, file.uid, file.gid, now);
+        header.set_size(data.len() as u64);
+        builder.append_data(&mut header, &file.guest_path, &data[..])?;
+    }
+    builder.into_inner()?.finish()?.sync_all()?;
+    Ok(())
+}
+
+fn add_users<W: ::std::io::Write>(
+    builder: &mut Builder<W>,
+    seen: &mut HashSet<String>,
+    files: Vec<(String, Header, String)>,
+    users: &[UserAddition],
+    now: u64,
+) -> Result<(), Error> {
+    let text = |name: &str| {
+        files
+            .iter()
+            .find(|(path, _, _)| path == name)
+            .map(|(_, _, text)| text.clone())
+    };
+    let mut accounts = Accounts {
+        passwd: text(PASSWD).ok_or_else(|| format_err!("/{} cannot be found", PASSWD))?,
+        group: text(GROUP),
+        shadow: text(SHADOW),
+        gshadow: text(GSHADOW),
+    };
+    let shell = if seen.contains("bin/bash") || seen.contains("usr/bin/bash") {
+        "/bin/bash"
+    } else {
+        "/bin/sh"
+    };
+    for user in users {
+        accounts.add(user, shell, now / SECONDS_PER_DAY)?;
+    }
+    for (path, mut header, _) in files {
+        let text = match path.as_str() {
+            PASSWD => &accounts.passwd,
+            GROUP => accounts.group.as_ref().unwrap(),
+            SHADOW => accounts.shadow.as_ref().unwrap(),
+            _ => accounts.gshadow.as_ref().unwrap(),
+        };
+        header.set_size(text.len() as u64);
+        builder.append_data(&mut header, &path, text.as_bytes())?;
+    }
+    for user in users {
+        let home = format!("home/{}", user.name);
+        if seen.contains(&home) {
+            continue;
+        }
+        add_parents(builder, seen, &home, now)?;
+        seen.insert(home.clone());
+        let mut header = new_header(EntryType::Directory, 0o750, user.uid, user.uid, now);
+        builder.append_data(&mut header, &home, &[][..])?;
+    }
+    Ok(())
+}
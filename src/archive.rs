@@ -0,0 +1,272 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use std::thread::{self, JoinHandle};
+use bzip2::read::MultiBzDecoder;
+use failure::Error;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use ruzstd::{FrameDecoder, StreamingDecoder};
+use tar::Builder;
+use process;
+
+const GZIP_MAGIC: &[u8] = &[0x1F, 0x8B];
+const XZ_MAGIC: &[u8] = &[0xFD, b'7', b'z', b'X', b'Z', 0x00];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xB5, 0x2F, 0xFD];
+const BZIP2_MAGIC: &[u8] = b"BZh";
+/// `ustar` at this offset marks both POSIX and GNU tar headers.
+const TAR_MAGIC_OFFSET: usize = 257;
+const TAR_MAGIC: &[u8] = b"ustar";
+
+/// The kinds of sources that a WSL distro can be registered from.
+///
+/// `WslRegisterDistribution` only accepts `TarGz`, so the others are turned into it first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SourceFormat {
+    TarGz,
+    Tar,
+    TarXz,
+    TarZst,
+    TarBz2,
+    /// A folder with the files of a WSL distro
+    Directory,
+}
+
+impl SourceFormat {
+    /// Tells the format of `path` from its first bytes, or returns `Directory` for a folder.
+    pub fn detect<P: AsRef<Path>>(path: P) -> Result<SourceFormat, Error> {
+        let path = path.as_ref();
+        if fs::metadata(path)?.is_dir() {
+            return Ok(SourceFormat::Directory);
+        }
+        let mut header = Vec::with_capacity(TAR_MAGIC_OFFSET + TAR_MAGIC.len());
+        File::open(path)?
+            .take((TAR_MAGIC_OFFSET + TAR_MAGIC.len()) as u64)
+            .read_to_end(&mut header)?;
+        if header.starts_with(GZIP_MAGIC) {
+            Ok(SourceFormat::TarGz)
+        } else if header.starts_with(XZ_MAGIC) {
+            Ok(SourceFormat::TarXz)
+        } else if header.starts_with(ZSTD_MAGIC) {
+            Ok(SourceFormat::TarZst)
+        } else if header.starts_with(BZIP2_MAGIC) {
+            Ok(SourceFormat::TarBz2)
+        } else if header[TAR_MAGIC_OFFSET.min(header.len())..].starts_with(TAR_MAGIC) {
+            Ok(SourceFormat::Tar)
+        } else {
+            Err(format_err!(
+                "{} is none of .tar, .tar.gz, .tar.xz, .tar.zst and .tar.bz2",
+                path.display()
+            ))
+        }
+    }
+}
+
+impl fmt::Display for SourceFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SourceFormat::TarGz => write!(f, ".tar.gz"),
+            SourceFormat::Tar => write!(f, ".tar"),
+            SourceFormat::TarXz => write!(f, ".tar.xz"),
+            SourceFormat::TarZst => write!(f, ".tar.zst"),
+            SourceFormat::TarBz2 => write!(f, ".tar.bz2"),
+            SourceFormat::Directory => write!(f, "folder"),
+        }
+    }
+}
+
+/// Removes `./` and `/` from the start and `/` from the end of a path in an archive.
+pub(crate) fn normalize_path(path: &str) -> String {
+    let mut path = path;
+    loop {
+        if let Some(rest) = path.strip_prefix("./") {
+            path = rest;
+        } else if let Some(rest) = path.strip_prefix('/') {
+            path = rest;
+        } else {
+            break;
+        }
+    }
+    if path == "." {
+        return String::new();
+    }
+    path.trim_end_matches('/').to_string()
+}
+
+/// Resolves `.` and `..` in a path in an archive and removes the leading `/`. Returns `None` if
+/// `..` goes above the top.
+///
+/// Unlike `normalize_path`, this changes what the path points to when a folder on the way is a
+/// symbolic link, so it is only for rewriting an archive, where each path needs one place.
+/// Checking and reading an archive keep `..` with `normalize_path` to see the path as written.
+pub(crate) fn resolve_path(path: &str) -> Option<String> {
+    let mut components = vec![];
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop()?;
+            }
+            _ => components.push(component),
+        }
+    }
+    Some(components.join("/"))
+}
+
+/// Reads every zstd frame of a reader one after another, as `zstd -d` does.
+struct MultiZstdDecoder<R: BufRead> {
+    decoder: Option<StreamingDecoder<R, FrameDecoder>>,
+    reader: Option<R>,
+}
+
+impl<R: BufRead> MultiZstdDecoder<R> {
+    fn new(reader: R) -> MultiZstdDecoder<R> {
+        MultiZstdDecoder {
+            decoder: None,
+            reader: Some(reader),
+        }
+    }
+}
+
+impl<R: BufRead> Read for MultiZstdDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let Some(ref mut decoder) = self.decoder {
+                let len = decoder.read(buf)?;
+                if len > 0 || buf.is_empty() {
+                    return Ok(len);
+                }
+            }
+            if let Some(decoder) = self.decoder.take() {
+                self.reader = Some(decoder.into_inner());
+            }
+            let mut reader = match self.reader.take() {
+                Some(reader) => reader,
+                None => return Ok(0),
+            };
+            if reader.fill_buf()?.is_empty() {
+                self.reader = Some(reader);
+                return Ok(0);
+            }
+            match StreamingDecoder::new(reader) {
+                Ok(decoder) => self.decoder = Some(decoder),
+                Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e.to_string())),
+            }
+        }
+    }
+}
+
+/// Returns a reader of the `.tar` archive in `reader`, which may be compressed with gzip, zstd or
+/// bzip2.
+pub fn decompress<'a, R: BufRead + 'a>(mut reader: R) -> io::Result<Box<dyn Read + 'a>> {
+    let header = reader.fill_buf()?;
+    if header.starts_with(GZIP_MAGIC) {
+        Ok(Box::new(MultiGzDecoder::new(reader)))
+    } else if header.starts_with(ZSTD_MAGIC) {
+        Ok(Box::new(MultiZstdDecoder::new(reader)))
+    } else if header.starts_with(BZIP2_MAGIC) {
+        Ok(Box::new(MultiBzDecoder::new(reader)))
+    } else if header.starts_with(XZ_MAGIC) {
+        Err(io::Error::new(io::ErrorKind::InvalidData, "xz cannot be read as a stream"))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Reads what a thread decodes from xz, which `lzma_rs` can only write, through a pipe.
+struct XzReader {
+    reader: File,
+    thread: Option<JoinHandle<Result<(), String>>>,
+}
+
+impl XzReader {
+    fn new(file: File) -> io::Result<XzReader> {
+        let (reader, mut writer) = process::pipe()?;
+        let thread = thread::spawn(move || {
+            lzma_rs::xz_decompress(&mut BufReader::new(file), &mut writer)
+                .map_err(|e| e.to_string())
+        });
+        Ok(XzReader {
+            reader,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Read for XzReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.reader.read(buf)?;
+        if len == 0 && !buf.is_empty() {
+            if let Some(thread) = self.thread.take() {
+                match thread.join() {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+                    Err(_) => return Err(io::Error::other("a thread decoding xz panicked")),
+                }
+            }
+        }
+        Ok(len)
+    }
+}
+
+/// Returns a reader of the `.tar` archive in `path`, whose format is `format`.
+pub fn open_tar<P: AsRef<Path>>(path: P, format: SourceFormat) -> Result<Box<dyn Read>, Error> {
+    let path = path.as_ref();
+    let file = File::open(path)?;
+    match format {
+        SourceFormat::TarXz => Ok(Box::new(XzReader::new(file)?)),
+        SourceFormat::Directory => Err(format_err!("{} is a folder", path.display())),
+        _ => Ok(decompress(BufReader::new(file))?),
+    }
+}
+
+/// Writes `src`, whose format is `format`, as a `.tar.gz` archive to `dest`.
+///
+/// A folder is archived with the paths relative to it, and `dest` must not be inside it.
+pub fn write_tar_gz<P, Q>(src: P, format: SourceFormat, dest: Q) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let (src, dest) = (src.as_ref(), dest.as_ref());
+    if format == SourceFormat::TarGz {
+        fs::copy(src, dest)?;
+        return Ok(());
+    }
+    if format == SourceFormat::Directory {
+        let dest_folder = fs::canonicalize(dest.parent().unwrap_or_else(|| Path::new(".")))?;
+        if dest_folder.starts_with(fs::canonicalize(src)?) {
+            return Err(format_err!("{} is inside {}", dest.display(), src.display()));
+        }
+    }
+    let mut encoder = GzEncoder::new(File::create(dest)?, Compression::default());
+    match format {
+        SourceFormat::TarGz => unreachable!(),
+        SourceFormat::Tar => {
+            io::copy(&mut File::open(src)?, &mut encoder)?;
+        }
+        SourceFormat::TarXz => {
+            lzma_rs::xz_decompress(&mut BufReader::new(File::open(src)?), &mut encoder)
+                .map_err(|e| format_err!("xz: {}", e))?;
+        }
+        SourceFormat::TarZst => {
+            io::copy(
+                &mut MultiZstdDecoder::new(BufReader::new(File::open(src)?)),
+                &mut encoder,
+            )?;
+        }
+        SourceFormat::TarBz2 => {
+            io::copy(&mut MultiBzDecoder::new(File::open(src)?), &mut encoder)?;
+        }
+        SourceFormat::Directory => {
+            let mut builder = Builder::new(&mut encoder);
+            builder.follow_symlinks(false);
+            builder.append_dir_all(".", src)?;
+            builder.finish()?;
+        }
+    }
+    encoder.finish()?.sync_all()?;
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use std::fs::File;
+use std::io::Write;
+use std::thread;
+use failure::Error;
+use distro::DistroConfiguration;
+use process::{self, LaunchStdio, Output, WslProcess};
+
+/// Operations that `yowsl` needs from the Windows Subsystem for Linux.
+///
+/// Failures reported by WSL itself are returned as a `WslError` inside `failure::Error`.
+///
+/// `Wslapi` implements this trait with `wslapi.dll`. `FakeBackend` implements it in memory so
+/// that code written against this trait can also be built and tested on non-Windows hosts.
+pub trait WslBackend {
+    type Process: WslProcess;
+
+    fn register_distro(&self, distro_name: &str, tar_gz_filename: &str) -> Result<(), Error>;
+
+    fn unregister_distro(&self, distro_name: &str) -> Result<(), Error>;
+
+    fn get_distro_configuration(&self, distro_name: &str)
+        -> Result<DistroConfiguration, Error>;
+
+    fn configure_distro(&self, distro_configuration: &DistroConfiguration) -> Result<(), Error>;
+
+    /// Launches an interactive process and returns its exit code.
+    fn launch(&self, distro_name: &str, command: &str, use_cwd: bool) -> Result<u32, Error>;
+
+    /// Launches a non-interactive process that uses `stdio` as its standard streams.
+    fn spawn(
+        &self,
+        distro_name: &str,
+        command: &str,
+        use_cwd: bool,
+        stdio: LaunchStdio,
+    ) -> Result<Self::Process, Error>;
+
+    fn is_distribution_registered(&self, distro_name: &str) -> Result<bool, Error>;
+
+    /// Runs `command` with an empty stdin and waits for it, capturing its stdout and stderr.
+    fn run_capture(&self, distro_name: &str, command: &str) -> Result<Output, Error> {
+        capture(self, distro_name, command, process::null_file()?)
+    }
+
+    /// Runs `command` with `input` as its stdin and waits for it, capturing its stdout and
+    /// stderr. Unlike the command line, stdin cannot be seen by other processes.
+    fn run_with_input(
+        &self,
+        distro_name: &str,
+        command: &str,
+        input: &[u8],
+    ) -> Result<Output, Error> {
+        let (stdin_reader, mut stdin_writer) = process::pipe()?;
+        let input = input.to_vec();
+        // Written in a thread, as the process may fill stdout before it reads all of stdin.
+        let written = thread::spawn(move || stdin_writer.write_all(&input));
+        let output = capture(self, distro_name, command, stdin_reader);
+        // The process may exit without reading stdin, so its exit code tells what happened.
+        let _ = written.join();
+        output
+    }
+}
+
+/// Runs `command` with `stdin` and waits for it, capturing its stdout and stderr.
+fn capture<B: WslBackend + ?Sized>(
+    backend: &B,
+    distro_name: &str,
+    command: &str,
+    stdin: File,
+) -> Result<Output, Error> {
+    let (stdout_reader, stdout_writer) = process::pipe()?;
+    let (stderr_reader, stderr_writer) = process::pipe()?;
+    let mut child = backend.spawn(
+        distro_name,
+        command,
+        false,
+        LaunchStdio {
+            stdin: Some(stdin),
+            stdout: Some(stdout_writer),
+            stderr: Some(stderr_writer),
+        },
+    )?;
+    let stdout = process::read_to_end_in_thread(stdout_reader);
+    let stderr = process::read_to_end_in_thread(stderr_reader);
+    let exit_code = child.wait()?;
+    Ok(Output {
+        exit_code,
+        stdout: process::join_read(stdout)?,
+        stderr: process::join_read(stderr)?,
+    })
+}
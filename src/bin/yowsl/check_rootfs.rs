@@ -0,0 +1,34 @@
+use std::path::Path;
+use clap::ArgMatches;
+use exit_code;
+use print_error;
+use yowsl::{self, Problem, Severity};
+
+/// Prints `problems` and tells whether any of them is an error.
+pub fn print_problems(problems: &[Problem]) -> bool {
+    for problem in problems {
+        eprintln!("{}", problem);
+    }
+    problems
+        .iter()
+        .any(|problem| problem.severity == Severity::Error)
+}
+
+pub fn run(matches: &ArgMatches) -> i32 {
+    let src = Path::new(matches.value_of("src").unwrap());
+    if !src.exists() {
+        eprintln!("\"{}\" does not exist", src.to_str().unwrap());
+        return exit_code::IO_ERROR;
+    }
+    let problems = match yowsl::check_rootfs(src) {
+        Ok(problems) => problems,
+        Err(e) => return print_error(&format!("I cannot check \"{}\"", src.display()), &e),
+    };
+    if print_problems(&problems) {
+        return exit_code::FAILURE;
+    }
+    if problems.is_empty() {
+        println!("\"{}\" has no problems", src.display());
+    }
+    exit_code::SUCCESS
+}
@@ -0,0 +1,50 @@
+use clap::ArgMatches;
+use exit_code;
+use new_backend;
+use print_error;
+use yowsl::{self, Registry, WslBackend};
+
+fn show<R: Registry>(registry: &R) -> i32 {
+    match yowsl::default_distro(registry) {
+        Ok(Some(distro)) => {
+            println!("{}", distro.configuration.name);
+            exit_code::SUCCESS
+        }
+        Ok(None) => {
+            eprintln!("There is no default WSL distro");
+            exit_code::NOT_REGISTERED
+        }
+        Err(e) => print_error("I cannot get the default WSL distro", &e),
+    }
+}
+
+fn set<B: WslBackend, R: Registry>(backend: &B, registry: &R, name: &str) -> i32 {
+    match backend.is_distribution_registered(name) {
+        Ok(true) => {}
+        Ok(false) => {
+            eprintln!("\"{}\" is not a registered WSL distro name", name);
+            return exit_code::NOT_REGISTERED;
+        }
+        Err(e) => {
+            return print_error(&format!("I cannot make \"{}\" the default", name), &e);
+        }
+    }
+    if let Err(e) = yowsl::set_default_distro(registry, name) {
+        return print_error(&format!("I cannot make \"{}\" the default", name), &e);
+    }
+    exit_code::SUCCESS
+}
+
+pub fn run<R: Registry>(registry: &R, matches: &ArgMatches) -> i32 {
+    let name = match matches.value_of("NAME") {
+        Some(name) => name,
+        None => return show(registry),
+    };
+    match new_backend() {
+        Ok(backend) => set(&backend, registry, name),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            exit_code::UNAVAILABLE
+        }
+    }
+}
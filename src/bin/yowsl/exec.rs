@@ -0,0 +1,204 @@
+use std::cmp;
+use std::fs::File;
+use std::time::{Duration, Instant};
+use clap::ArgMatches;
+use exit_code;
+use serde_json;
+use print_launch_error;
+use yowsl::{self, shell_quote, LaunchStdio, PipeReader, WslBackend, WslProcess};
+
+/// How long to wait for the rest of the output after a command is killed. A process that the
+/// command has left running may still hold the pipes, so they are not read to the end.
+const KILL_GRACE: Duration = Duration::from_millis(200);
+
+/// The result of `yowsl exec --json`.
+#[derive(Serialize)]
+struct ExecResult {
+    /// `None` if the command timed out
+    exit_code: Option<u32>,
+    stdout: String,
+    stderr: String,
+    /// Seconds
+    duration: f64,
+    timed_out: bool,
+}
+
+pub fn run<B: WslBackend>(backend: &B, matches: &ArgMatches) -> i32 {
+    let name = matches.value_of("NAME").unwrap();
+    match backend.is_distribution_registered(name) {
+        Ok(true) => {}
+        Ok(false) => {
+            eprintln!("\"{}\" is not a registered WSL distro name", name);
+            return exit_code::LAUNCH_ERROR;
+        }
+        Err(e) => {
+            return print_launch_error(&format!("I cannot execute a command in \"{}\"", name), &e);
+        }
+    }
+    let command = matches
+        .values_of("command")
+        .unwrap()
+        .map(shell_quote)
+        .collect::<Vec<_>>()
+        .join(" ");
+    let stdin = match matches.value_of("stdin") {
+        Some(path) => match File::open(path) {
+            Ok(file) => Some(file),
+            Err(e) => {
+                eprintln!("I cannot open \"{}\"\nError: {}", path, e);
+                return exit_code::LAUNCH_ERROR;
+            }
+        },
+        None => None,
+    };
+    let timeout = matches
+        .value_of("timeout")
+        .map(|timeout| Duration::from_secs_f64(timeout.parse().unwrap()));
+    let json = matches.is_present("json");
+    let mut stdio = LaunchStdio {
+        stdin,
+        stdout: None,
+        stderr: None,
+    };
+    let mut readers = None;
+    if json {
+        let pipes = yowsl::pipe().and_then(|stdout| yowsl::pipe().map(|stderr| (stdout, stderr)));
+        let ((stdout_reader, stdout_writer), (stderr_reader, stderr_writer)) = match pipes {
+            Ok(pipes) => pipes,
+            Err(e) => {
+                return print_launch_error(
+                    &format!("I cannot execute a command in \"{}\"", name),
+                    &e.into(),
+                );
+            }
+        };
+        stdio.stdout = Some(stdout_writer);
+        stdio.stderr = Some(stderr_writer);
+        readers = Some((stdout_reader, stderr_reader));
+    }
+    let start = Instant::now();
+    let mut process = match backend.spawn(name, &command, matches.is_present("use_cwd"), stdio) {
+        Ok(process) => process,
+        Err(e) => {
+            return print_launch_error(&format!("I cannot execute a command in \"{}\"", name), &e);
+        }
+    };
+    let readers =
+        readers.map(|(stdout, stderr)| (PipeReader::new(stdout), PipeReader::new(stderr)));
+    let result = match timeout {
+        Some(timeout) => process.wait_timeout(timeout),
+        None => process.wait().map(Some),
+    };
+    let child_exit_code = match result {
+        Ok(Some(exit_code)) => Some(exit_code),
+        Ok(None) => {
+            if let Err(e) = process.kill().and_then(|_| process.wait()) {
+                print_launch_error(&format!("I cannot stop a command in \"{}\"", name), &e);
+            }
+            None
+        }
+        Err(e) => {
+            return print_launch_error(&format!("I cannot execute a command in \"{}\"", name), &e);
+        }
+    };
+    let duration = start.elapsed();
+    if let Some((stdout, stderr)) = readers {
+        // With a timeout, the output is only waited for until then, or shortly after a kill.
+        let deadline = timeout.map(|timeout| {
+            let now = Instant::now();
+            match child_exit_code {
+                Some(_) => cmp::max(start + timeout, now + KILL_GRACE),
+                None => now + KILL_GRACE,
+            }
+        });
+        let (stdout, stderr) = match (stdout.finish(deadline), stderr.finish(deadline)) {
+            (Ok(stdout), Ok(stderr)) => (stdout, stderr),
+            (Err(e), _) | (_, Err(e)) => {
+                return print_launch_error(
+                    &format!("I cannot read the output from \"{}\"", name),
+                    &e,
+                );
+            }
+        };
+        let exec_result = ExecResult {
+            exit_code: child_exit_code,
+            stdout: String::from_utf8_lossy(&stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&stderr).into_owned(),
+            duration: duration.as_secs_f64(),
+            timed_out: child_exit_code.is_none(),
+        };
+        println!("{}", serde_json::to_string(&exec_result).unwrap());
+    }
+    match child_exit_code {
+        Some(child_exit_code) => child_exit_code as i32,
+        None => {
+            eprintln!(
+                "The command in \"{}\" timed out after {:.3} seconds",
+                name,
+                duration.as_secs_f64()
+            );
+            exit_code::TIMEOUT
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::process::Command;
+    use std::time::{Duration, Instant};
+    use super::run;
+    use exit_code;
+    use sub_matches;
+    use temp_dir;
+    use yowsl::{self, FakeBackend, SimulatorBackend, SourceFormat, WslBackend};
+
+    #[test]
+    fn run_reports_its_own_errors_apart_from_the_command() {
+        let backend = FakeBackend::new();
+        let matches = sub_matches(&["exec", "Ubuntu", "--", "true"]);
+        assert_eq!(run(&backend, &matches), exit_code::LAUNCH_ERROR);
+        backend.register_distro("Ubuntu", "rootfs.tar.gz").unwrap();
+        let matches = sub_matches(&["exec", "Ubuntu", "-i", "/nonexistent/stdin", "--", "true"]);
+        assert_eq!(run(&backend, &matches), exit_code::LAUNCH_ERROR);
+        backend.set_launch_exit_code(exit_code::NOT_REGISTERED as u32);
+        let matches = sub_matches(&["exec", "Ubuntu", "--", "true"]);
+        assert_eq!(run(&backend, &matches), exit_code::NOT_REGISTERED);
+        assert_eq!(backend.launches().len(), 1);
+    }
+
+    #[test]
+    fn run_stops_waiting_for_processes_left_running_after_a_timeout() {
+        let dir = temp_dir("exec");
+        fs::create_dir_all(dir.join("rootfs/etc")).unwrap();
+        fs::write(dir.join("rootfs/etc/passwd"), "root:x:0:0::/root:/bin/sh\n").unwrap();
+        let tar_gz = dir.join("rootfs.tar.gz");
+        yowsl::write_tar_gz(dir.join("rootfs"), SourceFormat::Directory, &tar_gz).unwrap();
+        let backend = SimulatorBackend::new(dir.join("simulator"))
+            .unwrap()
+            .with_responder(|launch| {
+                let mut command = Command::new("sh");
+                command.current_dir(launch.rootfs).arg("-c").arg(launch.command);
+                command
+            });
+        backend.register_distro("Ubuntu", tar_gz.to_str().unwrap()).unwrap();
+        let matches = sub_matches(&["exec", "Ubuntu", "--json", "--", "sh", "-c", "exit 3"]);
+        // 3 is NOT_REGISTERED too, but `exec` only reports its own errors as LAUNCH_ERROR.
+        assert_eq!(run(&backend, &matches), 3);
+        let matches = sub_matches(&[
+            "exec",
+            "Ubuntu",
+            "-t",
+            "0.5",
+            "--json",
+            "--",
+            "sh",
+            "-c",
+            "sleep 5 & sleep 5",
+        ]);
+        let start = Instant::now();
+        assert_eq!(run(&backend, &matches), exit_code::TIMEOUT);
+        assert!(start.elapsed() < Duration::from_secs(3));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,36 @@
+// Exit codes of yowsl.exe. `launch` and `exec` exit with the exit code of the command once it
+// runs in the WSL distro, so they report their own errors only as `LAUNCH_ERROR`.
+
+/// Succeeded
+pub const SUCCESS: i32 = 0;
+/// The Windows Subsystem for Linux or another operation failed
+pub const FAILURE: i32 = 1;
+/// The arguments are invalid
+pub const USAGE: i32 = 2;
+/// The WSL distro is not registered
+pub const NOT_REGISTERED: i32 = 3;
+/// The WSL distro is already registered
+pub const ALREADY_REGISTERED: i32 = 4;
+/// A file or a folder cannot be used
+pub const IO_ERROR: i32 = 5;
+/// The Windows Subsystem for Linux is not available
+pub const UNAVAILABLE: i32 = 6;
+/// The command in the WSL distro timed out
+pub const TIMEOUT: i32 = 124;
+/// `launch` or `exec` failed in yowsl.exe, for example because the WSL distro is not registered
+pub const LAUNCH_ERROR: i32 = 125;
+
+/// The list of exit codes shown in `yowsl.exe --help`.
+pub const HELP: &str = "EXIT CODES:
+    0      Succeeded
+    1      The Windows Subsystem for Linux or another operation failed
+    2      The arguments are invalid
+    3      The WSL distro is not registered
+    4      The WSL distro is already registered
+    5      A file or a folder cannot be used
+    6      The Windows Subsystem for Linux is not available
+    124    The command in the WSL distro timed out (exec)
+    125    launch or exec failed in yowsl.exe, which prints why
+`launch` and `exec` exit with 124, 125 or the exit code of the command, and only use 125 for
+their own errors. A command may exit with any of these codes, so use `exec --json` to tell them
+apart.";
@@ -1,23 +1,76 @@
+use std::collections::BTreeMap;
 use clap::ArgMatches;
-use yowsl::Wslapi;
+use exit_code;
+use print_error;
+use yowsl::{self, Format, WslBackend};
 
-pub fn run(wslapi: &Wslapi, matches: &ArgMatches) {
+pub fn run<B: WslBackend>(backend: &B, matches: &ArgMatches) -> i32 {
     let name = matches.value_of("NAME").unwrap();
-    match wslapi.is_distribution_registered(name) {
+    let format: Format = matches.value_of("format").unwrap().parse().unwrap();
+    match backend.is_distribution_registered(name) {
         Ok(true) => {}
         Ok(false) => {
             eprintln!("\"{}\" is not a registered WSL distro name", name);
-            return;
+            return exit_code::NOT_REGISTERED;
         }
         Err(e) => {
-            eprintln!("I cannot get a configuration of \"{}\"\nError: {}", name, e);
-            return;
+            return print_error(&format!("I cannot get a configuration of \"{}\"", name), &e);
         }
     }
-    match wslapi.get_distro_configuration(name) {
-        Ok(distro_configuration) => println!("{}", distro_configuration.to_toml()),
+    match backend.get_distro_configuration(name) {
+        Ok(distro_configuration) => {
+            let mut default_users = BTreeMap::new();
+            if matches.is_present("user_name") {
+                // The user name is only a help to read the ID, so it is left out if the WSL
+                // distro cannot run `cat`.
+                match yowsl::read_passwd(backend, name) {
+                    Ok(passwd) => {
+                        let uid = distro_configuration.default_uid;
+                        if let Some(user) = yowsl::user_name_of(&passwd, uid) {
+                            default_users.insert(name.to_string(), user);
+                        }
+                    }
+                    Err(e) => eprintln!(
+                        "Warning: I cannot read /etc/passwd of \"{}\"\nError: {}",
+                        name, e
+                    ),
+                }
+            }
+            match format.serialize_with_default_users(&[distro_configuration], &default_users) {
+                Ok(s) => print!("{}", s),
+                Err(e) => {
+                    return print_error(&format!("I cannot print \"{}\" as {}", name, format), &e);
+                }
+            }
+        }
         Err(e) => {
-            eprintln!("I cannot get a configuration of \"{}\"\nError: {}", name, e);
+            return print_error(&format!("I cannot get a configuration of \"{}\"", name), &e);
         }
     }
+    exit_code::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run;
+    use exit_code;
+    use sub_matches;
+    use yowsl::{FakeBackend, WslBackend};
+
+    #[test]
+    fn run_needs_a_registered_distro() {
+        let backend = FakeBackend::new();
+        let matches = sub_matches(&["get-configuration", "Ubuntu", "--format", "json"]);
+        assert_eq!(run(&backend, &matches), exit_code::NOT_REGISTERED);
+        backend.register_distro("Ubuntu", "rootfs.tar.gz").unwrap();
+        assert_eq!(run(&backend, &matches), exit_code::SUCCESS);
+        assert!(backend.launches().is_empty());
+        let matches =
+            sub_matches(&["get-configuration", "Ubuntu", "--format", "json", "--user-name"]);
+        assert_eq!(run(&backend, &matches), exit_code::SUCCESS);
+        assert_eq!(backend.launches()[0].command, "cat /etc/passwd");
+        // Without /etc/passwd the configuration is printed without the user name.
+        backend.set_launch_exit_code(1);
+        assert_eq!(run(&backend, &matches), exit_code::SUCCESS);
+    }
 }
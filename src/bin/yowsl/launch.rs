@@ -1,25 +1,53 @@
 use clap::ArgMatches;
-use yowsl::Wslapi;
+use exit_code;
+use print_launch_error;
+use yowsl::WslBackend;
 
-pub fn run(wslapi: &Wslapi, matches: &ArgMatches) {
+pub fn run<B: WslBackend>(backend: &B, matches: &ArgMatches) -> i32 {
     let name = matches.value_of("NAME").unwrap();
-    match wslapi.is_distribution_registered(name) {
+    match backend.is_distribution_registered(name) {
         Ok(true) => {}
         Ok(false) => {
             eprintln!("\"{}\" is not a registered WSL distro name", name);
-            return;
+            return exit_code::LAUNCH_ERROR;
         }
         Err(e) => {
-            eprintln!("I cannot launch \"{}\"\nError: {}", name, e);
-            return;
+            return print_launch_error(&format!("I cannot launch \"{}\"", name), &e);
         }
     }
-    let command = match matches.value_of("command") {
-        Some(command) => command,
-        None => "",
-    };
+    let command = matches.value_of("command").unwrap_or_default();
     let use_cwd = matches.is_present("use_cwd");
-    if let Err(e) = wslapi.launch(name, command, use_cwd) {
-        eprintln!("I cannot launch \"{}\"\nError: {}", name, e);
+    match backend.launch(name, command, use_cwd) {
+        Ok(exit_code) => exit_code as i32,
+        Err(e) => print_launch_error(&format!("I cannot launch \"{}\"", name), &e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run;
+    use exit_code;
+    use sub_matches;
+    use yowsl::{FakeBackend, FakeLaunch, WslBackend};
+
+    #[test]
+    fn run_forwards_the_exit_code_of_the_command() {
+        let backend = FakeBackend::new();
+        let matches = sub_matches(&["launch", "Ubuntu", "-c", "exit 42", "-u"]);
+        assert_eq!(run(&backend, &matches), exit_code::LAUNCH_ERROR);
+        assert!(backend.launches().is_empty());
+        backend.register_distro("Ubuntu", "rootfs.tar.gz").unwrap();
+        backend.set_launch_exit_code(42);
+        assert_eq!(run(&backend, &matches), 42);
+        assert_eq!(
+            backend.launches(),
+            vec![FakeLaunch {
+                distro_name: "Ubuntu".to_string(),
+                command: "exit 42".to_string(),
+                use_cwd: true,
+                default_uid: 0,
+                stdin: vec![],
+            }]
+        );
     }
 }
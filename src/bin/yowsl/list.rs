@@ -0,0 +1,154 @@
+use clap::ArgMatches;
+use serde_json;
+use serde_yaml;
+use exit_code;
+use print_error;
+use yowsl::{self, Format, LxssDistro, Registry};
+
+/// The formats of `yowsl list --format`, which are `table` and `Format::NAMES`.
+pub const FORMATS: [&str; 5] = ["table", "toml", "json", "yaml", "env"];
+
+/// A WSL distro as printed by `yowsl list --format json` or `--format yaml`.
+#[derive(Serialize)]
+struct ListedDistro<'a> {
+    name: &'a str,
+    guid: &'a str,
+    base_path: &'a str,
+    state: String,
+    version: u32,
+    default_uid: u32,
+    flags: u32,
+    default_environment_variables: &'a [String],
+    is_default: bool,
+}
+
+impl<'a> From<&'a LxssDistro> for ListedDistro<'a> {
+    fn from(distro: &'a LxssDistro) -> ListedDistro<'a> {
+        ListedDistro {
+            name: &distro.configuration.name,
+            guid: &distro.guid,
+            base_path: &distro.base_path,
+            state: distro.state.to_string(),
+            version: distro.configuration.version,
+            default_uid: distro.configuration.default_uid,
+            flags: distro.configuration.flags.bits(),
+            default_environment_variables: &distro.configuration.default_environment_variables,
+            is_default: distro.is_default,
+        }
+    }
+}
+
+/// Prints `distros` as a table whose columns are as wide as their widest cell. The default
+/// WSL distro is marked with `*`.
+fn print_table(distros: &[LxssDistro]) {
+    let header = ["NAME", "STATE", "VERSION", "UID", "FLAGS", "GUID", "BASE PATH"];
+    let rows = distros
+        .iter()
+        .map(|distro| {
+            let marker = if distro.is_default { "* " } else { "  " };
+            vec![
+                format!("{}{}", marker, distro.configuration.name),
+                distro.state.to_string(),
+                distro.configuration.version.to_string(),
+                distro.configuration.default_uid.to_string(),
+                format!("{:#X}", distro.configuration.flags.bits()),
+                distro.guid.clone(),
+                distro.base_path.clone(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let header = header
+        .iter()
+        .enumerate()
+        .map(|(i, cell)| if i == 0 { format!("  {}", cell) } else { cell.to_string() })
+        .collect::<Vec<_>>();
+    let widths = (0..header.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain(Some(header[i].len()))
+                .max()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    for row in Some(&header).into_iter().chain(&rows) {
+        let cells = row.iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{:1$}", cell, width))
+            .collect::<Vec<_>>();
+        println!("{}", cells.join("  ").trim_end());
+    }
+}
+
+pub fn run<R: Registry>(registry: &R, matches: &ArgMatches) -> i32 {
+    let distros = match yowsl::read_distros(registry) {
+        Ok(distros) => distros,
+        Err(e) => return print_error("I cannot list WSL distros", &e),
+    };
+    let distros = distros
+        .into_iter()
+        .filter_map(|distro| match distro {
+            Ok(distro) => Some(distro),
+            Err(e) => {
+                eprintln!("Warning: I cannot read a WSL distro, so it is not listed\nError: {}", e);
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    let format = matches.value_of("format").unwrap();
+    if format.eq_ignore_ascii_case("table") {
+        print_table(&distros);
+        return exit_code::SUCCESS;
+    }
+    let listed_distros = distros.iter().map(ListedDistro::from).collect::<Vec<_>>();
+    // JSON and YAML also show where each WSL distro is. TOML and shell variables are what
+    // `get-configuration` prints, so that `set-configuration --from-file` can read them back.
+    match format.parse().unwrap() {
+        Format::Json => println!("{}", serde_json::to_string_pretty(&listed_distros).unwrap()),
+        Format::Yaml => print!("{}", serde_yaml::to_string(&listed_distros).unwrap()),
+        format => {
+            let distro_configurations = distros
+                .into_iter()
+                .map(|distro| distro.configuration)
+                .collect::<Vec<_>>();
+            match format.serialize(&distro_configurations) {
+                Ok(s) => print!("{}", s),
+                Err(e) => {
+                    return print_error(&format!("I cannot list WSL distros as {}", format), &e);
+                }
+            }
+        }
+    }
+    exit_code::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run, FORMATS};
+    use exit_code;
+    use sub_matches;
+    use yowsl::{MemoryRegistry, RegFile, RegistryValue, LXSS_KEY};
+
+    #[test]
+    fn run_prints_every_format() {
+        let registry = RegFile::open("tests/fixtures/lxss.reg").unwrap();
+        for format in FORMATS.iter().chain(&["TOML"]).filter(|&&format| format != "env") {
+            let matches = sub_matches(&["list", "--format", format]);
+            assert_eq!(run(&registry, &matches), exit_code::SUCCESS, "{}", format);
+        }
+        // The variables of one WSL distro would overwrite those of another.
+        let matches = sub_matches(&["list", "--format", "env"]);
+        assert_eq!(run(&registry, &matches), exit_code::FAILURE);
+    }
+
+    #[test]
+    fn run_skips_a_wsl_distro_that_cannot_be_read() {
+        let registry = MemoryRegistry::new();
+        let ubuntu = format!("{}\\{{0a1b2c3d-0000-0000-0000-000000000001}}", LXSS_KEY);
+        registry.insert_value(&ubuntu, "DistributionName", RegistryValue::Sz("Ubuntu".into()));
+        registry.insert_value(&ubuntu, "BasePath", RegistryValue::Sz("C:\\WSL".into()));
+        registry.insert_key(&format!("{}\\{{0a1b2c3d-0000-0000-0000-000000000002}}", LXSS_KEY));
+        let matches = sub_matches(&["list", "--format", "table"]);
+        assert_eq!(run(&registry, &matches), exit_code::SUCCESS);
+    }
+}
@@ -1,7 +1,10 @@
-#![cfg(all(target_arch = "x86_64", target_os = "windows"))]
-
 #[macro_use]
 extern crate clap;
+extern crate failure;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate serde_yaml;
 extern crate yowsl;
 
 mod register;
@@ -9,71 +12,318 @@ mod unregister;
 mod get_configuration;
 mod set_configuration;
 mod launch;
+mod exec;
+mod list;
+mod default_distro;
+mod rename;
+mod move_distro;
+mod check_rootfs;
+mod normalize;
+mod exit_code;
+
+#[cfg(not(all(target_arch = "x86_64", target_os = "windows")))]
+use std::env;
+use std::{io, process};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use yowsl::{FileAddition, Format, Registry, UserAddition, WslBackend, WslError};
+#[cfg(all(target_arch = "x86_64", target_os = "windows"))]
+use yowsl::{WindowsRegistry, Wslapi};
+#[cfg(not(all(target_arch = "x86_64", target_os = "windows")))]
+use yowsl::{RegFile, SimulatorBackend};
+
+/// Prints `message` and `e` followed by a hint to fix `e` if there is one, and returns the exit
+/// code for `e`.
+fn print_error(message: &str, e: &failure::Error) -> i32 {
+    eprintln!("{}\nError: {}", message, e);
+    if let Some(hint) = e.downcast_ref::<WslError>().and_then(WslError::hint) {
+        eprintln!("Hint: {}", hint);
+    }
+    match e.downcast_ref::<WslError>() {
+        Some(&WslError::SubsystemNotPresent)
+        | Some(&WslError::SubsystemUpdateRequired)
+        | Some(&WslError::ClassNotRegistered)
+        | Some(&WslError::ServiceDisabled)
+        | Some(&WslError::VirtualMachinePlatformNotEnabled) => exit_code::UNAVAILABLE,
+        // `register` checks the name first, but the WSL distro may be registered in the meantime.
+        Some(&WslError::AlreadyExists) => exit_code::ALREADY_REGISTERED,
+        Some(&WslError::FileNotFound)
+        | Some(&WslError::PathNotFound)
+        | Some(&WslError::AccessDenied) => exit_code::IO_ERROR,
+        Some(_) => exit_code::FAILURE,
+        None if e.downcast_ref::<io::Error>().is_some() => exit_code::IO_ERROR,
+        None => exit_code::FAILURE,
+    }
+}
 
-use clap::{App, AppSettings, Arg, SubCommand};
-use yowsl::Wslapi;
+/// Prints `message` and `e` like `print_error` and returns `LAUNCH_ERROR`, as `launch` and
+/// `exec` leave the other exit codes to the command.
+fn print_launch_error(message: &str, e: &failure::Error) -> i32 {
+    print_error(message, e);
+    exit_code::LAUNCH_ERROR
+}
 
-#[cfg_attr(feature = "cargo-clippy", allow(needless_pass_by_value))]
+#[allow(clippy::needless_pass_by_value)]
 fn default_uid_validator(s: String) -> Result<(), String> {
-    if s.parse::<u64>().is_ok() {
+    if s.parse::<u32>().is_ok() {
         Ok(())
     } else {
-        Err("A 64-bit unsigned integer is expected".to_string())
+        Err("A 32-bit unsigned integer is expected".to_string())
     }
 }
 
-#[cfg_attr(feature = "cargo-clippy", allow(needless_pass_by_value))]
+#[allow(clippy::needless_pass_by_value)]
 fn flags_validator(s: String) -> Result<(), String> {
-    if s.len() == 3 && s.chars().all(|c| c == '0' || c == '1') {
-        Ok(())
-    } else {
-        Err("3 binary digits are expected".to_string())
+    set_configuration::parse_flags(&s)
+        .map(|_| ())
+        .map_err(|e| format!("{}. Flags such as interop,ntpath,mount or 0x7 are expected", e))
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn name_validator(s: String) -> Result<(), String> {
+    yowsl::validate_distro_name(&s).map_err(|e| e.to_string())
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn file_addition_validator(s: String) -> Result<(), String> {
+    s.parse::<FileAddition>().map(|_| ()).map_err(|e| e.to_string())
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn user_addition_validator(s: String) -> Result<(), String> {
+    s.parse::<UserAddition>().map(|_| ()).map_err(|e| e.to_string())
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn user_name_validator(s: String) -> Result<(), String> {
+    yowsl::validate_user_name(&s).map_err(|e| e.to_string())
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn password_hash_validator(s: String) -> Result<(), String> {
+    yowsl::validate_password_hash(&s).map_err(|e| e.to_string())
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn timeout_validator(s: String) -> Result<(), String> {
+    match s.parse::<f64>() {
+        Ok(timeout) if timeout.is_finite() && timeout > 0.0 => Ok(()),
+        _ => Err("A positive number of seconds is expected".to_string()),
     }
 }
 
-fn run() {
-    let matches = App::new("yowsl")
+/// `--format` of the commands that print WSL distros.
+fn format_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::from_usage("[format] --format <format> 'The output format'")
+        .possible_values(&Format::NAMES)
+        .case_insensitive(true)
+        .default_value("toml")
+}
+
+/// The command line of `yowsl.exe`.
+fn app<'a, 'b>() -> App<'a, 'b> {
+    App::new("yowsl")
         .version(crate_version!())
         .author(crate_authors!())
         .about("Yet another Windows Subsystem for Linux tweaker")
-        .global_settings(&[
-            AppSettings::ArgRequiredElseHelp,
-            AppSettings::DeriveDisplayOrder,
-        ])
+        .after_help(exit_code::HELP)
+        .setting(AppSettings::ArgRequiredElseHelp)
+        .global_setting(AppSettings::DeriveDisplayOrder)
         .subcommand(
             SubCommand::with_name("register")
+                .setting(AppSettings::ArgRequiredElseHelp)
                 .about("Registers a WSL distro")
-                .usage("yowsl.exe register <NAME> -s <source> -d <destination>")
+                .usage(
+                    "yowsl.exe register <NAME> -s <source> -d <destination> \
+[--add-file <file>]... [--add-user <user>]... [--default-user <user>] [--normalize] [--no-check] \
+[--user <user> [--password-hash <hash> | --no-password] [--sudo]]
+    yowsl.exe register <NAME> --oci <image> [--tag <tag>] [--platform <platform>] \
+-d <destination> [--add-file <file>]... [--add-user <user>]... [--default-user <user>] \
+[--no-check] [--user <user> [--password-hash <hash> | --no-password] [--sudo]]",
+                )
                 .arg(Arg::from_usage("<NAME> 'A WSL distro name to register'"))
+                .arg(
+                    Arg::from_usage(
+                        "[src] -s, --src <source>\
+'A .tar.gz, .tar, .tar.xz, .tar.zst or .tar.bz2 archive, or a folder, that contains all files \
+using a WSL distro'",
+                    ).required_unless("oci"),
+                )
+                .arg(
+                    Arg::from_usage(
+                        "[oci] --oci <image>\
+'A docker save archive or an OCI image layout to flatten into a WSL distro'",
+                    ).conflicts_with("src"),
+                )
+                .arg(
+                    Arg::from_usage("[tag] --tag <tag> 'The tag of the image to register'")
+                        .requires("oci"),
+                )
+                .arg(
+                    Arg::from_usage(
+                        "[platform] --platform <platform>\
+'The platform such as linux/amd64 of the image to register'",
+                    ).requires("oci"),
+                )
+                .arg(Arg::from_usage(
+                    "<dest> -d, --dest <destination> 'A folder to register a WSL distro'",
+                ))
+                .arg(
+                    Arg::from_usage(
+                        "[add_file] --add-file <file>...\
+'Adds a file as host_path:/guest/path[:mode[:uid:gid]], such as wsl.conf:/etc/wsl.conf:644'",
+                    ).number_of_values(1)
+                        .validator(file_addition_validator),
+                )
+                .arg(
+                    Arg::from_usage(
+                        "[add_user] --add-user <user>...\
+'Adds a user with a locked password as name:uid[:groups], such as alice:1000:sudo,adm'",
+                    ).number_of_values(1)
+                        .validator(user_addition_validator),
+                )
                 .arg(Arg::from_usage(
-                    "<src> -s, --src <source>\
-'A .tar.gz archive that contains all files using a WSL distro'",
+                    "[default_user] --default-user <user>\
+'The default Linux user name, which is looked up in /etc/passwd of the files to register'",
                 ))
+                .arg(
+                    Arg::from_usage(
+                        "[normalize] --normalize 'Normalizes the archive first, as normalize does'",
+                    ).conflicts_with("oci"),
+                )
                 .arg(Arg::from_usage(
-                    "<dest> -d, --dest <destination> 'A folder to register a WSL distro'",
+                    "[no_check] --no-check 'Registers without checking the files for problems'",
+                ))
+                .arg(
+                    Arg::from_usage(
+                        "[user] --user <user>\
+'Creates a user after registering, asks for its password and makes it the default user'",
+                    ).conflicts_with("default_user")
+                        .validator(user_name_validator),
+                )
+                .arg(
+                    Arg::from_usage(
+                        "[password_hash] --password-hash <hash>\
+'Sets the password of --user to a hash such as the output of openssl passwd -6 instead of asking'",
+                    ).requires("user")
+                        .validator(password_hash_validator),
+                )
+                .arg(
+                    Arg::from_usage(
+                        "[no_password] --no-password 'Leaves --user without a password instead of \
+asking'",
+                    ).requires("user")
+                        .conflicts_with("password_hash"),
+                )
+                .arg(
+                    Arg::from_usage("[sudo] --sudo 'Adds --user to sudo or wheel'")
+                        .requires("user"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("check-rootfs")
+                .setting(AppSettings::ArgRequiredElseHelp)
+                .about("Checks the files of a WSL distro for problems before registering them")
+                .usage("yowsl.exe check-rootfs <source>")
+                .arg(Arg::from_usage(
+                    "<src> 'A .tar.gz, .tar, .tar.xz, .tar.zst or .tar.bz2 archive, or a folder'",
                 )),
         )
+        .subcommand(
+            SubCommand::with_name("normalize")
+                .setting(AppSettings::ArgRequiredElseHelp)
+                .about(
+                    "Rewrites an archive into a .tar.gz archive that registers without common \
+problems",
+                )
+                .usage("yowsl.exe normalize <source> <destination>")
+                .after_help(
+                    "Hard links to outside of the archive are dropped, and so are their files, as \
+the archive does not have them. Symbolic links keep their targets, even if a target is renamed \
+with a ~1 suffix because its path differs from another only in case.",
+                )
+                .arg(Arg::from_usage(
+                    "<src> 'A .tar.gz, .tar, .tar.xz, .tar.zst or .tar.bz2 archive to normalize'",
+                ))
+                .arg(Arg::from_usage("<dest> 'A .tar.gz archive to write'")),
+        )
         .subcommand(
             SubCommand::with_name("unregister")
+                .setting(AppSettings::ArgRequiredElseHelp)
                 .about("Unregisters a WSL distro")
                 .usage("yowsl.exe unregister <NAME>")
                 .arg(Arg::from_usage("<NAME> 'A WSL distro name to unregister'")),
         )
+        .subcommand(
+            SubCommand::with_name("list")
+                .setting(AppSettings::ArgRequiredElseHelp)
+                .about("Lists registered WSL distros. The default one is marked with *")
+                .usage("yowsl.exe list [--format <format>]")
+                .arg(
+                    Arg::from_usage(
+                        "[format] --format <format> 'The output format. env holds only one WSL \
+distro'",
+                    )
+                    .possible_values(&list::FORMATS)
+                    .case_insensitive(true)
+                    .default_value("table"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("default")
+                .about("Shows the default WSL distro, or makes a WSL distro the default")
+                .usage("yowsl.exe default [NAME]")
+                .arg(Arg::from_usage("[NAME] 'A WSL distro name to make the default'")),
+        )
+        .subcommand(
+            SubCommand::with_name("rename")
+                .setting(AppSettings::ArgRequiredElseHelp)
+                .about("Renames a WSL distro and keeps its files")
+                .usage("yowsl.exe rename <OLD> <NEW>")
+                .after_help(
+                    "The launcher that register leaves in the folder of the WSL distro, named \
+yowsl.exe or <OLD>.exe after the yowsl.exe that registered it, is renamed to <NEW>.exe. The \
+running yowsl.exe is kept as it is.",
+                )
+                .arg(Arg::from_usage("<OLD> 'A WSL distro name to rename'"))
+                .arg(Arg::from_usage("<NEW> 'A new WSL distro name'").validator(name_validator)),
+        )
+        .subcommand(
+            SubCommand::with_name("move")
+                .setting(AppSettings::ArgRequiredElseHelp)
+                .about("Moves the folder of a WSL distro")
+                .usage("yowsl.exe move <NAME> <destination>")
+                .arg(Arg::from_usage("<NAME> 'A WSL distro name to move'"))
+                .arg(Arg::from_usage(
+                    "<dest> 'A missing or empty folder to move the WSL distro to'",
+                )),
+        )
         .subcommand(
             SubCommand::with_name("get-configuration")
-                .about("Gets a configuration of a WSL distro and prints it as TOML")
-                .usage("yowsl.exe get-configuration <NAME>")
+                .setting(AppSettings::ArgRequiredElseHelp)
+                .about("Gets a configuration of a WSL distro and prints it")
+                .usage("yowsl.exe get-configuration <NAME> [--format <format>] [--user-name]")
                 .arg(Arg::from_usage(
                     "<NAME> 'A WSL distro name to get a configuration'",
+                ))
+                .arg(format_arg())
+                .arg(Arg::from_usage(
+                    "[user_name] --user-name \
+'Also prints the default user name from /etc/passwd, which starts the WSL distro'",
                 )),
         )
         .subcommand(
             SubCommand::with_name("set-configuration")
+                .setting(AppSettings::ArgRequiredElseHelp)
                 .about("Sets a configuration of a WSL distro")
-                .usage("yowsl.exe set-configuration <NAME> [-d <default_uid>] [-f <flags>]")
-                .arg(Arg::from_usage(
-                    "<NAME> 'A WSL distro name to set a configuration'",
-                ))
+                .usage(
+                    "yowsl.exe set-configuration <NAME> [-d <default_uid> | --default-user <user>] \
+[-f <flags>] [--enable <flags>]... [--disable <flags>]...
+    yowsl.exe set-configuration [NAME] --from-file <file>",
+                )
+                .arg(
+                    Arg::from_usage("[NAME] 'A WSL distro name to set a configuration'")
+                        .required_unless("from_file"),
+                )
                 .arg(
                     Arg::from_usage(
                         "[default_uid] -d, --default-uid <default_uid>\
@@ -82,12 +332,45 @@ fn run() {
                 )
                 .arg(
                     Arg::from_usage(
-                        "[flags] -f, --flags <flags> 'Flags (3 binary digits) for this WSL distro'",
+                        "[default_user] --default-user <user>\
+'The default Linux user name for this WSL distro, which is looked up in its /etc/passwd'",
+                    ).conflicts_with("default_uid"),
+                )
+                .arg(
+                    Arg::from_usage(
+                        "[flags] -f, --flags <flags>\
+'Flags for this WSL distro: names such as ENABLE_INTEROP|APPEND_NT_PATH or interop,ntpath,mount, \
+a number such as 3 or 0x3, or 3 binary digits such as 011'",
                     ).validator(flags_validator),
+                )
+                .arg(
+                    Arg::from_usage(
+                        "[enable] --enable <flags>... 'Turns on flags and keeps the others'",
+                    ).number_of_values(1)
+                        .validator(flags_validator),
+                )
+                .arg(
+                    Arg::from_usage(
+                        "[disable] --disable <flags>... 'Turns off flags and keeps the others'",
+                    ).number_of_values(1)
+                        .validator(flags_validator),
+                )
+                .arg(
+                    Arg::from_usage(
+                        "[from_file] --from-file <file>\
+'Applies a TOML file from get-configuration. Without NAME, every WSL distro in it is configured'",
+                    ).conflicts_with_all(&[
+                        "default_uid",
+                        "default_user",
+                        "flags",
+                        "enable",
+                        "disable",
+                    ]),
                 ),
         )
         .subcommand(
             SubCommand::with_name("launch")
+                .setting(AppSettings::ArgRequiredElseHelp)
                 .about("Launches a WSL process")
                 .usage("yowsl.exe launch <NAME> [-c <command>] [-u]")
                 .arg(Arg::from_usage("<NAME> 'A WSL distro name to launch'"))
@@ -100,27 +383,202 @@ fn run() {
 'Uses the current working directory as a directory to start'",
                 )),
         )
-        .get_matches();
-    let wslapi = match Wslapi::new() {
-        Ok(wslapi) => wslapi,
+        .subcommand(
+            SubCommand::with_name("exec")
+                .setting(AppSettings::ArgRequiredElseHelp)
+                .about("Executes a command in a WSL distro non-interactively")
+                .usage(
+                    "yowsl.exe exec <NAME> [-u] [-t <seconds>] [-i <file>] [--json] -- \
+<command>...",
+                )
+                .arg(Arg::from_usage("<NAME> 'A WSL distro name to execute a command in'"))
+                .arg(Arg::from_usage(
+                    "[use_cwd] -u, --use-cwd\
+'Uses the current working directory as a directory to start'",
+                ))
+                .arg(
+                    Arg::from_usage(
+                        "[timeout] -t, --timeout <seconds>\
+'Kills the command if it does not finish in time'",
+                    ).validator(timeout_validator),
+                )
+                .arg(Arg::from_usage(
+                    "[stdin] -i, --stdin <file> 'A file to use as stdin of the command'",
+                ))
+                .arg(Arg::from_usage(
+                    "[json] --json\
+'Captures stdout and stderr and prints {exit_code, stdout, stderr, duration} as JSON, which tells \
+the exit code of the command from the errors of yowsl.exe'",
+                ))
+                .arg(
+                    Arg::from_usage("<command>... 'Command to execute and its arguments'")
+                        .last(true),
+                ),
+        )
+}
+
+/// Parses `args` of a subcommand such as `["register", "Ubuntu", ...]` and returns its matches,
+/// for the tests of each subcommand.
+#[cfg(test)]
+fn sub_matches(args: &[&str]) -> ArgMatches<'static> {
+    let matches = app()
+        .get_matches_from_safe(std::iter::once("yowsl").chain(args.iter().cloned()))
+        .unwrap();
+    matches.subcommand_matches(args[0]).unwrap().clone()
+}
+
+/// Returns a fresh temporary folder for the test `name`.
+#[cfg(test)]
+fn temp_dir(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("yowsl-bin-{}-{}", name, process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    path
+}
+
+fn run() -> i32 {
+    let matches = match app().get_matches_safe() {
+        Ok(matches) => matches,
+        Err(e) => {
+            if e.use_stderr() {
+                eprintln!("{}", e.message);
+                return exit_code::USAGE;
+            }
+            println!("{}", e.message);
+            return exit_code::SUCCESS;
+        }
+    };
+    if let Some(sub_matches) = matches.subcommand_matches("check-rootfs") {
+        return check_rootfs::run(sub_matches);
+    }
+    if let Some(sub_matches) = matches.subcommand_matches("normalize") {
+        return normalize::run(sub_matches);
+    }
+    // These subcommands mostly read or write the registry, so they only load wslapi.dll when they
+    // need it.
+    if REGISTRY_SUBCOMMANDS.contains(&matches.subcommand_name().unwrap_or_default()) {
+        let registry = match new_registry() {
+            Ok(registry) => registry,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return exit_code::UNAVAILABLE;
+            }
+        };
+        return dispatch_registry(&registry, &matches);
+    }
+    let backend = match new_backend() {
+        Ok(backend) => backend,
         Err(e) => {
             eprintln!("Error: {}", e);
-            return;
+            return exit_code::UNAVAILABLE;
         }
     };
+    dispatch(&backend, &matches)
+}
+
+#[cfg(all(target_arch = "x86_64", target_os = "windows"))]
+fn new_registry() -> Result<WindowsRegistry, failure::Error> {
+    Ok(WindowsRegistry::new())
+}
+
+/// Uses the `.reg` file in `YOWSL_REGISTRY` on other platforms.
+#[cfg(not(all(target_arch = "x86_64", target_os = "windows")))]
+fn new_registry() -> Result<RegFile, failure::Error> {
+    match env::var_os("YOWSL_REGISTRY") {
+        Some(path) => RegFile::open(path),
+        None => Err(failure::err_msg(
+            "yowsl needs the registry of Windows to find WSL distros. \
+             Set YOWSL_REGISTRY to a .reg file exported by regedit to use it instead",
+        )),
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", target_os = "windows"))]
+fn new_backend() -> Result<Wslapi, failure::Error> {
+    Wslapi::new()
+}
+
+/// Uses the simulator in `YOWSL_SIMULATOR` on other platforms, optionally running launches
+/// through the program in `YOWSL_SIMULATOR_RESPONDER`.
+#[cfg(not(all(target_arch = "x86_64", target_os = "windows")))]
+fn new_backend() -> Result<SimulatorBackend, failure::Error> {
+    let root = match env::var_os("YOWSL_SIMULATOR") {
+        Some(root) => root,
+        None => {
+            return Err(failure::err_msg(
+                "yowsl needs the Windows Subsystem for Linux on Windows (x64). \
+                 Set YOWSL_SIMULATOR to a folder to use a simulated one",
+            ))
+        }
+    };
+    let simulator = SimulatorBackend::new(root)?;
+    match env::var_os("YOWSL_SIMULATOR_RESPONDER") {
+        Some(responder) => Ok(simulator.with_responder_program(responder)),
+        None => Ok(simulator),
+    }
+}
+
+const REGISTRY_SUBCOMMANDS: [&str; 4] = ["list", "default", "rename", "move"];
+
+fn dispatch_registry<R: Registry>(registry: &R, matches: &ArgMatches) -> i32 {
+    if let Some(sub_matches) = matches.subcommand_matches("list") {
+        list::run(registry, sub_matches)
+    } else if let Some(sub_matches) = matches.subcommand_matches("default") {
+        default_distro::run(registry, sub_matches)
+    } else if let Some(sub_matches) = matches.subcommand_matches("rename") {
+        rename::run(registry, sub_matches)
+    } else if let Some(sub_matches) = matches.subcommand_matches("move") {
+        move_distro::run(registry, sub_matches)
+    } else {
+        exit_code::USAGE
+    }
+}
+
+fn dispatch<B: WslBackend>(backend: &B, matches: &ArgMatches) -> i32 {
     if let Some(sub_matches) = matches.subcommand_matches("register") {
-        register::run(&wslapi, sub_matches);
+        register::run(backend, sub_matches)
     } else if let Some(sub_matches) = matches.subcommand_matches("unregister") {
-        unregister::run(&wslapi, sub_matches);
+        unregister::run(backend, sub_matches)
     } else if let Some(sub_matches) = matches.subcommand_matches("get-configuration") {
-        get_configuration::run(&wslapi, sub_matches);
+        get_configuration::run(backend, sub_matches)
     } else if let Some(sub_matches) = matches.subcommand_matches("set-configuration") {
-        set_configuration::run(&wslapi, sub_matches);
+        set_configuration::run(backend, sub_matches)
     } else if let Some(sub_matches) = matches.subcommand_matches("launch") {
-        launch::run(&wslapi, sub_matches);
+        launch::run(backend, sub_matches)
+    } else if let Some(sub_matches) = matches.subcommand_matches("exec") {
+        exec::run(backend, sub_matches)
+    } else {
+        exit_code::USAGE
     }
 }
 
 fn main() {
-    run();
+    process::exit(run());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use failure;
+    use super::print_error;
+    use exit_code;
+    use yowsl::WslError;
+
+    #[test]
+    fn print_error_returns_the_documented_exit_codes() {
+        let cases = [
+            (WslError::AlreadyExists, exit_code::ALREADY_REGISTERED),
+            (WslError::FileNotFound, exit_code::IO_ERROR),
+            (WslError::PathNotFound, exit_code::IO_ERROR),
+            (WslError::AccessDenied, exit_code::IO_ERROR),
+            (WslError::SubsystemNotPresent, exit_code::UNAVAILABLE),
+            (WslError::Unexpected, exit_code::FAILURE),
+        ];
+        for &(e, code) in &cases {
+            assert_eq!(print_error("I cannot", &e.into()), code, "{:?}", e);
+        }
+        let e = io::Error::new(io::ErrorKind::NotFound, "missing").into();
+        assert_eq!(print_error("I cannot", &e), exit_code::IO_ERROR);
+        assert_eq!(print_error("I cannot", &failure::err_msg("failed")), exit_code::FAILURE);
+    }
 }
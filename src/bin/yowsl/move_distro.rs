@@ -0,0 +1,79 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use clap::ArgMatches;
+use exit_code;
+use new_backend;
+use print_error;
+use yowsl::{self, CopyProgress, Registry, WslBackend};
+
+fn print_progress(progress: &CopyProgress, percent: u64) {
+    eprint!(
+        "\rCopying {} of {} files ({}%)",
+        progress.copied_files, progress.total_files, percent
+    );
+    let _ = io::stderr().flush();
+}
+
+fn move_distro<B: WslBackend, R: Registry>(
+    backend: &B,
+    registry: &R,
+    name: &str,
+    dest: &Path,
+) -> i32 {
+    match backend.is_distribution_registered(name) {
+        Ok(true) => {}
+        Ok(false) => {
+            eprintln!("\"{}\" is not a registered WSL distro name", name);
+            return exit_code::NOT_REGISTERED;
+        }
+        Err(e) => {
+            return print_error(&format!("I cannot move \"{}\"", name), &e);
+        }
+    }
+    let old_base_path = match yowsl::find_distro(registry, name) {
+        Ok(Some(distro)) => distro.base_path,
+        Ok(None) => {
+            eprintln!("\"{}\" cannot be found in the registry", name);
+            return exit_code::NOT_REGISTERED;
+        }
+        Err(e) => return print_error(&format!("I cannot move \"{}\"", name), &e),
+    };
+    // Only print when the percentage changes, as a WSL distro has many small files.
+    let mut last_percent = None;
+    let result = yowsl::move_distro(registry, name, dest, |progress| {
+        let percent = (progress.copied_bytes * 100)
+            .checked_div(progress.total_bytes)
+            .unwrap_or(100);
+        if last_percent != Some(percent) || progress.copied_files == progress.total_files {
+            last_percent = Some(percent);
+            print_progress(progress, percent);
+        }
+    });
+    if last_percent.is_some() {
+        eprintln!();
+    }
+    if let Err(e) = result {
+        return print_error(&format!("I cannot move \"{}\"", name), &e);
+    }
+    if let Err(e) = fs::remove_dir_all(&old_base_path) {
+        eprintln!(
+            "\"{}\" is moved, but I cannot remove \"{}\"\nError: {}",
+            name, old_base_path, e
+        );
+        return exit_code::IO_ERROR;
+    }
+    exit_code::SUCCESS
+}
+
+pub fn run<R: Registry>(registry: &R, matches: &ArgMatches) -> i32 {
+    let name = matches.value_of("NAME").unwrap();
+    let dest = Path::new(matches.value_of("dest").unwrap());
+    match new_backend() {
+        Ok(backend) => move_distro(&backend, registry, name, dest),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            exit_code::UNAVAILABLE
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use std::fs;
+use std::path::Path;
+use clap::ArgMatches;
+use exit_code;
+use print_error;
+use yowsl::{self, SourceFormat};
+
+/// Prints what `normalize_tar_gz` changed.
+pub fn print_changes(changes: &[String]) {
+    for change in changes {
+        eprintln!("Note: {}", change);
+    }
+}
+
+pub fn run(matches: &ArgMatches) -> i32 {
+    let src = Path::new(matches.value_of("src").unwrap());
+    let dest = Path::new(matches.value_of("dest").unwrap());
+    if !src.exists() {
+        eprintln!("\"{}\" does not exist", src.to_str().unwrap());
+        return exit_code::IO_ERROR;
+    }
+    let format = match SourceFormat::detect(src) {
+        Ok(SourceFormat::Directory) => {
+            eprintln!("\"{}\" is a folder, but I can only normalize an archive", src.display());
+            return exit_code::IO_ERROR;
+        }
+        Ok(format) => format,
+        Err(e) => return print_error(&format!("I cannot normalize \"{}\"", src.display()), &e),
+    };
+    if dest.exists() {
+        eprintln!("\"{}\" already exists. Please remove it first", dest.to_str().unwrap());
+        return exit_code::IO_ERROR;
+    }
+    match yowsl::normalize_tar_gz(src, format, dest) {
+        Ok(changes) => {
+            print_changes(&changes);
+            exit_code::SUCCESS
+        }
+        Err(e) => {
+            let _ = fs::remove_file(dest);
+            print_error(&format!("I cannot normalize \"{}\"", src.display()), &e)
+        }
+    }
+}
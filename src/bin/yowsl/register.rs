@@ -1,50 +1,357 @@
-use std::{env, fs};
+use std::{env, fs, process};
 use std::path::Path;
 use std::process::Command;
+use std::str::FromStr;
 use clap::ArgMatches;
-use yowsl::Wslapi;
+use check_rootfs;
+use normalize;
+use exit_code;
+use new_registry;
+use print_error;
+use yowsl::{self, Additions, ImageSelector, NewPassword, NewUser, OciImage, SourceFormat,
+            WslBackend};
 
-pub fn run(wslapi: &Wslapi, matches: &ArgMatches) {
+/// What `register` reads the files of a WSL distro from.
+enum Source {
+    Archive(SourceFormat),
+    /// An archive to rewrite with `normalize_tar_gz` first
+    Normalized(SourceFormat),
+    Oci(ImageSelector),
+}
+
+/// Puts `overrides` such as `PATH=/bin` in place of the variables of the same names in
+/// `variables`, and adds the others at the end.
+fn merge_environment(variables: &[String], overrides: &[String]) -> Vec<String> {
+    let name = |variable: &str| variable.split('=').next().unwrap_or_default().to_string();
+    let mut merged = variables
+        .iter()
+        .filter(|variable| overrides.iter().all(|other| name(other) != name(variable)))
+        .cloned()
+        .collect::<Vec<_>>();
+    merged.extend(overrides.iter().cloned());
+    merged
+}
+
+/// Applies `User` and `Env` of `image` to the WSL distro `name`, leaving out `User` if
+/// `has_default_user`. As the WSL distro is already registered, failures are only warned about.
+fn seed_configuration<B: WslBackend>(
+    backend: &B,
+    name: &str,
+    image: &OciImage,
+    has_default_user: bool,
+) {
+    let mut configuration = match backend.get_distro_configuration(name) {
+        Ok(configuration) => configuration,
+        Err(e) => {
+            eprintln!("Warning: I cannot get the configuration of \"{}\"\nError: {}", name, e);
+            return;
+        }
+    };
+    match image.default_uid() {
+        Ok(Some(_)) if has_default_user => eprintln!(
+            "Note: User of the image is not used, as --user is the default user of \"{}\"",
+            name
+        ),
+        Ok(Some(uid)) => {
+            configuration.default_uid = uid;
+            if let Err(e) = backend.configure_distro(&configuration) {
+                eprintln!("Warning: I cannot set the default user ID {}\nError: {}", uid, e);
+            }
+        }
+        Ok(None) => {}
+        Err(e) => eprintln!("Warning: I cannot use User of the image\nError: {}", e),
+    }
+    if !image.config.env.is_empty() {
+        let variables =
+            merge_environment(&configuration.default_environment_variables, &image.config.env);
+        let result = new_registry()
+            .and_then(|registry| yowsl::set_default_environment(&registry, name, &variables));
+        if let Err(e) = result {
+            eprintln!("Warning: I cannot use Env of the image\nError: {}", e);
+        }
+    }
+    if let Some(ref working_dir) = image.config.working_dir {
+        if !working_dir.is_empty() && working_dir != "/" {
+            eprintln!(
+                "Warning: WSL has no working directory for each WSL distro, so WorkingDir {} of \
+                 the image is not used",
+                working_dir
+            );
+        }
+    }
+}
+
+/// Checks the `.tar.gz` archive `tar_gz` and prints its problems. Returns an exit code if
+/// `name` should not be registered from it.
+fn check(name: &str, tar_gz: &Path) -> Option<i32> {
+    match yowsl::check_rootfs(tar_gz) {
+        Ok(ref problems) if check_rootfs::print_problems(problems) => {
+            eprintln!(
+                "I do not register \"{}\" because of the errors above. Use --no-check to \
+                 register it anyway",
+                name
+            );
+            Some(exit_code::FAILURE)
+        }
+        Ok(_) => None,
+        Err(e) => Some(print_error(&format!("I cannot check the files of \"{}\"", name), &e)),
+    }
+}
+
+/// Adds `additions` to the `.tar.gz` archive `tar_gz` through another temporary archive.
+fn add_to(tar_gz: &Path, additions: &Additions) -> Result<(), failure::Error> {
+    let added = tar_gz.with_file_name(format!(".yowsl-register-{}.added.tar.gz", process::id()));
+    let result = yowsl::add_to_tar_gz(tar_gz, SourceFormat::TarGz, additions, &added)
+        .and_then(|_| Ok(fs::rename(&added, tar_gz)?));
+    if result.is_err() {
+        let _ = fs::remove_file(&added);
+    }
+    result
+}
+
+/// What `register` does besides registering.
+struct Options<'a> {
+    additions: Additions,
+    default_user: Option<&'a str>,
+    no_check: bool,
+    user: Option<NewUser>,
+}
+
+/// Looks up `user` in `/etc/passwd` of `tar_gz`. Returns an exit code if it cannot be found.
+fn find_default_uid(name: &str, tar_gz: &Path, user: &str) -> Result<u32, i32> {
+    match yowsl::read_rootfs_passwd(tar_gz) {
+        Ok(passwd) => match passwd.and_then(|passwd| yowsl::uid_of(&passwd, user)) {
+            Some(uid) => Ok(uid),
+            None => {
+                eprintln!("\"{}\" will have no user named \"{}\"", name, user);
+                Err(exit_code::FAILURE)
+            }
+        },
+        Err(e) => Err(print_error(&format!("I cannot read /etc/passwd of \"{}\"", name), &e)),
+    }
+}
+
+/// Makes `uid` the default user of the WSL distro `name`, which is already registered, so
+/// failures are only warned about.
+fn set_default_uid<B: WslBackend>(backend: &B, name: &str, uid: u32) {
+    let result = backend
+        .get_distro_configuration(name)
+        .and_then(|mut configuration| {
+            configuration.default_uid = uid;
+            backend.configure_distro(&configuration)
+        });
+    if let Err(e) = result {
+        eprintln!("Warning: I cannot set the default user ID {}\nError: {}", uid, e);
+    }
+}
+
+/// Creates `user` in the WSL distro `name`, which is already registered. If a step fails, prints
+/// which steps are done and which are not, and returns an exit code.
+fn create_user<B: WslBackend>(backend: &B, name: &str, user: &NewUser) -> Option<i32> {
+    let report = yowsl::create_user(backend, name, user);
+    let (failed, e) = report.failed?;
+    let code = print_error(
+        &format!("I cannot set up the user \"{}\" in \"{}\", which is registered", user.name, name),
+        &e,
+    );
+    eprintln!("Done: registering \"{}\"", name);
+    for step in &report.done {
+        eprintln!("Done: {}", step);
+    }
+    eprintln!("Failed: {}", failed);
+    for step in &report.not_done {
+        eprintln!("Not done: {}", step);
+    }
+    Some(code)
+}
+
+/// Checks `tar_gz` and registers `name` from it, then configures it with `image` and `options`.
+fn register_tar_gz<B: WslBackend>(
+    backend: &B,
+    name: &str,
+    tar_gz: &Path,
+    image: Option<&OciImage>,
+    options: &Options,
+) -> i32 {
+    if !options.no_check {
+        if let Some(code) = check(name, tar_gz) {
+            return code;
+        }
+    }
+    let default_uid = match options.default_user {
+        Some(user) => match find_default_uid(name, tar_gz, user) {
+            Ok(uid) => Some(uid),
+            Err(code) => return code,
+        },
+        None => None,
+    };
+    if let Err(e) = backend.register_distro(name, tar_gz.to_str().unwrap()) {
+        return print_error(&format!("I cannot register \"{}\"", name), &e);
+    }
+    // `create_user` runs its steps as the default user, which is root until `User` of the image
+    // is applied.
+    let code = match options.user {
+        Some(ref user) => create_user(backend, name, user),
+        None => None,
+    };
+    if let Some(image) = image {
+        seed_configuration(backend, name, image, options.user.is_some());
+    }
+    if let Some(uid) = default_uid {
+        set_default_uid(backend, name, uid);
+    }
+    code.unwrap_or(exit_code::SUCCESS)
+}
+
+/// Registers `name` from `src`, first turning it into a temporary `.tar.gz` archive in `dest`
+/// unless it already is one without additions.
+fn register<B: WslBackend>(
+    backend: &B,
+    name: &str,
+    src: &Path,
+    source: &Source,
+    dest: &Path,
+    options: &Options,
+) -> i32 {
+    let additions = &options.additions;
+    // An archive gets `additions` while it is turned into a `.tar.gz` archive, and the others
+    // after that.
+    let adds_directly = match *source {
+        Source::Archive(format) => format != SourceFormat::Directory && !additions.is_empty(),
+        _ => false,
+    };
+    if let Source::Archive(SourceFormat::TarGz) = *source {
+        if additions.is_empty() {
+            return register_tar_gz(backend, name, src, None, options);
+        }
+    }
+    let tar_gz = dest.join(format!(".yowsl-register-{}.tar.gz", process::id()));
+    let (image, message) = match *source {
+        Source::Archive(format) if adds_directly => (
+            yowsl::add_to_tar_gz(src, format, additions, &tar_gz).map(|_| None),
+            format!("I cannot add files and users to \"{}\"", src.display()),
+        ),
+        Source::Archive(format) => (
+            yowsl::write_tar_gz(src, format, &tar_gz).map(|_| None),
+            format!("I cannot turn the {} \"{}\" into a .tar.gz archive", format, src.display()),
+        ),
+        Source::Normalized(format) => (
+            yowsl::normalize_tar_gz(src, format, &tar_gz).map(|changes| {
+                normalize::print_changes(&changes);
+                None
+            }),
+            format!("I cannot normalize the {} \"{}\"", format, src.display()),
+        ),
+        Source::Oci(ref selector) => (
+            yowsl::write_oci_tar_gz(src, selector, &tar_gz).map(Some),
+            format!("I cannot flatten the image \"{}\"", src.display()),
+        ),
+    };
+    let image = match image {
+        Ok(image) => image,
+        Err(e) => {
+            let _ = fs::remove_file(&tar_gz);
+            return print_error(&message, &e);
+        }
+    };
+    if !additions.is_empty() && !adds_directly {
+        if let Err(e) = add_to(&tar_gz, additions) {
+            let _ = fs::remove_file(&tar_gz);
+            return print_error(&format!("I cannot add files and users to \"{}\"", name), &e);
+        }
+    }
+    let exit_code = register_tar_gz(backend, name, &tar_gz, image.as_ref(), options);
+    let _ = fs::remove_file(&tar_gz);
+    exit_code
+}
+
+/// Parses the values of `name`, which its validator has already checked.
+fn values_of<T: FromStr>(matches: &ArgMatches, name: &str) -> Vec<T> {
+    matches
+        .values_of(name)
+        .map(|values| values.filter_map(|value| value.parse().ok()).collect())
+        .unwrap_or_default()
+}
+
+pub fn run<B: WslBackend>(backend: &B, matches: &ArgMatches) -> i32 {
     let name = matches.value_of("NAME").unwrap();
-    match wslapi.is_distribution_registered(name) {
+    match backend.is_distribution_registered(name) {
         Ok(true) => {
             eprintln!("\"{}\" is an already registered WSL distro name", name);
-            return;
+            return exit_code::ALREADY_REGISTERED;
         }
         Ok(false) => {}
         Err(e) => {
-            eprintln!("I cannot register \"{}\"\nError: {}", name, e);
-            return;
+            return print_error(&format!("I cannot register \"{}\"", name), &e);
         }
     }
-    let src = Path::new(matches.value_of("src").unwrap());
-    if !src.is_file() {
+    let src = Path::new(matches.value_of("src").or_else(|| matches.value_of("oci")).unwrap());
+    if !src.exists() {
         eprintln!("\"{}\" does not exist", src.to_str().unwrap());
-        return;
+        return exit_code::IO_ERROR;
+    }
+    let source = if matches.is_present("oci") {
+        Source::Oci(ImageSelector {
+            tag: matches.value_of("tag").map(str::to_string),
+            platform: matches.value_of("platform").map(str::to_string),
+        })
+    } else {
+        match SourceFormat::detect(src) {
+            Ok(SourceFormat::Directory) if matches.is_present("normalize") => {
+                eprintln!(
+                    "\"{}\" is a folder, but I can only normalize an archive",
+                    src.display()
+                );
+                return exit_code::IO_ERROR;
+            }
+            Ok(format) if matches.is_present("normalize") => Source::Normalized(format),
+            Ok(format) => Source::Archive(format),
+            Err(e) => return print_error(&format!("I cannot register \"{}\"", name), &e),
+        }
+    };
+    let options = Options {
+        additions: Additions {
+            files: values_of(matches, "add_file"),
+            users: values_of(matches, "add_user"),
+        },
+        default_user: matches.value_of("default_user"),
+        no_check: matches.is_present("no_check"),
+        user: matches.value_of("user").map(|user| NewUser {
+            name: user.to_string(),
+            password: match matches.value_of("password_hash") {
+                Some(hash) => NewPassword::Hash(hash.to_string()),
+                None if matches.is_present("no_password") => NewPassword::Empty,
+                None => NewPassword::Prompt,
+            },
+            sudo: matches.is_present("sudo"),
+        }),
+    };
+    for file in &options.additions.files {
+        if !file.host_path.is_file() {
+            eprintln!("\"{}\" is not a file", file.host_path.display());
+            return exit_code::IO_ERROR;
+        }
     }
     let dest = Path::new(matches.value_of("dest").unwrap());
     if !dest.exists() {
-        if let Err(e) = fs::create_dir_all(&dest) {
+        if let Err(e) = fs::create_dir_all(dest) {
             eprintln!(
                 "Error: I cannot create \"{}\"\nError: {}",
                 dest.to_str().unwrap(),
                 e
             );
-            return;
+            return exit_code::IO_ERROR;
         }
     }
     if !dest.is_dir() {
         eprintln!("\"{}\" is not a folder", dest.to_str().unwrap());
-        return;
+        return exit_code::IO_ERROR;
     }
-    let src = fs::canonicalize(&src).unwrap();
-    let dest = fs::canonicalize(&dest).unwrap();
+    let src = fs::canonicalize(src).unwrap();
+    let dest = fs::canonicalize(dest).unwrap();
     let current_exe = env::current_exe().unwrap();
     if current_exe.as_path().parent().unwrap() == dest {
-        if let Err(e) = wslapi.register_distro(name, src.to_str().unwrap()) {
-            eprintln!("I cannot register \"{}\"\nError: {}", name, e);
-        }
-        return;
+        return register(backend, name, &src, &source, &dest, &options);
     }
     let new_exe = dest.join(current_exe.file_name().unwrap());
     if new_exe.exists() {
@@ -52,7 +359,7 @@ pub fn run(wslapi: &Wslapi, matches: &ArgMatches) {
             "\"{}\" already exists. Please remove it first",
             new_exe.to_str().unwrap()
         );
-        return;
+        return exit_code::IO_ERROR;
     }
     if let Err(e) = fs::hard_link(current_exe.as_path(), &new_exe) {
         eprintln!(
@@ -60,12 +367,124 @@ pub fn run(wslapi: &Wslapi, matches: &ArgMatches) {
             dest.to_str().unwrap(),
             e
         );
-        return;
-    }
-    Command::new(&new_exe)
-        .args(env::args().skip(1))
-        .spawn()
-        .unwrap()
-        .wait()
-        .unwrap();
+        return exit_code::IO_ERROR;
+    }
+    // The registering process has already reported any error, so only its exit code is needed.
+    let code = match Command::new(&new_exe).args(env::args().skip(1)).status() {
+        Ok(status) => status.code().unwrap_or(exit_code::FAILURE),
+        Err(e) => {
+            eprintln!(
+                "I cannot run \"{}\"\nError: {}",
+                new_exe.to_str().unwrap(),
+                e
+            );
+            exit_code::FAILURE
+        }
+    };
+    // Without a WSL distro the launcher is of no use, and it would stop the next try.
+    if code != exit_code::SUCCESS && !backend.is_distribution_registered(name).unwrap_or(true) {
+        let _ = fs::remove_file(&new_exe);
+    }
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+    use std::path::Path;
+    use super::run;
+    use exit_code;
+    use sub_matches;
+    use temp_dir;
+    use yowsl::{self, FakeBackend, SourceFormat, WslBackend};
+
+    /// Writes a `.tar.gz` archive of the folder `rootfs` with `files` in it.
+    fn write_rootfs(rootfs: &Path, files: &[(&str, &str)]) -> String {
+        fs::create_dir_all(rootfs).unwrap();
+        for &(path, data) in files {
+            let path = rootfs.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, data).unwrap();
+        }
+        let tar_gz = rootfs.with_extension("tar.gz");
+        yowsl::write_tar_gz(rootfs, SourceFormat::Directory, &tar_gz).unwrap();
+        tar_gz.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn run_registers_an_archive_once() {
+        let dir = temp_dir("register");
+        let tar_gz = write_rootfs(
+            &dir.join("rootfs"),
+            &[("bin/sh", "#!"), ("etc/passwd", "root:x:0:0::/root:/bin/sh\n")],
+        );
+        let empty = write_rootfs(&dir.join("empty"), &[]);
+        // A temporary archive is only written for other sources, so the folder of the test
+        // program can be the destination without re-executing it.
+        let dest = env::current_exe().unwrap();
+        let dest = dest.parent().unwrap().to_str().unwrap();
+        let backend = FakeBackend::new();
+        let matches = sub_matches(&["register", "Ubuntu", "-s", &empty, "-d", dest]);
+        assert_eq!(run(&backend, &matches), exit_code::FAILURE);
+        assert!(!backend.is_distribution_registered("Ubuntu").unwrap());
+        let missing = dir.join("missing.tar.gz");
+        let missing = missing.to_str().unwrap();
+        let matches = sub_matches(&["register", "Ubuntu", "-s", missing, "-d", dest]);
+        assert_eq!(run(&backend, &matches), exit_code::IO_ERROR);
+        let matches = sub_matches(&["register", "Ubuntu", "-s", &tar_gz, "-d", dest]);
+        assert_eq!(run(&backend, &matches), exit_code::SUCCESS);
+        assert!(backend.is_distribution_registered("Ubuntu").unwrap());
+        assert_eq!(run(&backend, &matches), exit_code::ALREADY_REGISTERED);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Writes an OCI image layout whose only layer is `layer` and whose `User` is `user`.
+    fn write_oci_layout(layout: &Path, layer: &str, user: &str) {
+        let blobs = layout.join("blobs/sha256");
+        fs::create_dir_all(&blobs).unwrap();
+        fs::copy(layer, blobs.join("l0")).unwrap();
+        let config = format!(
+            r#"{{"architecture": "amd64", "os": "linux", "config": {{"User": "{}"}}}}"#,
+            user
+        );
+        fs::write(blobs.join("c0"), config).unwrap();
+        fs::write(
+            blobs.join("m0"),
+            r#"{"schemaVersion": 2, "config": {"digest": "sha256:c0"},
+                "layers": [{"digest": "sha256:l0"}]}"#,
+        ).unwrap();
+        fs::write(layout.join("oci-layout"), r#"{"imageLayoutVersion": "1.0.0"}"#).unwrap();
+        fs::write(
+            layout.join("index.json"),
+            r#"{"schemaVersion": 2, "manifests": [{"digest": "sha256:m0"}]}"#,
+        ).unwrap();
+    }
+
+    #[test]
+    fn run_creates_the_user_as_root_before_using_user_of_the_image() {
+        let dir = temp_dir("register-oci-user");
+        let passwd = "root:x:0:0::/root:/bin/sh\nnobody:x:65534:65534::/:/bin/sh\n";
+        let layer = write_rootfs(&dir.join("rootfs"), &[("bin/sh", "#!"), ("etc/passwd", passwd)]);
+        let layout = dir.join("layout");
+        write_oci_layout(&layout, &layer, "nobody");
+        let layout = layout.to_str().unwrap();
+        let dest = env::current_exe().unwrap();
+        let dest = dest.parent().unwrap().to_str().unwrap();
+        let backend = FakeBackend::new();
+        let passwd = format!("{}alice:x:1000:1000::/home/alice:/bin/sh\n", passwd);
+        backend.set_launch_output(passwd.as_bytes(), b"");
+        let matches = sub_matches(&[
+            "register", "Alpine", "--oci", layout, "-d", dest, "--user", "alice", "--no-password",
+        ]);
+        assert_eq!(run(&backend, &matches), exit_code::SUCCESS);
+        let launches = backend.launches();
+        assert!(!launches.is_empty());
+        assert!(launches.iter().all(|launch| launch.default_uid == 0));
+        assert_eq!(backend.get_distro_configuration("Alpine").unwrap().default_uid, 1000);
+        let backend = FakeBackend::new();
+        let matches = sub_matches(&["register", "Alpine", "--oci", layout, "-d", dest]);
+        assert_eq!(run(&backend, &matches), exit_code::SUCCESS);
+        assert_eq!(backend.get_distro_configuration("Alpine").unwrap().default_uid, 65534);
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,155 @@
+use std::{env, fs, io};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use clap::ArgMatches;
+use exit_code;
+use new_backend;
+use print_error;
+use yowsl::{self, Registry, WslBackend};
+
+/// Renames the launcher that `register` has left in `base_path` to `<new>.exe`, and returns it.
+///
+/// `register` links the launcher under the file name of the running yowsl, which is usually
+/// `yowsl.exe`, but a yowsl that was copied as `<NAME>.exe` leaves one named after the WSL
+/// distro. `current_exe` itself is never renamed, as it is yowsl and not a launcher when the WSL
+/// distro was registered in its folder.
+fn rename_launcher(
+    base_path: &Path,
+    current_exe: &Path,
+    old: &str,
+    new: &str,
+) -> io::Result<Option<PathBuf>> {
+    let new_launcher = base_path.join(format!("{}.exe", new));
+    let names = [
+        OsString::from(format!("{}.exe", old)),
+        current_exe.file_name().unwrap_or_default().to_os_string(),
+    ];
+    let current_exe = fs::canonicalize(current_exe).ok();
+    for name in &names {
+        let launcher = base_path.join(name);
+        if !launcher.is_file() || fs::canonicalize(&launcher).ok() == current_exe {
+            continue;
+        }
+        fs::rename(&launcher, &new_launcher)?;
+        return Ok(Some(new_launcher));
+    }
+    Ok(None)
+}
+
+fn rename<B: WslBackend, R: Registry>(
+    backend: &B,
+    registry: &R,
+    old: &str,
+    new: &str,
+    current_exe: &Path,
+) -> i32 {
+    match backend.is_distribution_registered(old) {
+        Ok(true) => {}
+        Ok(false) => {
+            eprintln!("\"{}\" is not a registered WSL distro name", old);
+            return exit_code::NOT_REGISTERED;
+        }
+        Err(e) => {
+            return print_error(&format!("I cannot rename \"{}\"", old), &e);
+        }
+    }
+    if !new.eq_ignore_ascii_case(old) {
+        match backend.is_distribution_registered(new) {
+            Ok(true) => {
+                eprintln!("\"{}\" is an already registered WSL distro name", new);
+                return exit_code::ALREADY_REGISTERED;
+            }
+            Ok(false) => {}
+            Err(e) => {
+                return print_error(&format!("I cannot rename \"{}\"", old), &e);
+            }
+        }
+    }
+    let distro = match yowsl::rename_distro(registry, old, new) {
+        Ok(distro) => distro,
+        Err(e) => return print_error(&format!("I cannot rename \"{}\"", old), &e),
+    };
+    if let Err(e) = rename_launcher(Path::new(&distro.base_path), current_exe, old, new) {
+        eprintln!(
+            "\"{}\" is renamed, but I cannot rename its launcher in \"{}\"\nError: {}",
+            old, distro.base_path, e
+        );
+        return exit_code::IO_ERROR;
+    }
+    exit_code::SUCCESS
+}
+
+pub fn run<R: Registry>(registry: &R, matches: &ArgMatches) -> i32 {
+    let old = matches.value_of("OLD").unwrap();
+    let new = matches.value_of("NEW").unwrap();
+    match new_backend() {
+        Ok(backend) => match env::current_exe() {
+            Ok(current_exe) => rename(&backend, registry, old, new, &current_exe),
+            Err(e) => {
+                eprintln!("I cannot find the path of yowsl.exe\nError: {}", e);
+                exit_code::IO_ERROR
+            }
+        },
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            exit_code::UNAVAILABLE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::rename;
+    use exit_code;
+    use temp_dir;
+    use yowsl::{FakeBackend, MemoryRegistry, RegistryValue, WslBackend, LXSS_KEY};
+
+    /// Returns a backend and a registry that both have "Ubuntu" in `base_path`.
+    fn ubuntu(base_path: &str) -> (FakeBackend, MemoryRegistry) {
+        let backend = FakeBackend::new();
+        backend.register_distro("Ubuntu", "rootfs.tar.gz").unwrap();
+        let registry = MemoryRegistry::new();
+        let key = format!("{}\\{{0a1b2c3d-0000-0000-0000-000000000001}}", LXSS_KEY);
+        registry.insert_value(&key, "DistributionName", RegistryValue::Sz("Ubuntu".to_string()));
+        registry.insert_value(&key, "BasePath", RegistryValue::Sz(base_path.to_string()));
+        (backend, registry)
+    }
+
+    #[test]
+    fn rename_renames_the_launcher() {
+        let dir = temp_dir("rename");
+        let yowsl = dir.join("bin/yowsl.exe");
+        fs::create_dir_all(yowsl.parent().unwrap()).unwrap();
+        fs::write(&yowsl, "yowsl").unwrap();
+        for launcher in &["Ubuntu.exe", "yowsl.exe"] {
+            let base_path = dir.join("Ubuntu");
+            fs::create_dir_all(&base_path).unwrap();
+            fs::write(base_path.join(launcher), "yowsl").unwrap();
+            let (backend, registry) = ubuntu(base_path.to_str().unwrap());
+            assert_eq!(
+                rename(&backend, &registry, "Ubuntu", "Focal", &yowsl),
+                exit_code::SUCCESS
+            );
+            assert!(!base_path.join(launcher).exists(), "{}", launcher);
+            assert!(base_path.join("Focal.exe").is_file(), "{}", launcher);
+            fs::remove_dir_all(&base_path).unwrap();
+        }
+        // A WSL distro registered in the folder of yowsl.exe keeps it.
+        let (backend, registry) = ubuntu(dir.join("bin").to_str().unwrap());
+        assert_eq!(rename(&backend, &registry, "Ubuntu", "Focal", &yowsl), exit_code::SUCCESS);
+        assert!(yowsl.is_file());
+        assert!(!dir.join("bin/Focal.exe").exists());
+        let (backend, registry) = ubuntu(dir.to_str().unwrap());
+        assert_eq!(
+            rename(&backend, &registry, "Debian", "Focal", &yowsl),
+            exit_code::NOT_REGISTERED
+        );
+        backend.register_distro("Focal", "rootfs.tar.gz").unwrap();
+        assert_eq!(
+            rename(&backend, &registry, "Ubuntu", "Focal", &yowsl),
+            exit_code::ALREADY_REGISTERED
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
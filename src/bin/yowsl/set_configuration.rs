@@ -1,39 +1,305 @@
+use std::fs;
 use clap::ArgMatches;
-use yowsl::{DistroFlags, Wslapi};
+use failure::Error;
+use exit_code;
+use print_error;
+use yowsl::{self, DistroConfiguration, DistroFlags, WslBackend};
 
 #[allow(non_camel_case_types)]
 type WSL_DISTRIBUTION_FLAGS = u32;
 
-pub fn run(wslapi: &Wslapi, matches: &ArgMatches) {
-    let name = matches.value_of("NAME").unwrap();
-    match wslapi.is_distribution_registered(name) {
+/// Parses flags given to `-f`, `--enable` or `--disable`.
+///
+/// Exactly 3 binary digits such as `101` are still read as binary, as `-f` used to require.
+/// Anything else is parsed by `DistroFlags::from_str`.
+pub fn parse_flags(s: &str) -> Result<DistroFlags, Error> {
+    if s.len() == 3 && s.chars().all(|c| c == '0' || c == '1') {
+        return Ok(DistroFlags::from_raw(WSL_DISTRIBUTION_FLAGS::from_str_radix(s, 2)?));
+    }
+    s.parse()
+}
+
+/// Returns the current configuration of `name`, or the exit code if it cannot be set.
+fn get_distro_configuration<B: WslBackend>(
+    backend: &B,
+    name: &str,
+) -> Result<DistroConfiguration, i32> {
+    match backend.is_distribution_registered(name) {
         Ok(true) => {}
         Ok(false) => {
             eprintln!("\"{}\" is not a registered WSL distro name", name);
-            return;
+            return Err(exit_code::NOT_REGISTERED);
         }
         Err(e) => {
-            eprintln!("I cannot set a configuration of \"{}\"\nError: {}", name, e);
-            return;
+            return Err(print_error(
+                &format!("I cannot set a configuration of \"{}\"", name),
+                &e,
+            ));
         }
     }
-    let mut distro_configuration = match wslapi.get_distro_configuration(name) {
+    backend
+        .get_distro_configuration(name)
+        .map_err(|e| print_error(&format!("I cannot set a configuration of \"{}\"", name), &e))
+}
+
+fn configure_distro<B: WslBackend>(backend: &B, distro_configuration: &DistroConfiguration) -> i32 {
+    match backend.configure_distro(distro_configuration) {
+        Ok(()) => exit_code::SUCCESS,
+        Err(e) => print_error(
+            &format!(
+                "I cannot set a configuration of \"{}\"",
+                distro_configuration.name
+            ),
+            &e,
+        ),
+    }
+}
+
+/// Applies `wanted` to its WSL distro. Only `default_uid` and `flags` can be changed, so
+/// differences in the other fields are warned about.
+fn apply<B: WslBackend>(backend: &B, wanted: &DistroConfiguration) -> i32 {
+    let mut distro_configuration = match get_distro_configuration(backend, &wanted.name) {
         Ok(distro_configuration) => distro_configuration,
+        Err(exit_code) => return exit_code,
+    };
+    if wanted.version != distro_configuration.version {
+        eprintln!(
+            "Warning: The version of \"{}\" cannot be changed. It stays {}",
+            wanted.name, distro_configuration.version
+        );
+    }
+    if wanted.default_environment_variables != distro_configuration.default_environment_variables
+    {
+        eprintln!(
+            "Warning: The default environment variables of \"{}\" cannot be changed",
+            wanted.name
+        );
+    }
+    distro_configuration.default_uid = wanted.default_uid;
+    distro_configuration.flags = wanted.flags;
+    configure_distro(backend, &distro_configuration)
+}
+
+fn run_from_file<B: WslBackend>(backend: &B, name: Option<&str>, path: &str) -> i32 {
+    let toml = match fs::read_to_string(path) {
+        Ok(toml) => toml,
         Err(e) => {
-            eprintln!("I cannot set a configuration of \"{}\"\nError: {}", name, e);
-            return;
+            eprintln!("I cannot read \"{}\"\nError: {}", path, e);
+            return exit_code::IO_ERROR;
         }
     };
-    if matches.is_present("default_uid") {
-        distro_configuration.default_uid =
-            matches.value_of("default_uid").unwrap().parse().unwrap();
+    let distro_configurations = match DistroConfiguration::from_toml_all(&toml) {
+        Ok(distro_configurations) => distro_configurations,
+        Err(e) => return print_error(&format!("I cannot parse \"{}\"", path), &e),
+    };
+    let distro_configurations = match name {
+        Some(name) => match distro_configurations.into_iter().find(|c| c.name == name) {
+            Some(distro_configuration) => vec![distro_configuration],
+            None => {
+                eprintln!("\"{}\" does not have a [{}] table", path, name);
+                return exit_code::FAILURE;
+            }
+        },
+        None => distro_configurations,
+    };
+    // Keep going after a failure so that one bad table does not stop the others.
+    distro_configurations
+        .iter()
+        .map(|distro_configuration| apply(backend, distro_configuration))
+        .fold(exit_code::SUCCESS, |result, exit_code| {
+            if result == exit_code::SUCCESS {
+                exit_code
+            } else {
+                result
+            }
+        })
+}
+
+pub fn run<B: WslBackend>(backend: &B, matches: &ArgMatches) -> i32 {
+    if let Some(path) = matches.value_of("from_file") {
+        return run_from_file(backend, matches.value_of("NAME"), path);
     }
-    if matches.is_present("flags") {
-        distro_configuration.flags = DistroFlags::from_bits(
-            WSL_DISTRIBUTION_FLAGS::from_str_radix(matches.value_of("flags").unwrap(), 2).unwrap(),
-        ).unwrap();
+    let name = matches.value_of("NAME").unwrap();
+    let mut distro_configuration = match get_distro_configuration(backend, name) {
+        Ok(distro_configuration) => distro_configuration,
+        Err(exit_code) => return exit_code,
+    };
+    if let Some(default_uid) = matches.value_of("default_uid") {
+        let default_uid = default_uid.parse().unwrap();
+        match yowsl::read_passwd(backend, name) {
+            Ok(ref passwd) if yowsl::user_name_of(passwd, default_uid).is_none() => {
+                eprintln!("\"{}\" has no user whose ID is {}", name, default_uid);
+                return exit_code::FAILURE;
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!(
+                "Warning: I cannot check that \"{}\" has a user whose ID is {}\nError: {}",
+                name, default_uid, e
+            ),
+        }
+        distro_configuration.default_uid = default_uid;
     }
-    if let Err(e) = wslapi.configure_distro(&distro_configuration) {
-        eprintln!("I cannot set a configuration of \"{}\"\nError: {}", name, e);
+    if let Some(default_user) = matches.value_of("default_user") {
+        let passwd = match yowsl::read_passwd(backend, name) {
+            Ok(passwd) => passwd,
+            Err(e) => {
+                return print_error(&format!("I cannot read /etc/passwd of \"{}\"", name), &e);
+            }
+        };
+        match yowsl::uid_of(&passwd, default_user) {
+            Some(default_uid) => distro_configuration.default_uid = default_uid,
+            None => {
+                eprintln!("\"{}\" has no user named \"{}\"", name, default_user);
+                return exit_code::FAILURE;
+            }
+        }
+    }
+    if let Some(flags) = matches.value_of("flags") {
+        let flags = parse_flags(flags).unwrap();
+        let cleared_bits = distro_configuration.flags.unknown_bits() & !flags.bits();
+        if cleared_bits != 0 {
+            eprintln!(
+                "Warning: -f clears the unknown flags {:#X} of \"{}\". Use --enable and \
+                 --disable to keep them",
+                cleared_bits, name
+            );
+        }
+        distro_configuration.flags = flags;
+    }
+    for flags in matches.values_of("enable").into_iter().flatten() {
+        distro_configuration.flags.insert(parse_flags(flags).unwrap());
+    }
+    for flags in matches.values_of("disable").into_iter().flatten() {
+        distro_configuration.flags.remove(parse_flags(flags).unwrap());
+    }
+    configure_distro(backend, &distro_configuration)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::run;
+    use exit_code;
+    use sub_matches;
+    use temp_dir;
+    use yowsl::{DistroConfiguration, DistroFlags, FakeBackend, WslBackend};
+
+    const PASSWD: &str = "root:x:0:0:root:/root:/bin/bash\n\
+                          alice:x:1000:1000::/home/alice:/bin/bash\n";
+
+    fn backend_with(names: &[&str]) -> FakeBackend {
+        let backend = FakeBackend::new();
+        for name in names {
+            backend.insert_distro(DistroConfiguration {
+                name: name.to_string(),
+                version: 1,
+                default_uid: 0,
+                flags: DistroFlags::all(),
+                default_environment_variables: vec!["LANG=C.UTF-8".to_string()],
+            });
+        }
+        backend.set_launch_output(PASSWD.as_bytes(), b"");
+        backend
+    }
+
+    fn configuration(backend: &FakeBackend, name: &str) -> (u32, DistroFlags) {
+        let configuration = backend.get_distro_configuration(name).unwrap();
+        (configuration.default_uid, configuration.flags)
+    }
+
+    /// Runs `set-configuration` with `args` and the TOML file `toml` in the folder of the test
+    /// `name`.
+    fn run_from_file(name: &str, backend: &FakeBackend, args: &[&str], toml: &str) -> i32 {
+        let dir = temp_dir(name);
+        let path = dir.join("distros.toml");
+        fs::write(&path, toml).unwrap();
+        let mut all_args = vec!["set-configuration"];
+        all_args.extend_from_slice(args);
+        all_args.extend_from_slice(&["--from-file", path.to_str().unwrap()]);
+        let exit_code = run(backend, &sub_matches(&all_args));
+        fs::remove_dir_all(&dir).unwrap();
+        exit_code
+    }
+
+    #[test]
+    fn run_from_file_applies_only_the_tables_it_is_asked_to() {
+        let backend = backend_with(&["Debian", "Ubuntu"]);
+        let toml = "schema_version = 1\n\n[Ubuntu]\nversion = 1\ndefault_uid = 1000\nflags = 3\n\
+                    default_environment_variables = [\"LANG=C.UTF-8\"]\n";
+        let code = run_from_file("from-file-tables", &backend, &["Debian"], toml);
+        assert_eq!(code, exit_code::FAILURE);
+        assert_eq!(configuration(&backend, "Ubuntu"), (0, DistroFlags::all()));
+        assert_eq!(run_from_file("from-file-tables", &backend, &[], toml), exit_code::SUCCESS);
+        assert_eq!(configuration(&backend, "Ubuntu"), (1000, DistroFlags::from_raw(3)));
+        assert_eq!(configuration(&backend, "Debian"), (0, DistroFlags::all()));
+        // A table without some fields is not parsed rather than guessed at.
+        let toml = "schema_version = 1\n\n[Debian]\ndefault_uid = 1000\n";
+        assert_eq!(run_from_file("from-file-tables", &backend, &[], toml), exit_code::FAILURE);
+        assert_eq!(configuration(&backend, "Debian"), (0, DistroFlags::all()));
+    }
+
+    #[test]
+    fn run_from_file_warns_about_fields_that_cannot_be_changed() {
+        let backend = backend_with(&["Ubuntu"]);
+        let toml = "schema_version = 1\n\n[Ubuntu]\nversion = 2\ndefault_uid = 1000\nflags = 7\n\
+                    default_environment_variables = [\"LANG=en_US.UTF-8\"]\n";
+        assert_eq!(run_from_file("from-file-warnings", &backend, &[], toml), exit_code::SUCCESS);
+        let configuration = backend.get_distro_configuration("Ubuntu").unwrap();
+        assert_eq!(configuration.default_uid, 1000);
+        assert_eq!(configuration.version, 1);
+        assert_eq!(configuration.default_environment_variables, vec!["LANG=C.UTF-8"]);
+    }
+
+    #[test]
+    fn run_from_file_keeps_going_and_returns_the_first_failure() {
+        let backend = backend_with(&["Debian", "Ubuntu"]);
+        let table = |name: &str| {
+            format!(
+                "[{}]\nversion = 1\ndefault_uid = 1000\nflags = 1\n\
+                 default_environment_variables = []\n",
+                name
+            )
+        };
+        let toml = format!(
+            "schema_version = 1\n\n{}{}{}{}",
+            table("Alpine"),
+            table("Debian"),
+            table("Fedora"),
+            table("Ubuntu")
+        );
+        let code = run_from_file("from-file-failure", &backend, &[], &toml);
+        assert_eq!(code, exit_code::NOT_REGISTERED);
+        assert_eq!(configuration(&backend, "Debian"), (1000, DistroFlags::from_raw(1)));
+        assert_eq!(configuration(&backend, "Ubuntu"), (1000, DistroFlags::from_raw(1)));
+    }
+
+    #[test]
+    fn run_resolves_default_users_and_flips_flags() {
+        let backend = backend_with(&["Ubuntu"]);
+        let set = |args: &[&str]| {
+            let mut all_args = vec!["set-configuration", "Ubuntu"];
+            all_args.extend_from_slice(args);
+            run(&backend, &sub_matches(&all_args))
+        };
+        assert_eq!(set(&["--default-user", "bob"]), exit_code::FAILURE);
+        assert_eq!(set(&["-d", "4242"]), exit_code::FAILURE);
+        assert_eq!(configuration(&backend, "Ubuntu").0, 0);
+        assert_eq!(set(&["--default-user", "alice", "--disable", "ntpath"]), exit_code::SUCCESS);
+        assert_eq!(
+            configuration(&backend, "Ubuntu"),
+            (1000, DistroFlags::ENABLE_INTEROP | DistroFlags::ENABLE_DRIVE_MOUNTING)
+        );
+        assert_eq!(set(&["-d", "0", "-f", "0", "--enable", "mount"]), exit_code::SUCCESS);
+        assert_eq!(
+            configuration(&backend, "Ubuntu"),
+            (0, DistroFlags::ENABLE_DRIVE_MOUNTING)
+        );
+        // Without /etc/passwd, -d is only warned about but --default-user cannot be resolved.
+        backend.set_launch_exit_code(1);
+        assert_eq!(set(&["-d", "4242"]), exit_code::SUCCESS);
+        assert_eq!(set(&["--default-user", "alice"]), exit_code::FAILURE);
+        assert_eq!(configuration(&backend, "Ubuntu").0, 4242);
+        let matches = sub_matches(&["set-configuration", "Debian", "-d", "0"]);
+        assert_eq!(run(&backend, &matches), exit_code::NOT_REGISTERED);
     }
 }
@@ -1,20 +1,40 @@
 use clap::ArgMatches;
-use yowsl::Wslapi;
+use exit_code;
+use print_error;
+use yowsl::WslBackend;
 
-pub fn run(wslapi: &Wslapi, matches: &ArgMatches) {
+pub fn run<B: WslBackend>(backend: &B, matches: &ArgMatches) -> i32 {
     let name = matches.value_of("NAME").unwrap();
-    match wslapi.is_distribution_registered(name) {
+    match backend.is_distribution_registered(name) {
         Ok(true) => {}
         Ok(false) => {
             eprintln!("\"{}\" is not a registered WSL distro name", name);
-            return;
+            return exit_code::NOT_REGISTERED;
         }
         Err(e) => {
-            eprintln!("I cannot unregister \"{}\"\nError: {}", name, e);
-            return;
+            return print_error(&format!("I cannot unregister \"{}\"", name), &e);
         }
     }
-    if let Err(e) = wslapi.unregister_distro(name) {
-        eprintln!("I cannot unregister \"{}\"\nError: {}", name, e);
+    if let Err(e) = backend.unregister_distro(name) {
+        return print_error(&format!("I cannot unregister \"{}\"", name), &e);
+    }
+    exit_code::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run;
+    use exit_code;
+    use sub_matches;
+    use yowsl::{FakeBackend, WslBackend};
+
+    #[test]
+    fn run_unregisters_a_registered_distro() {
+        let backend = FakeBackend::new();
+        let matches = sub_matches(&["unregister", "Ubuntu"]);
+        assert_eq!(run(&backend, &matches), exit_code::NOT_REGISTERED);
+        backend.register_distro("Ubuntu", "rootfs.tar.gz").unwrap();
+        assert_eq!(run(&backend, &matches), exit_code::SUCCESS);
+        assert!(!backend.is_distribution_registered("Ubuntu").unwrap());
     }
 }
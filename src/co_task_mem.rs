@@ -0,0 +1,108 @@
+use std::borrow::Cow;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+use std::slice;
+
+/// Frees memory that the Windows API allocated with `CoTaskMemAlloc`.
+///
+/// `Wslapi` implements this trait with `CoTaskMemFree` from `ole32.dll`. Other implementations
+/// let the owner types in this module be used without Windows.
+pub trait CoTaskMemFree {
+    /// Frees `p`.
+    ///
+    /// # Safety
+    ///
+    /// `p` must have been allocated by the allocator that this `CoTaskMemFree` belongs to and
+    /// must not have been freed yet.
+    unsafe fn co_task_mem_free(&self, p: *mut c_void);
+}
+
+/// An owned nul-terminated string that is freed with `CoTaskMemFree` when dropped.
+pub struct CoTaskMemString<'a, F: CoTaskMemFree + 'a> {
+    ptr: *mut c_char,
+    free: &'a F,
+}
+
+impl<'a, F: CoTaskMemFree> CoTaskMemString<'a, F> {
+    /// Takes ownership of `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be null or a nul-terminated string that can be freed with `free`. Nothing else
+    /// may free it.
+    pub unsafe fn from_raw(ptr: *mut c_char, free: &'a F) -> CoTaskMemString<'a, F> {
+        CoTaskMemString { ptr, free }
+    }
+
+    /// Returns the string without the nul terminator. A null string is empty.
+    pub fn to_bytes(&self) -> &[u8] {
+        if self.ptr.is_null() {
+            return &[];
+        }
+        unsafe { CStr::from_ptr(self.ptr) }.to_bytes()
+    }
+
+    pub fn to_string_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(self.to_bytes())
+    }
+}
+
+impl<'a, F: CoTaskMemFree> Drop for CoTaskMemString<'a, F> {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe { self.free.co_task_mem_free(self.ptr as *mut c_void) };
+        }
+    }
+}
+
+/// An owned array that is freed with `CoTaskMemFree` when dropped.
+///
+/// Only the array itself is freed. Use `into_strings` for an array of owned strings.
+pub struct CoTaskMemArray<'a, T, F: CoTaskMemFree + 'a> {
+    ptr: *mut T,
+    len: usize,
+    free: &'a F,
+}
+
+impl<'a, T, F: CoTaskMemFree> CoTaskMemArray<'a, T, F> {
+    /// Takes ownership of the array of `len` elements at `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be null or point to `len` initialized elements that can be freed with `free`.
+    /// Nothing else may free it.
+    pub unsafe fn from_raw(ptr: *mut T, len: usize, free: &'a F) -> CoTaskMemArray<'a, T, F> {
+        CoTaskMemArray { ptr, len, free }
+    }
+
+    /// Returns the elements. A null array is empty.
+    pub fn as_slice(&self) -> &[T] {
+        if self.ptr.is_null() {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl<'a, F: CoTaskMemFree> CoTaskMemArray<'a, *mut c_char, F> {
+    /// Frees the array and takes ownership of the strings in it.
+    ///
+    /// # Safety
+    ///
+    /// Every element must satisfy the requirements of `CoTaskMemString::from_raw`.
+    pub unsafe fn into_strings(self) -> Vec<CoTaskMemString<'a, F>> {
+        let free = self.free;
+        self.as_slice()
+            .iter()
+            .map(|&p| CoTaskMemString::from_raw(p, free))
+            .collect()
+    }
+}
+
+impl<'a, T, F: CoTaskMemFree> Drop for CoTaskMemArray<'a, T, F> {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe { self.free.co_task_mem_free(self.ptr as *mut c_void) };
+        }
+    }
+}
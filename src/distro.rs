@@ -0,0 +1,160 @@
+use std::fmt;
+use std::slice;
+use std::str::FromStr;
+use failure::Error;
+use format::Format;
+
+#[allow(non_camel_case_types)]
+type WSL_DISTRIBUTION_FLAGS = u32;
+
+bitflags! {
+    #[derive(Default)]
+    pub struct DistroFlags: WSL_DISTRIBUTION_FLAGS {
+        // const NONE = 0;
+        const ENABLE_INTEROP = 1;
+        const APPEND_NT_PATH = 2;
+        const ENABLE_DRIVE_MOUNTING = 4;
+    }
+}
+
+impl fmt::Display for DistroFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut v = vec![];
+        if self.is_empty() {
+            return write!(f, "NONE (0)");
+        }
+        if self.contains(DistroFlags::ENABLE_INTEROP) {
+            v.push("ENABLE_INTEROP (1)".to_string());
+        }
+        if self.contains(DistroFlags::APPEND_NT_PATH) {
+            v.push("APPEND_NT_PATH (2)".to_string());
+        }
+        if self.contains(DistroFlags::ENABLE_DRIVE_MOUNTING) {
+            v.push("ENABLE_DRIVE_MOUNTING (4)".to_string());
+        }
+        if self.unknown_bits() != 0 {
+            v.push(format!("UNKNOWN ({:#X})", self.unknown_bits()));
+        }
+        write!(f, "{}", &v[..].join(" | "))
+    }
+}
+
+impl DistroFlags {
+    /// Returns flags with all of `bits`, keeping the bits that this crate does not know, e.g. the
+    /// flags of newer Windows builds, so that they are written back unchanged.
+    pub fn from_raw(bits: WSL_DISTRIBUTION_FLAGS) -> DistroFlags {
+        // `bitflags` only requires the bits to be known for `!`, which stays within `all()`.
+        unsafe { DistroFlags::from_bits_unchecked(bits) }
+    }
+
+    /// Returns the bits that are none of the known flags.
+    pub fn unknown_bits(&self) -> WSL_DISTRIBUTION_FLAGS {
+        self.bits() & !DistroFlags::all().bits()
+    }
+
+    /// Returns the flag named `name`, either its full name such as `ENABLE_INTEROP` or its short
+    /// form such as `interop`, ignoring case.
+    fn from_name(name: &str) -> Option<DistroFlags> {
+        match &name.to_ascii_uppercase()[..] {
+            "NONE" => Some(DistroFlags::empty()),
+            "ENABLE_INTEROP" | "INTEROP" => Some(DistroFlags::ENABLE_INTEROP),
+            "APPEND_NT_PATH" | "NTPATH" => Some(DistroFlags::APPEND_NT_PATH),
+            "ENABLE_DRIVE_MOUNTING" | "MOUNT" => Some(DistroFlags::ENABLE_DRIVE_MOUNTING),
+            _ => None,
+        }
+    }
+}
+
+/// Parses flags from a decimal or `0x` hexadecimal value, which may have unknown bits, or from
+/// names separated by `|` or `,` such as `ENABLE_INTEROP|APPEND_NT_PATH` or `interop,ntpath,mount`.
+impl FromStr for DistroFlags {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<DistroFlags, Error> {
+        let s = s.trim();
+        let bits = if s.starts_with("0x") || s.starts_with("0X") {
+            Some(WSL_DISTRIBUTION_FLAGS::from_str_radix(&s[2..], 16)?)
+        } else if s.starts_with(|c: char| c.is_ascii_digit()) {
+            Some(s.parse::<WSL_DISTRIBUTION_FLAGS>()?)
+        } else {
+            None
+        };
+        if let Some(bits) = bits {
+            return Ok(DistroFlags::from_raw(bits));
+        }
+        s.split(&['|', ','][..])
+            .map(str::trim)
+            .try_fold(DistroFlags::empty(), |flags, name| {
+                DistroFlags::from_name(name)
+                    .map(|flag| flags | flag)
+                    .ok_or_else(|| format_err!("\"{}\" is not a flag name", name))
+            })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DistroConfiguration {
+    pub name: String,
+    pub version: u32,
+    pub default_uid: u32,
+    pub flags: DistroFlags,
+    pub default_environment_variables: Vec<String>,
+}
+
+impl DistroConfiguration {
+    /// Returns the environment variables that WSL gives a newly registered distro.
+    pub fn default_environment_variables() -> Vec<String> {
+        vec![
+            "HOSTTYPE=x86_64".to_string(),
+            "LANG=en_US.UTF-8".to_string(),
+            "PATH=/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin:/usr/games:\
+             /usr/local/games"
+                .to_string(),
+            "TERM=xterm-256color".to_string(),
+        ]
+    }
+
+    /// Serializes the configuration into TOML, which `from_toml` parses back.
+    ///
+    /// The distro is written as a table named after it, next to a `schema_version` key:
+    ///
+    /// ```toml
+    /// schema_version = 1
+    ///
+    /// [Ubuntu]
+    /// version = 1
+    /// default_uid = 1000
+    /// flags = 7
+    /// default_environment_variables = ["HOSTTYPE=x86_64", "LANG=en_US.UTF-8"]
+    /// ```
+    pub fn to_toml(&self) -> String {
+        DistroConfiguration::to_toml_all(slice::from_ref(self))
+    }
+
+    /// Serializes several configurations into one TOML document, one table each.
+    pub fn to_toml_all(distro_configurations: &[DistroConfiguration]) -> String {
+        Format::Toml.serialize(distro_configurations).unwrap()
+    }
+
+    /// Parses a TOML document with exactly one distro table.
+    pub fn from_toml(s: &str) -> Result<DistroConfiguration, Error> {
+        let mut distro_configurations = DistroConfiguration::from_toml_all(s)?;
+        match distro_configurations.len() {
+            1 => Ok(distro_configurations.remove(0)),
+            n => Err(format_err!("expected 1 WSL distro table, found {}", n)),
+        }
+    }
+
+    /// Parses a TOML document with any number of distro tables, ordered by name.
+    pub fn from_toml_all(s: &str) -> Result<Vec<DistroConfiguration>, Error> {
+        Format::Toml.deserialize(s)
+    }
+}
+
+impl FromStr for DistroConfiguration {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<DistroConfiguration, Error> {
+        DistroConfiguration::from_toml(s)
+    }
+}
@@ -0,0 +1,190 @@
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use failure::Error;
+use backend::WslBackend;
+use distro::{DistroConfiguration, DistroFlags};
+use process::{LaunchStdio, WslProcess};
+use wsl_error::WslError;
+
+/// A process launched through `FakeBackend::launch`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FakeLaunch {
+    pub distro_name: String,
+    pub command: String,
+    pub use_cwd: bool,
+    /// The default user ID of the distro, which the command runs as
+    pub default_uid: u32,
+    /// What a spawned process reads from stdin, which is empty unless stdin is given
+    pub stdin: Vec<u8>,
+}
+
+/// An in-memory `WslBackend` that does not need Windows.
+///
+/// Registered distros only live as long as the `FakeBackend` does. Launches are recorded
+/// instead of being executed.
+#[derive(Debug, Default)]
+pub struct FakeBackend {
+    distros: RefCell<BTreeMap<String, DistroConfiguration>>,
+    launches: RefCell<Vec<FakeLaunch>>,
+    launch_exit_code: Cell<u32>,
+    launch_stdout: RefCell<Vec<u8>>,
+    launch_stderr: RefCell<Vec<u8>>,
+}
+
+/// A process spawned by `FakeBackend::spawn`. It has already exited.
+#[derive(Debug)]
+pub struct FakeProcess {
+    exit_code: u32,
+}
+
+impl WslProcess for FakeProcess {
+    fn wait(&mut self) -> Result<u32, Error> {
+        Ok(self.exit_code)
+    }
+
+    fn try_wait(&mut self) -> Result<Option<u32>, Error> {
+        Ok(Some(self.exit_code))
+    }
+
+    fn exit_code(&self) -> Option<u32> {
+        Some(self.exit_code)
+    }
+
+    fn kill(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl FakeBackend {
+    pub fn new() -> FakeBackend {
+        FakeBackend::default()
+    }
+
+    /// Adds `distro_configuration` as a registered distro, replacing any distro with the same
+    /// name.
+    pub fn insert_distro(&self, distro_configuration: DistroConfiguration) {
+        self.distros.borrow_mut().insert(
+            distro_configuration.name.clone(),
+            distro_configuration,
+        );
+    }
+
+    /// Sets the exit code that `launch` returns and spawned processes exit with.
+    pub fn set_launch_exit_code(&self, exit_code: u32) {
+        self.launch_exit_code.set(exit_code);
+    }
+
+    /// Sets what spawned processes write to stdout and stderr.
+    ///
+    /// The output is written while spawning, so it must fit in a pipe buffer when the streams
+    /// are pipes.
+    pub fn set_launch_output(&self, stdout: &[u8], stderr: &[u8]) {
+        *self.launch_stdout.borrow_mut() = stdout.to_vec();
+        *self.launch_stderr.borrow_mut() = stderr.to_vec();
+    }
+
+    /// Returns all launches so far, oldest first.
+    pub fn launches(&self) -> Vec<FakeLaunch> {
+        self.launches.borrow().clone()
+    }
+
+    /// Records a launch and returns the exit code that it exits with.
+    fn record(
+        &self,
+        distro_name: &str,
+        command: &str,
+        use_cwd: bool,
+        stdin: Vec<u8>,
+    ) -> Result<u32, Error> {
+        self.registered(distro_name)?;
+        let default_uid = self.distros.borrow()[distro_name].default_uid;
+        self.launches.borrow_mut().push(FakeLaunch {
+            distro_name: distro_name.to_string(),
+            command: command.to_string(),
+            use_cwd,
+            default_uid,
+            stdin,
+        });
+        Ok(self.launch_exit_code.get())
+    }
+
+    fn registered(&self, distro_name: &str) -> Result<(), Error> {
+        if self.distros.borrow().contains_key(distro_name) {
+            Ok(())
+        } else {
+            Err(WslError::InvalidArgument.into())
+        }
+    }
+}
+
+impl WslBackend for FakeBackend {
+    type Process = FakeProcess;
+
+    fn register_distro(&self, distro_name: &str, _tar_gz_filename: &str) -> Result<(), Error> {
+        if self.distros.borrow().contains_key(distro_name) {
+            return Err(WslError::AlreadyExists.into());
+        }
+        self.insert_distro(DistroConfiguration {
+            name: distro_name.to_string(),
+            version: 1,
+            default_uid: 0,
+            flags: DistroFlags::all(),
+            default_environment_variables: DistroConfiguration::default_environment_variables(),
+        });
+        Ok(())
+    }
+
+    fn unregister_distro(&self, distro_name: &str) -> Result<(), Error> {
+        self.registered(distro_name)?;
+        self.distros.borrow_mut().remove(distro_name);
+        Ok(())
+    }
+
+    fn get_distro_configuration(
+        &self,
+        distro_name: &str,
+    ) -> Result<DistroConfiguration, Error> {
+        self.registered(distro_name)?;
+        Ok(self.distros.borrow()[distro_name].clone())
+    }
+
+    fn configure_distro(&self, distro_configuration: &DistroConfiguration) -> Result<(), Error> {
+        self.registered(&distro_configuration.name)?;
+        let mut distros = self.distros.borrow_mut();
+        let distro = distros.get_mut(&distro_configuration.name).unwrap();
+        distro.default_uid = distro_configuration.default_uid;
+        distro.flags = distro_configuration.flags;
+        Ok(())
+    }
+
+    fn launch(&self, distro_name: &str, command: &str, use_cwd: bool) -> Result<u32, Error> {
+        self.record(distro_name, command, use_cwd, vec![])
+    }
+
+    fn spawn(
+        &self,
+        distro_name: &str,
+        command: &str,
+        use_cwd: bool,
+        stdio: LaunchStdio,
+    ) -> Result<FakeProcess, Error> {
+        let mut input = vec![];
+        if let Some(mut stdin) = stdio.stdin {
+            stdin.read_to_end(&mut input)?;
+        }
+        let exit_code = self.record(distro_name, command, use_cwd, input)?;
+        let write = |file: Option<File>, buf: &[u8]| match file {
+            Some(mut file) => file.write_all(buf),
+            None => Ok(()),
+        };
+        write(stdio.stdout, &self.launch_stdout.borrow())?;
+        write(stdio.stderr, &self.launch_stderr.borrow())?;
+        Ok(FakeProcess { exit_code })
+    }
+
+    fn is_distribution_registered(&self, distro_name: &str) -> Result<bool, Error> {
+        Ok(self.distros.borrow().contains_key(distro_name))
+    }
+}
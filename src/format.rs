@@ -0,0 +1,325 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use failure::Error;
+use serde_json;
+use serde_yaml;
+use toml;
+use distro::{DistroConfiguration, DistroFlags};
+
+#[allow(non_camel_case_types)]
+type WSL_DISTRIBUTION_FLAGS = u32;
+
+/// The version of the documents written by `Format::serialize`.
+const SCHEMA_VERSION: u32 = 1;
+
+/// A text format for a list of `DistroConfiguration`s.
+///
+/// TOML, JSON and YAML hold a `schema_version` key and one table or object per distro, named
+/// after it. `Env` holds shell assignments that a POSIX shell can source, such as
+/// `YOWSL_DEFAULT_UID=1000`, and starts the variables of the distro with `YOWSL_NAME`. As the
+/// variables of a second distro would overwrite them, `Env` holds at most one distro.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Json,
+    Yaml,
+    Env,
+}
+
+impl Format {
+    /// The names that `Format::from_str` accepts.
+    pub const NAMES: [&'static str; 4] = ["toml", "json", "yaml", "env"];
+
+    pub fn serialize(
+        &self,
+        distro_configurations: &[DistroConfiguration],
+    ) -> Result<String, Error> {
+        self.serialize_with_default_users(distro_configurations, &BTreeMap::new())
+    }
+
+    /// Serializes `distro_configurations` like `serialize`, adding the name of the default user
+    /// of each distro in `default_users` next to its ID. `deserialize` ignores the names.
+    pub fn serialize_with_default_users(
+        &self,
+        distro_configurations: &[DistroConfiguration],
+        default_users: &BTreeMap<String, String>,
+    ) -> Result<String, Error> {
+        let document = Document {
+            schema_version: SCHEMA_VERSION,
+            distros: distro_configurations
+                .iter()
+                .map(|c| {
+                    let mut distro = DocumentDistro::from(c);
+                    distro.default_user = default_users.get(&c.name).cloned();
+                    (c.name.clone(), distro)
+                })
+                .collect(),
+        };
+        match *self {
+            Format::Toml => Ok(toml::to_string(&document)?),
+            Format::Json => Ok(serde_json::to_string_pretty(&document)? + "\n"),
+            Format::Yaml => Ok(serde_yaml::to_string(&document)?),
+            Format::Env => to_env(distro_configurations, default_users),
+        }
+    }
+
+    /// Parses a document written by `serialize`. Distros are ordered by name.
+    pub fn deserialize(&self, s: &str) -> Result<Vec<DistroConfiguration>, Error> {
+        let document: Document = match *self {
+            Format::Toml => toml::from_str(s)?,
+            Format::Json => serde_json::from_str(s)?,
+            Format::Yaml => serde_yaml::from_str(s)?,
+            Format::Env => return from_env(s),
+        };
+        check_schema_version(document.schema_version)?;
+        Ok(document
+            .distros
+            .into_iter()
+            .map(|(name, distro)| DistroConfiguration {
+                name,
+                version: distro.version,
+                default_uid: distro.default_uid,
+                flags: DistroFlags::from_raw(distro.flags),
+                default_environment_variables: distro.default_environment_variables,
+            })
+            .collect())
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Format::Toml => "toml",
+            Format::Json => "json",
+            Format::Yaml => "yaml",
+            Format::Env => "env",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Format, Error> {
+        match &s.to_ascii_lowercase()[..] {
+            "toml" => Ok(Format::Toml),
+            "json" => Ok(Format::Json),
+            "yaml" | "yml" => Ok(Format::Yaml),
+            "env" => Ok(Format::Env),
+            _ => Err(format_err!("\"{}\" is not a format", s)),
+        }
+    }
+}
+
+fn check_schema_version(schema_version: u32) -> Result<(), Error> {
+    if schema_version != SCHEMA_VERSION {
+        return Err(format_err!(
+            "schema_version {} is not supported. Expected {}",
+            schema_version,
+            SCHEMA_VERSION
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize)]
+struct Document {
+    schema_version: u32,
+    #[serde(flatten)]
+    distros: BTreeMap<String, DocumentDistro>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DocumentDistro {
+    version: u32,
+    default_uid: u32,
+    /// The name of `default_uid`, which is only for people to read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default_user: Option<String>,
+    flags: WSL_DISTRIBUTION_FLAGS,
+    default_environment_variables: Vec<String>,
+}
+
+impl<'a> From<&'a DistroConfiguration> for DocumentDistro {
+    fn from(distro_configuration: &'a DistroConfiguration) -> DocumentDistro {
+        DocumentDistro {
+            version: distro_configuration.version,
+            default_uid: distro_configuration.default_uid,
+            default_user: None,
+            flags: distro_configuration.flags.bits(),
+            default_environment_variables: distro_configuration
+                .default_environment_variables
+                .clone(),
+        }
+    }
+}
+
+/// Quotes `arg` for a POSIX shell unless it only contains safe characters.
+pub fn shell_quote(arg: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "%+,-./:=@_".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+const ENV_PREFIX: &str = "YOWSL_DEFAULT_ENVIRONMENT_VARIABLE_";
+
+fn to_env(
+    distro_configurations: &[DistroConfiguration],
+    default_users: &BTreeMap<String, String>,
+) -> Result<String, Error> {
+    if distro_configurations.len() > 1 {
+        return Err(format_err!(
+            "env holds only one distro, but there are {}",
+            distro_configurations.len()
+        ));
+    }
+    let mut s = format!("YOWSL_SCHEMA_VERSION={}\n", SCHEMA_VERSION);
+    for distro_configuration in distro_configurations {
+        s += &format!(
+            "\nYOWSL_NAME={}\nYOWSL_VERSION={}\nYOWSL_DEFAULT_UID={}\n",
+            shell_quote(&distro_configuration.name),
+            distro_configuration.version,
+            distro_configuration.default_uid
+        );
+        if let Some(default_user) = default_users.get(&distro_configuration.name) {
+            s += &format!("YOWSL_DEFAULT_USER={}\n", shell_quote(default_user));
+        }
+        s += &format!("YOWSL_FLAGS={}\n", distro_configuration.flags.bits());
+        for (i, variable) in distro_configuration
+            .default_environment_variables
+            .iter()
+            .enumerate()
+        {
+            s += &format!("{}{}={}\n", ENV_PREFIX, i, shell_quote(variable));
+        }
+    }
+    Ok(s)
+}
+
+/// Splits shell assignments written by `to_env` into names and unquoted values. Blank lines and
+/// comments are skipped.
+fn parse_assignments(s: &str) -> Result<Vec<(String, String)>, Error> {
+    let mut assignments = vec![];
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' | ' ' | '\t' | '\r' => continue,
+            '#' => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+                continue;
+            }
+            _ => {}
+        }
+        let mut name = c.to_string();
+        loop {
+            match chars.next() {
+                Some('=') => break,
+                Some(c) if c.is_ascii_alphanumeric() || c == '_' => name.push(c),
+                _ => return Err(format_err!("\"{}\" is not followed by =", name)),
+            }
+        }
+        let mut value = String::new();
+        loop {
+            match chars.next() {
+                None | Some('\n') => break,
+                Some('\'') => loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => value.push(c),
+                        None => return Err(format_err!("the value of {} is not closed", name)),
+                    }
+                },
+                Some('\\') => match chars.next() {
+                    Some(c) => value.push(c),
+                    None => return Err(format_err!("the value of {} ends with \\", name)),
+                },
+                Some(c) if c == ' ' || c == '\t' || c == '\r' => {
+                    return Err(format_err!("the value of {} has unquoted whitespace", name))
+                }
+                Some(c) => value.push(c),
+            }
+        }
+        assignments.push((name, value));
+    }
+    Ok(assignments)
+}
+
+/// A distro in `Env` whose fields other than the name are `None` until they are assigned.
+struct EnvDistro {
+    name: String,
+    version: Option<u32>,
+    default_uid: Option<u32>,
+    flags: Option<DistroFlags>,
+    default_environment_variables: Vec<String>,
+}
+
+fn from_env(s: &str) -> Result<Vec<DistroConfiguration>, Error> {
+    let mut assignments = parse_assignments(s)?.into_iter();
+    match assignments.next() {
+        Some((ref name, ref value)) if name == "YOWSL_SCHEMA_VERSION" => {
+            check_schema_version(value.parse()?)?
+        }
+        _ => return Err(format_err!("YOWSL_SCHEMA_VERSION is not the first variable")),
+    }
+    let mut distros: Vec<EnvDistro> = vec![];
+    for (name, value) in assignments {
+        if name == "YOWSL_NAME" {
+            if !distros.is_empty() {
+                return Err(format_err!("YOWSL_NAME is set twice, but env holds only one distro"));
+            }
+            distros.push(EnvDistro {
+                name: value,
+                version: None,
+                default_uid: None,
+                flags: None,
+                default_environment_variables: vec![],
+            });
+            continue;
+        }
+        let distro = match distros.last_mut() {
+            Some(distro) => distro,
+            None => return Err(format_err!("{} is set before YOWSL_NAME", name)),
+        };
+        let variables = &mut distro.default_environment_variables;
+        match &name[..] {
+            "YOWSL_VERSION" => distro.version = Some(value.parse()?),
+            "YOWSL_DEFAULT_UID" => distro.default_uid = Some(value.parse()?),
+            "YOWSL_DEFAULT_USER" => {}
+            "YOWSL_FLAGS" => distro.flags = Some(DistroFlags::from_raw(value.parse()?)),
+            _ if name.starts_with(ENV_PREFIX)
+                && name[ENV_PREFIX.len()..] == variables.len().to_string() =>
+            {
+                variables.push(value)
+            }
+            _ => return Err(format_err!("{} is not expected", name)),
+        }
+    }
+    distros
+        .into_iter()
+        .map(|distro| match distro {
+            EnvDistro {
+                name,
+                version: Some(version),
+                default_uid: Some(default_uid),
+                flags: Some(flags),
+                default_environment_variables,
+            } => Ok(DistroConfiguration {
+                name,
+                version,
+                default_uid,
+                flags,
+                default_environment_variables,
+            }),
+            EnvDistro { name, .. } => Err(format_err!(
+                "YOWSL_VERSION, YOWSL_DEFAULT_UID or YOWSL_FLAGS of \"{}\" is missing",
+                name
+            )),
+        })
+        .collect()
+}
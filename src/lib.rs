@@ -1,12 +1,73 @@
-#![cfg(all(target_arch = "x86_64", target_os = "windows"))]
-
 #[macro_use]
 extern crate bitflags;
+extern crate bzip2;
 #[macro_use]
 extern crate failure;
+extern crate flate2;
+#[cfg(all(target_arch = "x86_64", target_os = "windows"))]
 extern crate libloading;
+extern crate lzma_rs;
+extern crate ruzstd;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate serde_yaml;
+extern crate tar;
+extern crate toml;
 
+mod additions;
+mod archive;
+mod backend;
+mod co_task_mem;
+mod distro;
+mod fake_backend;
+mod format;
+mod lxss;
+mod move_distro;
+mod new_user;
+mod normalize;
+mod oci;
+mod passwd;
+mod process;
+mod reg_file;
+mod registry;
+mod rootfs_check;
+mod simulator;
+#[cfg(all(target_arch = "x86_64", target_os = "windows"))]
 mod wide_chars;
+#[cfg(all(target_arch = "x86_64", target_os = "windows"))]
+mod windows_fs;
+#[cfg(all(target_arch = "x86_64", target_os = "windows"))]
+mod windows_registry;
+#[cfg(all(target_arch = "x86_64", target_os = "windows"))]
 mod wslapi;
+mod wsl_error;
 
-pub use wslapi::{DistroConfiguration, DistroFlags, Wslapi};
+pub use additions::{add_to_tar_gz, validate_user_name, Additions, FileAddition, UserAddition};
+pub use archive::{open_tar, write_tar_gz, SourceFormat};
+pub use backend::WslBackend;
+pub use co_task_mem::{CoTaskMemArray, CoTaskMemFree, CoTaskMemString};
+pub use distro::{DistroConfiguration, DistroFlags};
+pub use fake_backend::{FakeBackend, FakeLaunch, FakeProcess};
+pub use format::{shell_quote, Format};
+pub use lxss::{default_distro, default_distro_guid, find_distro, list_distros, read_distro,
+               read_distros, rename_distro, set_default_distro, set_default_environment,
+               validate_distro_name, DistroState, LxssDistro, LXSS_KEY};
+pub use move_distro::{copy_tree, move_distro, verify_tree, CopyProgress};
+pub use new_user::{create_user, validate_password_hash, NewPassword, NewUser, UserReport};
+pub use normalize::normalize_tar_gz;
+pub use oci::{write_oci_tar_gz, ImageConfig, ImageSelector, OciImage};
+pub use passwd::{parse_passwd, read_passwd, read_rootfs_passwd, uid_of, user_name_of,
+                 PasswdUser};
+pub use process::{join_read, null_file, pipe, read_to_end_in_thread, HostProcess, LaunchStdio,
+                  Output, PipeReader, WslProcess};
+pub use reg_file::{format_reg, parse_reg, RegFile};
+pub use registry::{MemoryRegistry, Registry, RegistryValue};
+pub use rootfs_check::{check_rootfs, check_tar, Problem, Severity};
+pub use simulator::{SimulatedLaunch, SimulatorBackend};
+#[cfg(all(target_arch = "x86_64", target_os = "windows"))]
+pub use windows_registry::WindowsRegistry;
+#[cfg(all(target_arch = "x86_64", target_os = "windows"))]
+pub use wslapi::{Capability, Wslapi, WslapiProcess};
+pub use wsl_error::WslError;
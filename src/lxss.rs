@@ -0,0 +1,232 @@
+use std::fmt;
+use failure::Error;
+use distro::{DistroConfiguration, DistroFlags};
+use registry::{Registry, RegistryValue};
+
+/// The registry key under which WSL keeps the registered distros of the current user.
+pub const LXSS_KEY: &str = "HKEY_CURRENT_USER\\Software\\Microsoft\\Windows\\CurrentVersion\\Lxss";
+
+/// The state of a WSL distro as recorded in the `State` value of its key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DistroState {
+    /// 1
+    Installed,
+    /// 3
+    Installing,
+    /// 4
+    Uninstalling,
+    /// 5
+    Converting,
+    /// Any other value
+    Other(u32),
+}
+
+impl DistroState {
+    pub fn from_u32(state: u32) -> DistroState {
+        match state {
+            1 => DistroState::Installed,
+            3 => DistroState::Installing,
+            4 => DistroState::Uninstalling,
+            5 => DistroState::Converting,
+            state => DistroState::Other(state),
+        }
+    }
+
+    pub fn to_u32(&self) -> u32 {
+        match *self {
+            DistroState::Installed => 1,
+            DistroState::Installing => 3,
+            DistroState::Uninstalling => 4,
+            DistroState::Converting => 5,
+            DistroState::Other(state) => state,
+        }
+    }
+}
+
+impl fmt::Display for DistroState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DistroState::Installed => write!(f, "Installed"),
+            DistroState::Installing => write!(f, "Installing"),
+            DistroState::Uninstalling => write!(f, "Uninstalling"),
+            DistroState::Converting => write!(f, "Converting"),
+            DistroState::Other(state) => write!(f, "Unknown ({})", state),
+        }
+    }
+}
+
+/// A WSL distro as registered under `LXSS_KEY`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LxssDistro {
+    /// The name of the subkey, e.g. `{12345678-1234-1234-1234-123456789ABC}`
+    pub guid: String,
+    pub base_path: String,
+    pub state: DistroState,
+    pub configuration: DistroConfiguration,
+    /// Whether this is the `DefaultDistribution`
+    pub is_default: bool,
+}
+
+/// Returns the value `name` of `key` with `f` applied, or `None` if it is missing.
+fn get<R: Registry, T, F>(registry: &R, key: &str, name: &str, f: F) -> Result<Option<T>, Error>
+where
+    F: FnOnce(&RegistryValue) -> Option<T>,
+{
+    match registry.value(key, name)? {
+        Some(value) => match f(&value) {
+            Some(t) => Ok(Some(t)),
+            None => Err(format_err!("{}\\{} has an unexpected type: {:?}", key, name, value)),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Returns the GUID of the default WSL distro if there is one.
+pub fn default_distro_guid<R: Registry>(registry: &R) -> Result<Option<String>, Error> {
+    get(registry, LXSS_KEY, "DefaultDistribution", |v| {
+        v.as_str().map(str::to_string)
+    })
+}
+
+/// Reads the WSL distro under the subkey `guid` of `LXSS_KEY`.
+///
+/// Missing values are the ones that WSL assumes: version 1, user ID 0, all the known flags and
+/// no environment variables.
+pub fn read_distro<R: Registry>(registry: &R, guid: &str) -> Result<LxssDistro, Error> {
+    let key = format!("{}\\{}", LXSS_KEY, guid);
+    let name = get(registry, &key, "DistributionName", |v| {
+        v.as_str().map(str::to_string)
+    })?.ok_or_else(|| format_err!("{} does not have DistributionName", key))?;
+    let base_path = get(registry, &key, "BasePath", |v| v.as_str().map(str::to_string))?
+        .ok_or_else(|| format_err!("{} does not have BasePath", key))?;
+    let state = get(registry, &key, "State", RegistryValue::as_dword)?.unwrap_or(1);
+    let version = get(registry, &key, "Version", RegistryValue::as_dword)?.unwrap_or(1);
+    let default_uid = get(registry, &key, "DefaultUid", RegistryValue::as_dword)?.unwrap_or(0);
+    let flags = get(registry, &key, "Flags", RegistryValue::as_dword)?
+        .map_or_else(DistroFlags::all, DistroFlags::from_raw);
+    let default_environment_variables = get(registry, &key, "DefaultEnvironment", |v| {
+        v.as_multi_sz().map(<[String]>::to_vec)
+    })?.unwrap_or_else(Vec::new);
+    let is_default = default_distro_guid(registry)?
+        .is_some_and(|default| default.eq_ignore_ascii_case(guid));
+    Ok(LxssDistro {
+        guid: guid.to_string(),
+        base_path,
+        state: DistroState::from_u32(state),
+        configuration: DistroConfiguration {
+            name,
+            version,
+            default_uid,
+            flags,
+            default_environment_variables,
+        },
+        is_default,
+    })
+}
+
+/// Lists the registered WSL distros in the order that the registry enumerates them.
+pub fn list_distros<R: Registry>(registry: &R) -> Result<Vec<LxssDistro>, Error> {
+    read_distros(registry)?.into_iter().collect()
+}
+
+/// Reads every registered WSL distro like `list_distros`, but keeps going after a subkey that
+/// cannot be read, so that it does not hide the others.
+pub fn read_distros<R: Registry>(registry: &R) -> Result<Vec<Result<LxssDistro, Error>>, Error> {
+    Ok(registry
+        .subkeys(LXSS_KEY)?
+        .iter()
+        .filter(|guid| guid.starts_with('{'))
+        .map(|guid| read_distro(registry, guid))
+        .collect())
+}
+
+/// Finds the registered WSL distro named `name`, ignoring case like WSL does.
+pub fn find_distro<R: Registry>(registry: &R, name: &str) -> Result<Option<LxssDistro>, Error> {
+    Ok(list_distros(registry)?
+        .into_iter()
+        .find(|distro| distro.configuration.name.eq_ignore_ascii_case(name)))
+}
+
+/// Returns the default WSL distro if there is one.
+pub fn default_distro<R: Registry>(registry: &R) -> Result<Option<LxssDistro>, Error> {
+    Ok(list_distros(registry)?
+        .into_iter()
+        .find(|distro| distro.is_default))
+}
+
+/// Makes the WSL distro named `name` the default one and returns it.
+pub fn set_default_distro<R: Registry>(registry: &R, name: &str) -> Result<LxssDistro, Error> {
+    let mut distro = match find_distro(registry, name)? {
+        Some(distro) => distro,
+        None => return Err(format_err!("\"{}\" cannot be found under {}", name, LXSS_KEY)),
+    };
+    registry.set_value(
+        LXSS_KEY,
+        "DefaultDistribution",
+        &RegistryValue::Sz(distro.guid.clone()),
+    )?;
+    distro.is_default = true;
+    Ok(distro)
+}
+
+/// Checks that `name` can name a WSL distro, which WSL limits to ASCII letters, digits, `.`, `_`
+/// and `-`.
+pub fn validate_distro_name(name: &str) -> Result<(), Error> {
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-')
+    {
+        Ok(())
+    } else {
+        Err(format_err!(
+            "\"{}\" is not a valid WSL distro name. Only letters, digits, '.', '_' and '-' are \
+             allowed",
+            name
+        ))
+    }
+}
+
+/// Renames the WSL distro named `old` to `new` and returns it with the new name.
+///
+/// Only `DistributionName` changes, so the files of the WSL distro stay where they are.
+pub fn rename_distro<R: Registry>(registry: &R, old: &str, new: &str) -> Result<LxssDistro, Error> {
+    validate_distro_name(new)?;
+    let mut distro = match find_distro(registry, old)? {
+        Some(distro) => distro,
+        None => return Err(format_err!("\"{}\" cannot be found under {}", old, LXSS_KEY)),
+    };
+    // Changing only the case of the name finds the same WSL distro.
+    if let Some(other) = find_distro(registry, new)? {
+        if other.guid != distro.guid {
+            return Err(format_err!("\"{}\" is already under {}", new, LXSS_KEY));
+        }
+    }
+    registry.set_value(
+        &format!("{}\\{}", LXSS_KEY, distro.guid),
+        "DistributionName",
+        &RegistryValue::Sz(new.to_string()),
+    )?;
+    distro.configuration.name = new.to_string();
+    Ok(distro)
+}
+
+/// Sets `DefaultEnvironment` of the WSL distro named `name`, which WSL passes to every process
+/// but `WslConfigureDistribution` cannot change.
+pub fn set_default_environment<R: Registry>(
+    registry: &R,
+    name: &str,
+    variables: &[String],
+) -> Result<LxssDistro, Error> {
+    let mut distro = match find_distro(registry, name)? {
+        Some(distro) => distro,
+        None => return Err(format_err!("\"{}\" cannot be found under {}", name, LXSS_KEY)),
+    };
+    registry.set_value(
+        &format!("{}\\{}", LXSS_KEY, distro.guid),
+        "DefaultEnvironment",
+        &RegistryValue::MultiSz(variables.to_vec()),
+    )?;
+    distro.configuration.default_environment_variables = variables.to_vec();
+    Ok(distro)
+}
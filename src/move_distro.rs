@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use failure::Error;
+use lxss::{find_distro, LxssDistro, LXSS_KEY};
+use registry::{Registry, RegistryValue};
+#[cfg(all(target_arch = "x86_64", target_os = "windows"))]
+use windows_fs;
+
+/// How far `copy_tree` has got.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CopyProgress {
+    pub copied_bytes: u64,
+    pub total_bytes: u64,
+    pub copied_files: u64,
+    pub total_files: u64,
+}
+
+/// Returns the number of bytes and files in `path`, without following symbolic links. Folders
+/// themselves are not counted.
+fn measure(path: &Path) -> Result<(u64, u64), Error> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok((metadata.len(), 1));
+    }
+    let mut total = (0, 0);
+    for entry in fs::read_dir(path)? {
+        let (bytes, files) = measure(&entry?.path())?;
+        total.0 += bytes;
+        total.1 += files;
+    }
+    Ok(total)
+}
+
+#[cfg(unix)]
+fn copy_symlink(src: &Path, dest: &Path) -> Result<(), Error> {
+    Ok(::std::os::unix::fs::symlink(fs::read_link(src)?, dest)?)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(src: &Path, _dest: &Path) -> Result<(), Error> {
+    Err(format_err!("{} is a symbolic link, which cannot be copied", src.display()))
+}
+
+/// Returns an ID that the hard links of the file `path` share, or `None` if it has no other
+/// hard link.
+#[cfg(unix)]
+fn hard_link_id(_path: &Path, metadata: &fs::Metadata) -> Result<Option<(u64, u64)>, Error> {
+    use std::os::unix::fs::MetadataExt;
+    Ok(if metadata.nlink() > 1 { Some((metadata.dev(), metadata.ino())) } else { None })
+}
+
+#[cfg(all(target_arch = "x86_64", target_os = "windows"))]
+fn hard_link_id(path: &Path, _metadata: &fs::Metadata) -> Result<Option<(u64, u64)>, Error> {
+    windows_fs::hard_link_id(path).map_err(|e| format_err!("{}: {}", path.display(), e))
+}
+
+#[cfg(not(any(unix, all(target_arch = "x86_64", target_os = "windows"))))]
+fn hard_link_id(_path: &Path, _metadata: &fs::Metadata) -> Result<Option<(u64, u64)>, Error> {
+    Ok(None)
+}
+
+/// Creates the folder `dest` with the owner, the mode and the case sensitivity of `src`.
+#[cfg(all(target_arch = "x86_64", target_os = "windows"))]
+fn create_dir_like(src: &Path, dest: &Path) -> Result<(), Error> {
+    windows_fs::create_dir_like(src, dest).map_err(|e| format_err!("{}: {}", dest.display(), e))
+}
+
+/// Creates the folder `dest`, whose mode `copy_entries` sets after filling it.
+#[cfg(not(all(target_arch = "x86_64", target_os = "windows")))]
+fn create_dir_like(_src: &Path, dest: &Path) -> Result<(), Error> {
+    Ok(fs::create_dir(dest)?)
+}
+
+/// `links` maps the `hard_link_id`s of the files copied so far to their copies, so that the other
+/// hard links of a file become hard links to its copy.
+fn copy_entries<F>(
+    src: &Path,
+    dest: &Path,
+    links: &mut HashMap<(u64, u64), PathBuf>,
+    progress: &mut CopyProgress,
+    f: &mut F,
+) -> Result<(), Error>
+where
+    F: FnMut(&CopyProgress),
+{
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let src = entry.path();
+        let dest = dest.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            create_dir_like(&src, &dest)?;
+            copy_entries(&src, &dest, links, progress, f)?;
+            // Only the read-only attribute is a permission on Windows, which `create_dir_like`
+            // has already copied. Elsewhere the mode is set last so that a folder without write
+            // permission can still be filled.
+            #[cfg(not(all(target_arch = "x86_64", target_os = "windows")))]
+            fs::set_permissions(&dest, fs::metadata(&src)?.permissions())?;
+            continue;
+        }
+        if file_type.is_symlink() {
+            copy_symlink(&src, &dest)?;
+        } else {
+            let id = hard_link_id(&src, &entry.metadata()?)?;
+            match id.and_then(|id| links.get(&id)) {
+                Some(copy) => fs::hard_link(copy, &dest)
+                    .map_err(|e| format_err!("{}: {}", dest.display(), e))?,
+                None => {
+                    // `fs::copy` uses CopyFileExW on Windows, which keeps the extended attributes
+                    // where WSL stores the owners and modes of the files in `rootfs`.
+                    fs::copy(&src, &dest).map_err(|e| format_err!("{}: {}", src.display(), e))?;
+                    if let Some(id) = id {
+                        links.insert(id, dest.clone());
+                    }
+                }
+            }
+        }
+        progress.copied_bytes += fs::symlink_metadata(&dest)?.len();
+        progress.copied_files += 1;
+        f(progress);
+    }
+    Ok(())
+}
+
+/// Copies the files under `src` into the existing folder `dest`, calling `f` after each file.
+/// Hard links to one file are copied as hard links to one copy.
+pub fn copy_tree<F>(src: &Path, dest: &Path, mut f: F) -> Result<CopyProgress, Error>
+where
+    F: FnMut(&CopyProgress),
+{
+    let (total_bytes, total_files) = measure(src)?;
+    let mut progress = CopyProgress {
+        copied_bytes: 0,
+        total_bytes,
+        copied_files: 0,
+        total_files,
+    };
+    f(&progress);
+    copy_entries(src, dest, &mut HashMap::new(), &mut progress, &mut f)?;
+    Ok(progress)
+}
+
+/// Checks that `dest` has the same files, folders and file sizes as `src`.
+pub fn verify_tree(src: &Path, dest: &Path) -> Result<(), Error> {
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let src = entry.path();
+        let dest = dest.join(entry.file_name());
+        let src_metadata = fs::symlink_metadata(&src)?;
+        let dest_metadata = match fs::symlink_metadata(&dest) {
+            Ok(metadata) => metadata,
+            Err(_) => return Err(format_err!("{} is missing", dest.display())),
+        };
+        if src_metadata.file_type() != dest_metadata.file_type() {
+            return Err(format_err!("{} is not the same kind as {}", dest.display(), src.display()));
+        }
+        if src_metadata.is_dir() {
+            verify_tree(&src, &dest)?;
+        } else if src_metadata.len() != dest_metadata.len() {
+            return Err(format_err!("{} is not the same size as {}", dest.display(), src.display()));
+        }
+    }
+    Ok(())
+}
+
+/// Returns `path` as the registry keeps it, without the `\\?\` that `fs::canonicalize` adds on
+/// Windows.
+fn registry_path(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    match path.strip_prefix("\\\\?\\") {
+        Some(path) if !path.starts_with("UNC\\") => path.to_string(),
+        _ => path.into_owned(),
+    }
+}
+
+/// Checks that nothing has a file under `base_path` open.
+///
+/// Windows refuses to rename a folder while a file in it is open, which is the case for `rootfs`
+/// while the WSL distro is running. Other platforms let it through.
+fn check_not_in_use(base_path: &Path) -> Result<(), Error> {
+    let mut probe = base_path.as_os_str().to_os_string();
+    probe.push(".yowsl-move");
+    let probe = PathBuf::from(probe);
+    if probe.exists() {
+        return Err(format_err!("{} is in the way; remove it first", probe.display()));
+    }
+    if let Err(e) = fs::rename(base_path, &probe) {
+        return Err(format_err!(
+            "{} is in use; terminate the WSL distro with `wslconfig /terminate` first ({})",
+            base_path.display(),
+            e
+        ));
+    }
+    fs::rename(&probe, base_path).map_err(|e| {
+        format_err!("{} cannot be renamed back to {}: {}", probe.display(), base_path.display(), e)
+    })
+}
+
+/// Copies the folder of the WSL distro named `name` into `dest` and points its `BasePath` there.
+///
+/// `dest` must be missing or empty. If anything fails, `dest` is removed again and `BasePath`
+/// is left alone. The old folder is kept for the caller to remove, and the moved WSL distro is
+/// returned.
+pub fn move_distro<R, F>(registry: &R, name: &str, dest: &Path, f: F) -> Result<LxssDistro, Error>
+where
+    R: Registry,
+    F: FnMut(&CopyProgress),
+{
+    let mut distro = match find_distro(registry, name)? {
+        Some(distro) => distro,
+        None => return Err(format_err!("\"{}\" cannot be found under {}", name, LXSS_KEY)),
+    };
+    let src = fs::canonicalize(&distro.base_path)
+        .map_err(|e| format_err!("{}: {}", distro.base_path, e))?;
+    let dest_existed = dest.exists();
+    if dest_existed && fs::read_dir(dest)?.next().is_some() {
+        return Err(format_err!("{} is not empty", dest.display()));
+    }
+    fs::create_dir_all(dest)?;
+    let dest = fs::canonicalize(dest)?;
+    let rollback = |e: Error| {
+        let _ = fs::remove_dir_all(&dest);
+        if dest_existed {
+            let _ = fs::create_dir(&dest);
+        }
+        e
+    };
+    if dest.starts_with(&src) || src.starts_with(&dest) {
+        return Err(rollback(format_err!("{} and {} overlap", src.display(), dest.display())));
+    }
+    check_not_in_use(&src).map_err(&rollback)?;
+    copy_tree(&src, &dest, f).map_err(&rollback)?;
+    verify_tree(&src, &dest).map_err(&rollback)?;
+    let base_path = registry_path(&dest);
+    registry
+        .set_value(
+            &format!("{}\\{}", LXSS_KEY, distro.guid),
+            "BasePath",
+            &RegistryValue::Sz(base_path.clone()),
+        )
+        .map_err(&rollback)?;
+    distro.base_path = base_path;
+    Ok(distro)
+}
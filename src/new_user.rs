@@ -0,0 +1,172 @@
+use failure::Error;
+use backend::WslBackend;
+use format::shell_quote;
+use passwd;
+use process::Output;
+
+/// Creates a user with a home folder and the shell that the rootfs has, with `useradd` or with
+/// `adduser` of BusyBox.
+const CREATE_USER: &str = "shell=/bin/sh; [ -x /bin/bash ] && shell=/bin/bash; \
+                           if command -v useradd >/dev/null 2>&1; then \
+                           useradd -m -s \"$shell\" \"$1\"; else adduser -D -s \"$shell\" \"$1\"; \
+                           fi";
+/// Adds a user to the first of the groups `sudo` and `wheel` that exists.
+const ADD_TO_ADMINISTRATORS: &str = "for group in sudo wheel; do \
+                                     if grep -q \"^$group:\" /etc/group; then \
+                                     if command -v usermod >/dev/null 2>&1; then \
+                                     usermod -a -G \"$group\" \"$1\"; \
+                                     else addgroup \"$1\" \"$group\"; fi; exit; fi; done; \
+                                     echo 'there is neither a sudo nor a wheel group' >&2; exit 1";
+
+/// How `create_user` sets the password of a new user.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NewPassword {
+    /// A hash in the format of `/etc/shadow`, such as the output of `openssl passwd -6`
+    Hash(String),
+    /// No password at all
+    Empty,
+    /// Asks for a password interactively with `passwd`
+    Prompt,
+}
+
+/// A user that `create_user` creates in a registered WSL distro.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NewUser {
+    pub name: String,
+    pub password: NewPassword,
+    /// Whether to add the user to `sudo` or `wheel`
+    pub sudo: bool,
+}
+
+/// What `create_user` has done, what has failed and what it has not done because of that.
+#[derive(Debug)]
+pub struct UserReport {
+    pub done: Vec<String>,
+    pub failed: Option<(String, Error)>,
+    pub not_done: Vec<String>,
+}
+
+/// Checks that `hash` can be put into `/etc/shadow`.
+pub fn validate_password_hash(hash: &str) -> Result<(), Error> {
+    if !hash.is_empty() && !hash.contains(|c: char| c == ':' || c.is_whitespace()) {
+        Ok(())
+    } else {
+        Err(format_err!("\"{}\" is not a password hash without : and spaces", hash))
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Step {
+    Create,
+    SetPassword,
+    AddToAdministrators,
+    SetDefaultUser,
+}
+
+impl NewUser {
+    fn steps(&self) -> Vec<Step> {
+        let mut steps = vec![Step::Create, Step::SetPassword];
+        if self.sudo {
+            steps.push(Step::AddToAdministrators);
+        }
+        steps.push(Step::SetDefaultUser);
+        steps
+    }
+
+    fn describe(&self, step: Step) -> String {
+        match step {
+            Step::Create => format!("creating the user \"{}\"", self.name),
+            Step::SetPassword => match self.password {
+                NewPassword::Empty => format!("removing the password of \"{}\"", self.name),
+                _ => format!("setting the password of \"{}\"", self.name),
+            },
+            Step::AddToAdministrators => format!("adding \"{}\" to sudo or wheel", self.name),
+            Step::SetDefaultUser => format!("making \"{}\" the default user", self.name),
+        }
+    }
+
+    fn run<B: WslBackend>(&self, backend: &B, distro_name: &str, step: Step) -> Result<(), Error> {
+        let name = shell_quote(&self.name);
+        match step {
+            Step::Create => run_script(backend, distro_name, CREATE_USER, &name),
+            Step::SetPassword => match self.password {
+                NewPassword::Hash(ref hash) => {
+                    validate_password_hash(hash)?;
+                    // The hash goes to stdin, as other processes can read command lines.
+                    let line = format!("{}:{}\n", self.name, hash);
+                    check(backend.run_with_input(distro_name, "chpasswd -e", line.as_bytes())?)
+                }
+                NewPassword::Empty => {
+                    run_command(backend, distro_name, &format!("passwd -d {}", name))
+                }
+                NewPassword::Prompt => {
+                    match backend.launch(distro_name, &format!("passwd {}", name), false)? {
+                        0 => Ok(()),
+                        exit_code => Err(format_err!("passwd exited with {}", exit_code)),
+                    }
+                }
+            },
+            Step::AddToAdministrators => {
+                run_script(backend, distro_name, ADD_TO_ADMINISTRATORS, &name)
+            }
+            Step::SetDefaultUser => {
+                let passwd = passwd::read_passwd(backend, distro_name)?;
+                let uid = passwd::uid_of(&passwd, &self.name)
+                    .ok_or_else(|| format_err!("/etc/passwd has no user named \"{}\"", self.name))?;
+                let mut configuration = backend.get_distro_configuration(distro_name)?;
+                configuration.default_uid = uid;
+                backend.configure_distro(&configuration)
+            }
+        }
+    }
+}
+
+/// Runs `command` as the default user, which is root right after registering.
+fn run_command<B: WslBackend>(backend: &B, distro_name: &str, command: &str) -> Result<(), Error> {
+    check(backend.run_capture(distro_name, command)?)
+}
+
+/// Turns a nonzero exit code of `output` into an error with its stderr.
+fn check(output: Output) -> Result<(), Error> {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    match (output.exit_code, stderr.trim()) {
+        (0, _) => Ok(()),
+        (exit_code, "") => Err(format_err!("the command exited with {}", exit_code)),
+        (exit_code, stderr) => {
+            Err(format_err!("the command exited with {}: {}", exit_code, stderr))
+        }
+    }
+}
+
+/// Runs `script` with `sh -c`, passing `arg` as `$1`.
+fn run_script<B: WslBackend>(
+    backend: &B,
+    distro_name: &str,
+    script: &str,
+    arg: &str,
+) -> Result<(), Error> {
+    run_command(backend, distro_name, &format!("sh -c {} sh {}", shell_quote(script), arg))
+}
+
+/// Creates `user` in the registered WSL distro `distro_name`, sets its password, adds it to
+/// `sudo` or `wheel` if asked to, and makes it the default user. Stops at the first step that
+/// fails.
+pub fn create_user<B: WslBackend>(backend: &B, distro_name: &str, user: &NewUser) -> UserReport {
+    let mut report = UserReport {
+        done: vec![],
+        failed: None,
+        not_done: vec![],
+    };
+    for step in user.steps() {
+        let description = user.describe(step);
+        if report.failed.is_some() {
+            report.not_done.push(description);
+            continue;
+        }
+        match user.run(backend, distro_name, step) {
+            Ok(()) => report.done.push(description),
+            Err(e) => report.failed = Some((description, e)),
+        }
+    }
+    report
+}
@@ -0,0 +1,205 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::path::Path;
+use failure::Error;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use tar::{Archive, Builder, EntryType};
+use archive::{self, SourceFormat};
+
+/// Folders that tell a rootfs wrapped in a folder from a rootfs with only one folder.
+const ROOTFS_FOLDERS: [&str; 3] = ["bin", "etc", "usr"];
+
+/// Returns the folder that wraps every path in `paths`, if it has folders of a rootfs.
+fn wrapping_folder(paths: &[String]) -> Option<String> {
+    let top = |path: &str| path.split('/').next().unwrap_or_default().to_string();
+    let folder = top(paths.iter().find(|path| !path.is_empty())?);
+    let wraps = paths.iter().all(|path| path.is_empty() || top(path) == folder);
+    let has_rootfs = paths.iter().any(|path| {
+        path.split('/')
+            .nth(1)
+            .is_some_and(|name| ROOTFS_FOLDERS.contains(&name))
+    });
+    if wraps && has_rootfs {
+        Some(folder)
+    } else {
+        None
+    }
+}
+
+/// Gives each path a place whose name differs from the others in more than case, as NTFS needs.
+#[derive(Default)]
+struct Places {
+    places: HashMap<String, String>,
+    taken: HashSet<String>,
+}
+
+impl Places {
+    fn get(&self, path: &str) -> Option<&String> {
+        self.places.get(path)
+    }
+
+    fn place(&mut self, path: &str, changes: &mut Vec<String>) -> String {
+        if let Some(place) = self.places.get(path) {
+            return place.clone();
+        }
+        let (parent, name) = match path.rfind('/') {
+            Some(i) => (self.place(&path[..i], changes), &path[i + 1..]),
+            None => (String::new(), path),
+        };
+        let join = |name: &str| {
+            if parent.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}/{}", parent, name)
+            }
+        };
+        let mut place = join(name);
+        let mut i = 0;
+        while self.taken.contains(&place.to_lowercase()) {
+            i += 1;
+            place = join(&format!("{}~{}", name, i));
+        }
+        if i > 0 {
+            changes.push(format!(
+                "renamed {} to {}, as it differs from another path only in case",
+                path, place
+            ));
+        }
+        self.taken.insert(place.to_lowercase());
+        self.places.insert(path.to_string(), place.clone());
+        place
+    }
+}
+
+/// Rewrites `src`, whose format is `format`, into a `.tar.gz` archive in `dest` that
+/// `WslRegisterDistribution` handles well, and returns what was changed.
+///
+/// The folder that wraps a rootfs is removed, device nodes are dropped, paths that differ only
+/// in case get a `~1` suffix, and hard links to outside of the archive are dropped.
+///
+/// A dropped hard link loses its file, as the archive does not have the data of its target. A
+/// hard link that comes before its target is written after it. Symbolic links keep their
+/// targets as they are, even if a target gets a `~1` suffix.
+pub fn normalize_tar_gz<P, Q>(src: P, format: SourceFormat, dest: Q) -> Result<Vec<String>, Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let src = src.as_ref();
+    let mut paths = vec![];
+    for entry in Archive::new(archive::open_tar(src, format)?).entries()? {
+        let entry = entry?;
+        if entry.header().entry_type() == EntryType::XGlobalHeader {
+            continue;
+        }
+        if let Some(path) = archive::resolve_path(&String::from_utf8_lossy(&entry.path_bytes())) {
+            paths.push(path);
+        }
+    }
+    let mut changes = vec![];
+    let folder = wrapping_folder(&paths);
+    if let Some(ref folder) = folder {
+        changes.push(format!("removed the folder {} that wraps the rootfs", folder));
+    }
+    // Returns `None` for a path outside of the folder, which only a hard link target can be.
+    let strip = |path: String| match folder {
+        Some(ref folder) if path == *folder => Some(String::new()),
+        Some(ref folder) => path.strip_prefix(&format!("{}/", folder)).map(str::to_string),
+        None => Some(path),
+    };
+    let mut places = Places::default();
+    for path in paths.into_iter().filter_map(&strip) {
+        if !path.is_empty() {
+            places.place(&path, &mut changes);
+        }
+    }
+    let mut written = HashSet::new();
+    // Hard links whose targets come later, with their places and the targets as written
+    let mut waiting = vec![];
+    let mut builder = Builder::new(GzEncoder::new(File::create(dest)?, Compression::default()));
+    for entry in Archive::new(archive::open_tar(src, format)?).entries()? {
+        let mut entry = entry?;
+        let entry_type = entry.header().entry_type();
+        if entry_type == EntryType::XGlobalHeader {
+            continue;
+        }
+        let raw_path = String::from_utf8_lossy(&entry.path_bytes()).into_owned();
+        let path = match archive::resolve_path(&raw_path).and_then(&strip) {
+            Some(path) => path,
+            None => {
+                changes.push(format!("dropped {}, which is outside of the archive", raw_path));
+                continue;
+            }
+        };
+        if path.is_empty() {
+            continue;
+        }
+        let path = places.place(&path, &mut changes);
+        let mut header = entry.header().clone();
+        match entry_type {
+            EntryType::Char | EntryType::Block => {
+                changes.push(format!("dropped the device node {}, which WSL1 rejects", path));
+            }
+            EntryType::Link => {
+                let target = entry
+                    .link_name_bytes()
+                    .map(|target| String::from_utf8_lossy(&target).into_owned())
+                    .unwrap_or_default();
+                let place = archive::resolve_path(&target)
+                    .and_then(&strip)
+                    .and_then(|target| places.get(&target));
+                match place {
+                    Some(place) if written.contains(place) => {
+                        builder.append_link(&mut header, &path, place)?;
+                        written.insert(path);
+                    }
+                    Some(place) => waiting.push((header, path, place.clone(), target)),
+                    None => changes.push(format!(
+                        "dropped the hard link {} to {}, which is outside of the archive",
+                        path, target
+                    )),
+                }
+            }
+            EntryType::Symlink => {
+                let target = entry
+                    .link_name_bytes()
+                    .map(|target| String::from_utf8_lossy(&target).into_owned())
+                    .unwrap_or_default();
+                builder.append_link(&mut header, &path, target)?;
+                written.insert(path);
+            }
+            _ => {
+                // Sparse and contiguous files are read as they are, so they become regular ones.
+                if entry_type == EntryType::GNUSparse || entry_type == EntryType::Continuous {
+                    header.set_entry_type(EntryType::Regular);
+                }
+                header.set_size(entry.size());
+                builder.append_data(&mut header, &path, &mut entry)?;
+                written.insert(path);
+            }
+        }
+    }
+    // A waiting hard link may point to another one, so they are written until none is ready.
+    loop {
+        let (ready, rest): (Vec<_>, Vec<_>) = waiting
+            .into_iter()
+            .partition(|(_, _, place, _)| written.contains(place));
+        waiting = rest;
+        if ready.is_empty() {
+            break;
+        }
+        for (mut header, path, place, _) in ready {
+            builder.append_link(&mut header, &path, &place)?;
+            written.insert(path);
+        }
+    }
+    for (_, path, _, target) in waiting {
+        changes.push(format!(
+            "dropped the hard link {} to {}, whose target is dropped",
+            path, target
+        ));
+    }
+    builder.into_inner()?.finish()?.sync_all()?;
+    Ok(changes)
+}
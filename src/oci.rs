@@ -0,0 +1,536 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use failure::Error;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::de::DeserializeOwned;
+use serde_json;
+use tar::{Archive, Builder, EntryType};
+use archive;
+use passwd;
+
+const DOCKER_MANIFEST: &str = "manifest.json";
+const OCI_INDEX: &str = "index.json";
+const OPAQUE_WHITEOUT: &str = ".wh..wh..opq";
+const WHITEOUT_PREFIX: &str = ".wh.";
+const REF_NAME_ANNOTATION: &str = "org.opencontainers.image.ref.name";
+const INDEX_MEDIA_TYPES: [&str; 2] = [
+    "application/vnd.oci.image.index.v1+json",
+    "application/vnd.docker.distribution.manifest.list.v2+json",
+];
+/// The platform picked when an image has several and none is asked for
+const DEFAULT_PLATFORM: &str = "linux/amd64";
+
+/// Picks one image out of a `docker save` archive or an OCI image layout that holds several.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImageSelector {
+    /// A tag such as `latest` or a reference such as `ubuntu:18.04`
+    pub tag: Option<String>,
+    /// A platform such as `linux/amd64` or `linux/arm/v7`
+    pub platform: Option<String>,
+}
+
+/// The parts of an image configuration that can seed the configuration of a WSL distro.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImageConfig {
+    pub env: Vec<String>,
+    pub user: Option<String>,
+    pub working_dir: Option<String>,
+}
+
+/// What `write_oci_tar_gz` found in an image.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OciImage {
+    pub config: ImageConfig,
+    /// `/etc/passwd` of the flattened files, to look `User` up in
+    pub passwd: Option<String>,
+}
+
+impl OciImage {
+    /// Returns the user ID that `User` names, which may be a user name or a user ID followed by
+    /// `:` and a group. `None` means that the image does not set `User`.
+    pub fn default_uid(&self) -> Result<Option<u32>, Error> {
+        let user = match self.config.user {
+            Some(ref user) if !user.is_empty() => user,
+            _ => return Ok(None),
+        };
+        let user = user.split(':').next().unwrap_or_default();
+        if let Ok(uid) = user.parse() {
+            return Ok(Some(uid));
+        }
+        let passwd = self.passwd.as_ref().map_or("", String::as_str);
+        match passwd::uid_of(passwd, user) {
+            Some(uid) => Ok(Some(uid)),
+            None => Err(format_err!("the user {} is not in /etc/passwd of the image", user)),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DockerManifest {
+    config: String,
+    #[serde(default)]
+    repo_tags: Option<Vec<String>>,
+    layers: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Platform {
+    os: String,
+    architecture: String,
+    #[serde(default)]
+    variant: Option<String>,
+}
+
+impl Platform {
+    fn name(&self) -> String {
+        match self.variant {
+            Some(ref variant) => format!("{}/{}/{}", self.os, self.architecture, variant),
+            None => format!("{}/{}", self.os, self.architecture),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Descriptor {
+    #[serde(default)]
+    media_type: Option<String>,
+    digest: String,
+    #[serde(default)]
+    platform: Option<Platform>,
+    #[serde(default)]
+    annotations: Option<HashMap<String, String>>,
+}
+
+#[derive(Deserialize)]
+struct Index {
+    manifests: Vec<Descriptor>,
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    config: Descriptor,
+    layers: Vec<Descriptor>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ContainerConfig {
+    #[serde(default)]
+    env: Option<Vec<String>>,
+    #[serde(default)]
+    user: Option<String>,
+    #[serde(default)]
+    working_dir: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ConfigFile {
+    #[serde(flatten)]
+    platform: Option<Platform>,
+    #[serde(default)]
+    config: Option<ContainerConfig>,
+}
+
+/// An image that `ImageSelector` can pick.
+struct Candidate {
+    references: Vec<String>,
+    platform: Option<String>,
+    config: String,
+    layers: Vec<String>,
+}
+
+/// The files of a `docker save` archive or an OCI image layout.
+enum ImageSource {
+    Layout(PathBuf),
+    /// The offset and the size of each file in the archive
+    Archive(PathBuf, HashMap<String, (u64, u64)>),
+}
+
+/// Returns the path of the blob that `digest` such as `sha256:...` names.
+fn blob_path(digest: &str) -> Result<String, Error> {
+    match digest.find(':') {
+        Some(i) if !digest.contains(&['/', '\\', '.'][..]) => {
+            Ok(format!("blobs/{}/{}", &digest[..i], &digest[i + 1..]))
+        }
+        _ => Err(format_err!("{} is not a digest", digest)),
+    }
+}
+
+impl ImageSource {
+    fn open(path: &Path) -> Result<ImageSource, Error> {
+        if fs::metadata(path)?.is_dir() {
+            return Ok(ImageSource::Layout(path.to_path_buf()));
+        }
+        let mut files = HashMap::new();
+        let mut archive = Archive::new(File::open(path)?);
+        for entry in archive.entries()? {
+            let entry = entry?;
+            if entry.header().entry_type().is_file() {
+                let name = archive::normalize_path(&entry.path()?.to_string_lossy());
+                files.insert(name, (entry.raw_file_position(), entry.size()));
+            }
+        }
+        Ok(ImageSource::Archive(path.to_path_buf(), files))
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        match *self {
+            ImageSource::Layout(ref path) => path.join(name).is_file(),
+            ImageSource::Archive(_, ref files) => files.contains_key(name),
+        }
+    }
+
+    fn read(&self, name: &str) -> Result<Box<dyn Read>, Error> {
+        match *self {
+            ImageSource::Layout(ref path) => match File::open(path.join(name)) {
+                Ok(file) => Ok(Box::new(file)),
+                Err(e) => Err(format_err!("{}: {}", name, e)),
+            },
+            ImageSource::Archive(ref path, ref files) => {
+                let (offset, size) = match files.get(name) {
+                    Some(&file) => file,
+                    None => return Err(format_err!("{} is not in {}", name, path.display())),
+                };
+                let mut file = File::open(path)?;
+                file.seek(SeekFrom::Start(offset))?;
+                Ok(Box::new(file.take(size)))
+            }
+        }
+    }
+
+    fn read_json<T: DeserializeOwned>(&self, name: &str) -> Result<T, Error> {
+        serde_json::from_reader(self.read(name)?).map_err(|e| format_err!("{}: {}", name, e))
+    }
+
+    fn platform_of(&self, config: &str) -> Result<Option<String>, Error> {
+        let config: ConfigFile = self.read_json(config)?;
+        Ok(config.platform.as_ref().map(Platform::name))
+    }
+
+    fn docker_candidates(&self) -> Result<Vec<Candidate>, Error> {
+        let manifests: Vec<DockerManifest> = self.read_json(DOCKER_MANIFEST)?;
+        manifests
+            .into_iter()
+            .map(|manifest| {
+                Ok(Candidate {
+                    references: manifest.repo_tags.unwrap_or_default(),
+                    platform: self.platform_of(&manifest.config)?,
+                    config: manifest.config,
+                    layers: manifest.layers,
+                })
+            })
+            .collect()
+    }
+
+    /// Adds the images under `descriptors`, which inherit `references` unless they have their own.
+    fn add_oci_candidates(
+        &self,
+        descriptors: Vec<Descriptor>,
+        references: &[String],
+        candidates: &mut Vec<Candidate>,
+    ) -> Result<(), Error> {
+        for descriptor in descriptors {
+            let path = blob_path(&descriptor.digest)?;
+            let references = match descriptor
+                .annotations
+                .as_ref()
+                .and_then(|annotations| annotations.get(REF_NAME_ANNOTATION))
+            {
+                Some(reference) => vec![reference.clone()],
+                None => references.to_vec(),
+            };
+            let is_index = descriptor
+                .media_type
+                .as_ref()
+                .is_some_and(|media_type| INDEX_MEDIA_TYPES.contains(&media_type.as_str()));
+            if is_index {
+                let index: Index = self.read_json(&path)?;
+                self.add_oci_candidates(index.manifests, &references, candidates)?;
+                continue;
+            }
+            let manifest: Manifest = self.read_json(&path)?;
+            let config = blob_path(&manifest.config.digest)?;
+            let platform = match descriptor.platform {
+                Some(ref platform) => Some(platform.name()),
+                None => self.platform_of(&config)?,
+            };
+            candidates.push(Candidate {
+                references,
+                platform,
+                config,
+                layers: manifest
+                    .layers
+                    .iter()
+                    .map(|layer| blob_path(&layer.digest))
+                    .collect::<Result<_, _>>()?,
+            });
+        }
+        Ok(())
+    }
+
+    /// Lists the images, preferring `manifest.json` of `docker save` to `index.json`.
+    fn candidates(&self) -> Result<Vec<Candidate>, Error> {
+        if self.contains(DOCKER_MANIFEST) {
+            return self.docker_candidates();
+        }
+        if !self.contains(OCI_INDEX) {
+            return Err(format_err!(
+                "neither {} nor {} can be found in the image",
+                DOCKER_MANIFEST,
+                OCI_INDEX
+            ));
+        }
+        let index: Index = self.read_json(OCI_INDEX)?;
+        let mut candidates = vec![];
+        self.add_oci_candidates(index.manifests, &[], &mut candidates)?;
+        Ok(candidates)
+    }
+}
+
+/// Tells whether `reference` such as `ubuntu:18.04` has `tag`.
+fn has_tag(reference: &str, tag: &str) -> bool {
+    reference == tag
+        || reference
+            .rfind(':')
+            .is_some_and(|i| !reference[i..].contains('/') && &reference[i + 1..] == tag)
+}
+
+/// Tells whether `platform` such as `linux/arm/v7` is `wanted` such as `linux/arm`.
+fn is_platform(platform: &str, wanted: &str) -> bool {
+    platform == wanted || platform.starts_with(&format!("{}/", wanted))
+}
+
+fn select(candidates: Vec<Candidate>, selector: &ImageSelector) -> Result<Candidate, Error> {
+    let mut candidates = candidates
+        .into_iter()
+        .filter(|candidate| {
+            selector.tag.as_ref().is_none_or(|tag| {
+                candidate.references.iter().any(|reference| has_tag(reference, tag))
+            })
+        })
+        // Attestations that BuildKit stores next to images have the platform unknown/unknown.
+        .filter(|candidate| {
+            candidate.platform.as_ref().is_none_or(|platform| {
+                platform != "unknown/unknown"
+                    && selector
+                        .platform
+                        .as_ref()
+                        .is_none_or(|wanted| is_platform(platform, wanted))
+            })
+        })
+        .collect::<Vec<_>>();
+    if candidates.len() > 1 && selector.platform.is_none() {
+        let defaults = candidates
+            .iter()
+            .filter(|candidate| {
+                candidate
+                    .platform
+                    .as_ref()
+                    .is_some_and(|platform| is_platform(platform, DEFAULT_PLATFORM))
+            })
+            .count();
+        if defaults > 0 {
+            candidates.retain(|candidate| {
+                candidate
+                    .platform
+                    .as_ref()
+                    .is_some_and(|platform| is_platform(platform, DEFAULT_PLATFORM))
+            });
+        }
+    }
+    match candidates.len() {
+        0 => Err(format_err!("no image in the archive matches")),
+        1 => Ok(candidates.remove(0)),
+        _ => Err(format_err!(
+            "several images match; pick one with a tag or a platform: {}",
+            candidates
+                .iter()
+                .map(|candidate| format!(
+                    "{} ({})",
+                    candidate.references.join(", "),
+                    candidate.platform.as_ref().map_or("unknown", String::as_str)
+                ))
+                .collect::<Vec<_>>()
+                .join("; ")
+        )),
+    }
+}
+
+/// Tells whether `path` is a whiteout, which only marks what the layers below lose.
+fn is_whiteout(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or_default();
+    name.starts_with(WHITEOUT_PREFIX)
+}
+
+/// The paths in the layers of an image, each with the last layer that has it.
+#[derive(Default)]
+struct Layers {
+    /// Each path with whether it is a folder in that layer
+    entries: HashMap<String, (usize, bool)>,
+    whiteouts: HashMap<String, usize>,
+    opaque_dirs: HashMap<String, usize>,
+}
+
+impl Layers {
+    /// Records the entry `path` of layer `layer`.
+    fn add(&mut self, layer: usize, path: &str, is_dir: bool) {
+        let (parent, name) = match path.rfind('/') {
+            Some(i) => (&path[..i], &path[i + 1..]),
+            None => ("", path),
+        };
+        if name == OPAQUE_WHITEOUT {
+            self.opaque_dirs.insert(parent.to_string(), layer);
+        } else if let Some(name) = name.strip_prefix(WHITEOUT_PREFIX) {
+            let path = if parent.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}/{}", parent, name)
+            };
+            self.whiteouts.insert(path, layer);
+        } else {
+            self.entries.insert(path.to_string(), (layer, is_dir));
+        }
+    }
+
+    /// Tells whether a layer above `layer` replaces or deletes `path`.
+    ///
+    /// A folder in several layers is kept in each of them so that the files under it are
+    /// written after it.
+    fn is_hidden(&self, layer: usize, path: &str, is_dir: bool) -> bool {
+        if let Some(&(upper, upper_is_dir)) = self.entries.get(path) {
+            if upper > layer && !(is_dir && upper_is_dir) {
+                return true;
+            }
+        }
+        if self.whiteouts.get(path).is_some_and(|&upper| upper > layer) {
+            return true;
+        }
+        let ancestors = path
+            .match_indices('/')
+            .map(|(i, _)| &path[..i])
+            .chain(Some(""));
+        for ancestor in ancestors {
+            let above = |layers: Option<&usize>| layers.is_some_and(|&upper| upper > layer);
+            if above(self.whiteouts.get(ancestor)) || above(self.opaque_dirs.get(ancestor)) {
+                return true;
+            }
+            if let Some(&(upper, false)) = self.entries.get(ancestor) {
+                if upper > layer {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+fn open_layer(source: &ImageSource, layer: &str) -> Result<Archive<Box<dyn Read>>, Error> {
+    let reader = archive::decompress(BufReader::new(source.read(layer)?))
+        .map_err(|e| format_err!("{}: {}", layer, e))?;
+    Ok(Archive::new(reader))
+}
+
+/// Flattens the image in `src`, a `docker save` archive or an OCI image layout, into a `.tar.gz`
+/// archive at `dest`.
+///
+/// The layers are applied in order. A whiteout `.wh.<name>` deletes `<name>` of the layers below
+/// it, and `.wh..wh..opq` hides everything that the layers below have in its folder.
+pub fn write_oci_tar_gz<P, Q>(src: P, selector: &ImageSelector, dest: Q) -> Result<OciImage, Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let source = ImageSource::open(src.as_ref())?;
+    let candidate = select(source.candidates()?, selector)?;
+    let config: ConfigFile = source.read_json(&candidate.config)?;
+    let config = config.config.unwrap_or_default();
+    let mut layers = Layers::default();
+    // The targets of the hard links in each layer
+    let mut link_targets = HashSet::new();
+    for (i, layer) in candidate.layers.iter().enumerate() {
+        for entry in open_layer(&source, layer)?.entries()? {
+            let entry = entry?;
+            let entry_type = entry.header().entry_type();
+            layers.add(
+                i,
+                &archive::normalize_path(&entry.path()?.to_string_lossy()),
+                entry_type.is_dir(),
+            );
+            if entry_type.is_hard_link() {
+                if let Some(target) = entry.link_name()? {
+                    link_targets.insert((i, archive::normalize_path(&target.to_string_lossy())));
+                }
+            }
+        }
+    }
+    let mut passwd = None;
+    let mut builder = Builder::new(GzEncoder::new(File::create(dest)?, Compression::default()));
+    for (i, layer) in candidate.layers.iter().enumerate() {
+        // The contents of the files that an upper layer hides, but a hard link in this layer
+        // still points to
+        let mut hidden_targets = HashMap::new();
+        for entry in open_layer(&source, layer)?.entries()? {
+            let mut entry = entry?;
+            let entry_type = entry.header().entry_type();
+            if entry_type == EntryType::XGlobalHeader {
+                continue;
+            }
+            let path = archive::normalize_path(&entry.path()?.to_string_lossy());
+            if path.is_empty() || is_whiteout(&path) {
+                continue;
+            }
+            if layers.is_hidden(i, &path, entry_type.is_dir()) {
+                if entry_type.is_file() && link_targets.contains(&(i, path.clone())) {
+                    let mut data = vec![];
+                    entry.read_to_end(&mut data)?;
+                    hidden_targets.insert(path, data);
+                }
+                continue;
+            }
+            let mut header = entry.header().clone();
+            if entry_type.is_hard_link() || entry_type.is_symlink() {
+                let target = match entry.link_name()? {
+                    Some(target) => target.to_string_lossy().into_owned(),
+                    None => return Err(format_err!("{} does not have a link target", path)),
+                };
+                let target = if entry_type.is_hard_link() {
+                    archive::normalize_path(&target)
+                } else {
+                    target
+                };
+                // The link would point to nothing or to the file of the upper layer, so it
+                // becomes a copy of the file that it pointed to in this layer.
+                match hidden_targets.get(&target) {
+                    Some(data) if entry_type.is_hard_link() => {
+                        header.set_entry_type(EntryType::Regular);
+                        header.set_size(data.len() as u64);
+                        builder.append_data(&mut header, &path, &data[..])?;
+                    }
+                    _ => builder.append_link(&mut header, &path, &target)?,
+                }
+            } else if path == "etc/passwd" && entry_type.is_file() {
+                let mut s = String::new();
+                entry.read_to_string(&mut s)?;
+                builder.append_data(&mut header, &path, s.as_bytes())?;
+                passwd = Some(s);
+            } else {
+                builder.append_data(&mut header, &path, &mut entry)?;
+            }
+        }
+    }
+    builder.into_inner()?.finish()?.sync_all()?;
+    Ok(OciImage {
+        config: ImageConfig {
+            env: config.env.unwrap_or_default(),
+            user: config.user,
+            working_dir: config.working_dir,
+        },
+        passwd,
+    })
+}
+
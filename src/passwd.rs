@@ -0,0 +1,90 @@
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use failure::Error;
+use tar::Archive;
+use archive::{self, SourceFormat};
+use backend::WslBackend;
+
+const PASSWD: &str = "etc/passwd";
+
+/// A user in `/etc/passwd`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PasswdUser {
+    pub name: String,
+    pub uid: u32,
+}
+
+/// Parses the text of `/etc/passwd`. Comments and broken lines are skipped.
+pub fn parse_passwd(passwd: &str) -> Vec<PasswdUser> {
+    passwd
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let fields = line.split(':').collect::<Vec<_>>();
+            if fields.len() < 3 || fields[0].is_empty() {
+                return None;
+            }
+            Some(PasswdUser {
+                name: fields[0].to_string(),
+                uid: fields[2].parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Returns the user ID of `name` in `passwd`.
+pub fn uid_of(passwd: &str, name: &str) -> Option<u32> {
+    parse_passwd(passwd)
+        .into_iter()
+        .find(|user| user.name == name)
+        .map(|user| user.uid)
+}
+
+/// Returns the name of the first user whose ID is `uid` in `passwd`.
+pub fn user_name_of(passwd: &str, uid: u32) -> Option<String> {
+    parse_passwd(passwd)
+        .into_iter()
+        .find(|user| user.uid == uid)
+        .map(|user| user.name)
+}
+
+/// Reads `/etc/passwd` of the registered WSL distro `distro_name` by running `cat` in it.
+pub fn read_passwd<B: WslBackend>(backend: &B, distro_name: &str) -> Result<String, Error> {
+    let output = backend.run_capture(distro_name, "cat /etc/passwd")?;
+    if output.exit_code != 0 {
+        return Err(format_err!(
+            "cat /etc/passwd exited with {}: {}",
+            output.exit_code,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Reads `/etc/passwd` of the rootfs in `path`, which is a folder or an archive that `register`
+/// accepts. Returns `None` if the rootfs does not have it.
+pub fn read_rootfs_passwd<P: AsRef<Path>>(path: P) -> Result<Option<String>, Error> {
+    let path = path.as_ref();
+    let format = SourceFormat::detect(path)?;
+    if format == SourceFormat::Directory {
+        let passwd = path.join(PASSWD);
+        if !passwd.is_file() {
+            return Ok(None);
+        }
+        return Ok(Some(fs::read_to_string(passwd)?));
+    }
+    // A later entry of the same path replaces an earlier one when the archive is unpacked.
+    let mut passwd = None;
+    for entry in Archive::new(archive::open_tar(path, format)?).entries()? {
+        let mut entry = entry?;
+        if entry.header().entry_type().is_file()
+            && archive::normalize_path(&entry.path()?.to_string_lossy()) == PASSWD
+        {
+            let mut s = String::new();
+            entry.read_to_string(&mut s)?;
+            passwd = Some(s);
+        }
+    }
+    Ok(passwd)
+}
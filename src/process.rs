@@ -0,0 +1,206 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::process::{Child, ExitStatus};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::{cmp, mem, thread};
+use failure::Error;
+
+/// A process launched non-interactively in a WSL distro.
+pub trait WslProcess {
+    /// Waits for the process to exit and returns its exit code.
+    fn wait(&mut self) -> Result<u32, Error>;
+
+    /// Returns the exit code if the process has exited, without blocking.
+    fn try_wait(&mut self) -> Result<Option<u32>, Error>;
+
+    /// Returns the exit code if `wait` or `try_wait` has seen the process exit.
+    fn exit_code(&self) -> Option<u32>;
+
+    /// Forcibly terminates the process.
+    fn kill(&mut self) -> Result<(), Error>;
+
+    /// Waits at most `timeout` for the process to exit and returns its exit code, or `None` if
+    /// it is still running.
+    fn wait_timeout(&mut self, timeout: Duration) -> Result<Option<u32>, Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(exit_code) = self.try_wait()? {
+                return Ok(Some(exit_code));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            thread::sleep(cmp::min(deadline - now, Duration::from_millis(10)));
+        }
+    }
+}
+
+/// Files that a launched process uses as its standard streams.
+///
+/// A stream that is `None` is inherited from the current process.
+#[derive(Debug, Default)]
+pub struct LaunchStdio {
+    pub stdin: Option<File>,
+    pub stdout: Option<File>,
+    pub stderr: Option<File>,
+}
+
+/// The output of a finished process, shaped like `std::process::Output`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Output {
+    pub exit_code: u32,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+/// Opens the null device, which is empty to read and discards writes.
+pub fn null_file() -> io::Result<File> {
+    #[cfg(windows)]
+    let path = "NUL";
+    #[cfg(not(windows))]
+    let path = "/dev/null";
+    File::options().read(true).write(true).open(path)
+}
+
+/// Creates an anonymous pipe and returns its read end and write end.
+pub fn pipe() -> io::Result<(File, File)> {
+    let (reader, writer) = io::pipe()?;
+    #[cfg(windows)]
+    {
+        use std::os::windows::io::OwnedHandle;
+        Ok((
+            File::from(OwnedHandle::from(reader)),
+            File::from(OwnedHandle::from(writer)),
+        ))
+    }
+    #[cfg(not(windows))]
+    {
+        use std::os::fd::OwnedFd;
+        Ok((
+            File::from(OwnedFd::from(reader)),
+            File::from(OwnedFd::from(writer)),
+        ))
+    }
+}
+
+/// Reads `file` to the end in a new thread.
+pub fn read_to_end_in_thread(mut file: File) -> thread::JoinHandle<io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut buf = vec![];
+        file.read_to_end(&mut buf)?;
+        Ok(buf)
+    })
+}
+
+/// Joins a thread started by `read_to_end_in_thread`.
+pub fn join_read(handle: thread::JoinHandle<io::Result<Vec<u8>>>) -> Result<Vec<u8>, Error> {
+    match handle.join() {
+        Ok(result) => Ok(result?),
+        Err(_) => Err(format_err!("a thread reading a pipe panicked")),
+    }
+}
+
+/// Reads a pipe to the end in a new thread, keeping what it has read so far.
+///
+/// Unlike `read_to_end_in_thread`, the output can be taken before the end of the pipe, which
+/// never comes while a process left running by the launched one, such as a daemon, holds the
+/// pipe open.
+pub struct PipeReader {
+    buf: Arc<Mutex<Vec<u8>>>,
+    done: mpsc::Receiver<io::Result<()>>,
+}
+
+impl PipeReader {
+    pub fn new(mut file: File) -> PipeReader {
+        let buf = Arc::new(Mutex::new(vec![]));
+        let (sender, done) = mpsc::channel();
+        let thread_buf = Arc::clone(&buf);
+        thread::spawn(move || {
+            let mut chunk = [0; 8192];
+            let result = loop {
+                match file.read(&mut chunk) {
+                    Ok(0) => break Ok(()),
+                    Ok(n) => thread_buf.lock().unwrap().extend_from_slice(&chunk[..n]),
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => break Err(e),
+                }
+            };
+            let _ = sender.send(result);
+        });
+        PipeReader { buf, done }
+    }
+
+    /// Waits for the end of the pipe until `deadline`, or for as long as it takes if `deadline`
+    /// is `None`, and returns what has been read. If the pipe is still open at `deadline`, the
+    /// thread is left reading it.
+    pub fn finish(self, deadline: Option<Instant>) -> Result<Vec<u8>, Error> {
+        let result = match deadline {
+            Some(deadline) => {
+                self.done.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            }
+            None => self.done.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match result {
+            Ok(result) => result?,
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(format_err!("a thread reading a pipe panicked"))
+            }
+        }
+        Ok(mem::take(&mut *self.buf.lock().unwrap()))
+    }
+}
+
+/// Converts the exit status of a host process into a Linux-style exit code.
+pub(crate) fn exit_code(status: ExitStatus) -> u32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal as u32;
+        }
+    }
+    status.code().unwrap_or(1) as u32
+}
+
+/// A host process, as launched by `SimulatorBackend`.
+pub struct HostProcess {
+    child: Child,
+    exit_code: Option<u32>,
+}
+
+impl HostProcess {
+    pub(crate) fn new(child: Child) -> HostProcess {
+        HostProcess {
+            child,
+            exit_code: None,
+        }
+    }
+}
+
+impl WslProcess for HostProcess {
+    fn wait(&mut self) -> Result<u32, Error> {
+        let exit_code = exit_code(self.child.wait()?);
+        self.exit_code = Some(exit_code);
+        Ok(exit_code)
+    }
+
+    fn try_wait(&mut self) -> Result<Option<u32>, Error> {
+        let exit_code = self.child.try_wait()?.map(exit_code);
+        if exit_code.is_some() {
+            self.exit_code = exit_code;
+        }
+        Ok(exit_code)
+    }
+
+    fn exit_code(&self) -> Option<u32> {
+        self.exit_code
+    }
+
+    fn kill(&mut self) -> Result<(), Error> {
+        Ok(self.child.kill()?)
+    }
+}
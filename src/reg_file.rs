@@ -0,0 +1,283 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use failure::Error;
+use registry::{MemoryRegistry, Registry, RegistryValue};
+
+const HEADER: &str = "Windows Registry Editor Version 5.00";
+const REGEDIT4_HEADER: &str = "REGEDIT4";
+/// How long `regedit` makes lines of `hex` values before continuing them with `\`
+const LINE_WIDTH: usize = 80;
+
+/// Decodes a `.reg` file, which `regedit` writes in UTF-16LE with a BOM. UTF-8 is also read.
+fn decode(bytes: &[u8]) -> Result<String, Error> {
+    if bytes.starts_with(&[0xFF, 0xFE]) {
+        let wide = bytes[2..]
+            .chunks(2)
+            .map(|c| u16::from_le_bytes([c[0], *c.get(1).unwrap_or(&0)]))
+            .collect::<Vec<_>>();
+        return Ok(String::from_utf16(&wide)?);
+    }
+    let bytes = if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        &bytes[3..]
+    } else {
+        bytes
+    };
+    Ok(String::from_utf8(bytes.to_vec())?)
+}
+
+/// Encodes `s` in UTF-16LE with a BOM like `regedit` does.
+fn encode(s: &str) -> Vec<u8> {
+    let mut bytes = vec![0xFF, 0xFE];
+    bytes.extend(s.encode_utf16().flat_map(u16::to_le_bytes));
+    bytes
+}
+
+fn escape(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Parses a string quoted by `escape` at the start of `s` and returns it with the rest of `s`.
+fn unescape(s: &str) -> Result<(String, &str), Error> {
+    let mut chars = s.char_indices();
+    if chars.next().map(|(_, c)| c) != Some('"') {
+        return Err(format_err!("{} does not start with \"", s));
+    }
+    let mut unescaped = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((unescaped, &s[i + 1..])),
+            '\\' => match chars.next() {
+                Some((_, c)) => unescaped.push(c),
+                None => break,
+            },
+            c => unescaped.push(c),
+        }
+    }
+    Err(format_err!("{} is not closed with \"", s))
+}
+
+/// Formats `"name"=hex(type):xx,xx,...` with the lines wrapped like `regedit` does.
+fn format_hex(name: &str, value_type: &str, data: &[u8]) -> String {
+    let mut s = format!("{}={}:", name, value_type);
+    let mut line_len = s.len();
+    for (i, byte) in data.iter().enumerate() {
+        let piece = if i + 1 < data.len() {
+            format!("{:02x},", byte)
+        } else {
+            format!("{:02x}", byte)
+        };
+        if line_len + piece.len() > LINE_WIDTH - 2 {
+            s += "\\\r\n  ";
+            line_len = 2;
+        }
+        s += &piece;
+        line_len += piece.len();
+    }
+    s
+}
+
+fn format_value(name: &str, value: &RegistryValue) -> String {
+    let name = if name.is_empty() {
+        "@".to_string()
+    } else {
+        escape(name)
+    };
+    match *value {
+        // A quoted string cannot hold line breaks, so such strings are written as `hex(1)`.
+        RegistryValue::Sz(ref s) if !s.contains(&['\r', '\n', '\0'][..]) => {
+            format!("{}={}", name, escape(s))
+        }
+        RegistryValue::Dword(dword) => format!("{}=dword:{:08x}", name, dword),
+        RegistryValue::Binary(ref data) => format_hex(&name, "hex", data),
+        ref value => format_hex(
+            &name,
+            &format!("hex({:x})", value.raw_type()),
+            &value.raw_data(),
+        ),
+    }
+}
+
+fn parse_hex(s: &str) -> Result<Vec<u8>, Error> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|byte| !byte.is_empty())
+        .map(|byte| Ok(u8::from_str_radix(byte, 16)?))
+        .collect()
+}
+
+/// Parses the right-hand side of `"name"=...`. `None` deletes the value.
+fn parse_value(s: &str) -> Result<Option<RegistryValue>, Error> {
+    if s == "-" {
+        return Ok(None);
+    }
+    if s.starts_with('"') {
+        let (value, rest) = unescape(s)?;
+        if !rest.trim().is_empty() {
+            return Err(format_err!("{} follows a string", rest));
+        }
+        return Ok(Some(RegistryValue::Sz(value)));
+    }
+    if let Some(dword) = s.strip_prefix("dword:") {
+        return Ok(Some(RegistryValue::Dword(u32::from_str_radix(dword, 16)?)));
+    }
+    if let Some(data) = s.strip_prefix("hex:") {
+        return Ok(Some(RegistryValue::Binary(parse_hex(data)?)));
+    }
+    if let Some(rest) = s.strip_prefix("hex(") {
+        if let Some(i) = rest.find("):") {
+            let value_type = u32::from_str_radix(&rest[..i], 16)?;
+            let data = parse_hex(&rest[i + 2..])?;
+            return Ok(Some(RegistryValue::from_raw(value_type, data)));
+        }
+    }
+    Err(format_err!("{} is not a registry value", s))
+}
+
+/// Parses the text of a `.reg` file into `registry`.
+pub fn parse_reg(s: &str, registry: &MemoryRegistry) -> Result<(), Error> {
+    // Join the lines that end with `\`, which continue `hex` values.
+    let mut lines = vec![];
+    let mut continued: Option<String> = None;
+    for line in s.lines() {
+        let line = line.trim();
+        let line = match continued.take() {
+            Some(previous) => previous + line,
+            None => line.to_string(),
+        };
+        match line.strip_suffix('\\') {
+            Some(line) => continued = Some(line.to_string()),
+            None => lines.push(line),
+        }
+    }
+    lines.extend(continued);
+    let mut lines = lines
+        .into_iter()
+        .filter(|line| !line.is_empty() && !line.starts_with(';'));
+    match lines.next() {
+        Some(ref header) if header == HEADER || header == REGEDIT4_HEADER => {}
+        _ => return Err(format_err!("the file does not start with \"{}\"", HEADER)),
+    }
+    let mut key: Option<String> = None;
+    for line in lines {
+        if line.starts_with('[') && line.ends_with(']') {
+            let path = &line[1..line.len() - 1];
+            key = match path.strip_prefix('-') {
+                Some(path) => {
+                    registry.remove_key(path);
+                    None
+                }
+                None => {
+                    registry.insert_key(path);
+                    Some(path.to_string())
+                }
+            };
+            continue;
+        }
+        let key = match key {
+            Some(ref key) => key,
+            None => return Err(format_err!("{} is not under a key", line)),
+        };
+        let (name, rest) = if let Some(rest) = line.strip_prefix('@') {
+            (String::new(), rest)
+        } else {
+            unescape(&line)?
+        };
+        let value = match rest.trim_start().strip_prefix('=') {
+            Some(value) => parse_value(value.trim())?,
+            None => return Err(format_err!("{} does not have =", line)),
+        };
+        match value {
+            Some(value) => registry.insert_value(key, &name, value),
+            None => registry.remove_value(key, &name),
+        }
+    }
+    Ok(())
+}
+
+/// Formats `registry` as the text of a `.reg` file with CRLF line breaks.
+///
+/// A key without values is only written if it has no subkeys either, because importing a
+/// subkey creates its parents anyway.
+pub fn format_reg(registry: &MemoryRegistry) -> String {
+    let mut s = format!("{}\r\n\r\n", HEADER);
+    let keys = registry.keys();
+    for key in &keys {
+        let values = registry.values(key);
+        let prefix = format!("{}\\", key.to_ascii_lowercase());
+        let has_subkeys = keys
+            .iter()
+            .any(|other| other.to_ascii_lowercase().starts_with(&prefix));
+        if values.is_empty() && has_subkeys {
+            continue;
+        }
+        s += &format!("[{}]\r\n", key);
+        for (name, value) in values {
+            s += &format_value(&name, &value);
+            s += "\r\n";
+        }
+        s += "\r\n";
+    }
+    s
+}
+
+/// A `Registry` in a `.reg` file exported by `regedit`.
+///
+/// The file is read when opened and written back after every change, in UTF-16LE with a BOM
+/// so that `regedit` can import it.
+#[derive(Debug)]
+pub struct RegFile {
+    path: PathBuf,
+    registry: MemoryRegistry,
+}
+
+impl RegFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<RegFile, Error> {
+        let path = path.as_ref().to_path_buf();
+        let registry = MemoryRegistry::new();
+        match fs::read(&path) {
+            Ok(bytes) => parse_reg(&decode(&bytes)?, &registry)
+                .map_err(|e| format_err!("{}: {}", path.display(), e))?,
+            Err(e) => return Err(format_err!("{}: {}", path.display(), e)),
+        }
+        Ok(RegFile { path, registry })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes the registry back to the file.
+    fn save(&self) -> Result<(), Error> {
+        Ok(fs::write(&self.path, encode(&format_reg(&self.registry)))?)
+    }
+}
+
+impl Registry for RegFile {
+    fn subkeys(&self, key: &str) -> Result<Vec<String>, Error> {
+        self.registry.subkeys(key)
+    }
+
+    fn value(&self, key: &str, name: &str) -> Result<Option<RegistryValue>, Error> {
+        self.registry.value(key, name)
+    }
+
+    fn create_key(&self, key: &str) -> Result<(), Error> {
+        self.registry.insert_key(key);
+        self.save()
+    }
+
+    fn delete_key(&self, key: &str) -> Result<(), Error> {
+        self.registry.remove_key(key);
+        self.save()
+    }
+
+    fn set_value(&self, key: &str, name: &str, value: &RegistryValue) -> Result<(), Error> {
+        self.registry.insert_value(key, name, value.clone());
+        self.save()
+    }
+
+    fn delete_value(&self, key: &str, name: &str) -> Result<(), Error> {
+        self.registry.remove_value(key, name);
+        self.save()
+    }
+}
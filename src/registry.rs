@@ -0,0 +1,288 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use failure::Error;
+
+/// A value in the Windows registry, named after its `REG_*` type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RegistryValue {
+    /// `REG_SZ`
+    Sz(String),
+    /// `REG_EXPAND_SZ`
+    ExpandSz(String),
+    /// `REG_BINARY`
+    Binary(Vec<u8>),
+    /// `REG_DWORD`
+    Dword(u32),
+    /// `REG_MULTI_SZ`
+    MultiSz(Vec<String>),
+    /// `REG_QWORD`
+    Qword(u64),
+    /// Any other type with its raw data
+    Other(u32, Vec<u8>),
+}
+
+/// `REG_SZ`
+const REG_SZ: u32 = 1;
+/// `REG_EXPAND_SZ`
+const REG_EXPAND_SZ: u32 = 2;
+/// `REG_BINARY`
+const REG_BINARY: u32 = 3;
+/// `REG_DWORD`
+const REG_DWORD: u32 = 4;
+/// `REG_MULTI_SZ`
+const REG_MULTI_SZ: u32 = 7;
+/// `REG_QWORD`
+const REG_QWORD: u32 = 11;
+
+/// Decodes UTF-16LE `data` into strings separated by nulls. Empty strings are dropped.
+fn decode_strings(data: &[u8]) -> Vec<String> {
+    let wide = data
+        .chunks(2)
+        .filter(|c| c.len() == 2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect::<Vec<_>>();
+    wide.split(|&c| c == 0)
+        .filter(|s| !s.is_empty())
+        .map(String::from_utf16_lossy)
+        .collect()
+}
+
+/// Encodes `s` into null-terminated UTF-16LE.
+fn encode_string(s: &str) -> Vec<u8> {
+    s.encode_utf16()
+        .chain(Some(0))
+        .flat_map(u16::to_le_bytes)
+        .collect()
+}
+
+impl RegistryValue {
+    /// Decodes `data` of the `REG_*` type `value_type` as Windows stores it.
+    pub fn from_raw(value_type: u32, data: Vec<u8>) -> RegistryValue {
+        let string = |data: &[u8]| decode_strings(data).into_iter().next().unwrap_or_default();
+        match value_type {
+            REG_SZ => RegistryValue::Sz(string(&data)),
+            REG_EXPAND_SZ => RegistryValue::ExpandSz(string(&data)),
+            REG_BINARY => RegistryValue::Binary(data),
+            REG_DWORD if data.len() == 4 => {
+                RegistryValue::Dword(u32::from_le_bytes([data[0], data[1], data[2], data[3]]))
+            }
+            REG_MULTI_SZ => RegistryValue::MultiSz(decode_strings(&data)),
+            REG_QWORD if data.len() == 8 => {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(&data);
+                RegistryValue::Qword(u64::from_le_bytes(bytes))
+            }
+            value_type => RegistryValue::Other(value_type, data),
+        }
+    }
+
+    /// Returns the `REG_*` type of the value.
+    pub fn raw_type(&self) -> u32 {
+        match *self {
+            RegistryValue::Sz(_) => REG_SZ,
+            RegistryValue::ExpandSz(_) => REG_EXPAND_SZ,
+            RegistryValue::Binary(_) => REG_BINARY,
+            RegistryValue::Dword(_) => REG_DWORD,
+            RegistryValue::MultiSz(_) => REG_MULTI_SZ,
+            RegistryValue::Qword(_) => REG_QWORD,
+            RegistryValue::Other(value_type, _) => value_type,
+        }
+    }
+
+    /// Encodes the value as Windows stores it, which `from_raw` decodes.
+    pub fn raw_data(&self) -> Vec<u8> {
+        match *self {
+            RegistryValue::Sz(ref s) | RegistryValue::ExpandSz(ref s) => encode_string(s),
+            RegistryValue::Binary(ref data) | RegistryValue::Other(_, ref data) => data.clone(),
+            RegistryValue::Dword(dword) => dword.to_le_bytes().to_vec(),
+            RegistryValue::MultiSz(ref strings) => {
+                let mut data = strings
+                    .iter()
+                    .flat_map(|s| encode_string(s))
+                    .collect::<Vec<_>>();
+                data.extend_from_slice(&[0, 0]);
+                data
+            }
+            RegistryValue::Qword(qword) => qword.to_le_bytes().to_vec(),
+        }
+    }
+
+    /// Returns the string of a `REG_SZ` or `REG_EXPAND_SZ` value.
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            RegistryValue::Sz(ref s) | RegistryValue::ExpandSz(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of a `REG_DWORD` value.
+    pub fn as_dword(&self) -> Option<u32> {
+        match *self {
+            RegistryValue::Dword(dword) => Some(dword),
+            _ => None,
+        }
+    }
+
+    /// Returns the strings of a `REG_MULTI_SZ` value.
+    pub fn as_multi_sz(&self) -> Option<&[String]> {
+        match *self {
+            RegistryValue::MultiSz(ref strings) => Some(strings),
+            _ => None,
+        }
+    }
+}
+
+/// Access to the Windows registry.
+///
+/// A key is a full path such as `HKEY_CURRENT_USER\Software\Microsoft`. Like in Windows, key and
+/// value names are case-insensitive.
+///
+/// `WindowsRegistry` implements this trait with `advapi32.dll`. `MemoryRegistry` implements it
+/// in memory, and `RegFile` in a `.reg` file exported by `regedit`.
+pub trait Registry {
+    /// Returns the names of the subkeys of `key`. A key that does not exist has none.
+    fn subkeys(&self, key: &str) -> Result<Vec<String>, Error>;
+
+    /// Returns the value `name` of `key`, or `None` if either does not exist. The empty name is
+    /// the default value of `key`.
+    fn value(&self, key: &str, name: &str) -> Result<Option<RegistryValue>, Error>;
+
+    /// Creates `key` and its parents unless they exist.
+    fn create_key(&self, key: &str) -> Result<(), Error>;
+
+    /// Deletes `key` with all of its subkeys and values. A key that does not exist is ignored.
+    fn delete_key(&self, key: &str) -> Result<(), Error>;
+
+    /// Sets the value `name` of `key`, creating `key` unless it exists.
+    fn set_value(&self, key: &str, name: &str, value: &RegistryValue) -> Result<(), Error>;
+
+    /// Deletes the value `name` of `key`. A value that does not exist is ignored.
+    fn delete_value(&self, key: &str, name: &str) -> Result<(), Error>;
+}
+
+/// A registry key in `MemoryRegistry`.
+#[derive(Clone, Debug, Default)]
+struct MemoryKey {
+    /// The path as it was first written
+    path: String,
+    /// Values keyed by their lowercase names, with their names as they were first written
+    values: BTreeMap<String, (String, RegistryValue)>,
+}
+
+/// An in-memory `Registry` that does not need Windows.
+#[derive(Debug, Default)]
+pub struct MemoryRegistry {
+    /// Keys by their lowercase paths
+    keys: RefCell<BTreeMap<String, MemoryKey>>,
+}
+
+impl MemoryRegistry {
+    pub fn new() -> MemoryRegistry {
+        MemoryRegistry::default()
+    }
+
+    /// Creates `key` and its parents unless they exist.
+    pub fn insert_key(&self, key: &str) {
+        let mut keys = self.keys.borrow_mut();
+        let key = key.trim_matches('\\');
+        let mut end = 0;
+        for component in key.split('\\') {
+            end += component.len();
+            let path = &key[..end];
+            keys.entry(path.to_ascii_lowercase()).or_insert_with(|| MemoryKey {
+                path: path.to_string(),
+                values: BTreeMap::new(),
+            });
+            end += 1;
+        }
+    }
+
+    /// Sets the value `name` of `key`, creating `key` unless it exists.
+    pub fn insert_value(&self, key: &str, name: &str, value: RegistryValue) {
+        self.insert_key(key);
+        let mut keys = self.keys.borrow_mut();
+        let values = &mut keys
+            .get_mut(&key.trim_matches('\\').to_ascii_lowercase())
+            .unwrap()
+            .values;
+        let name = values
+            .get(&name.to_ascii_lowercase())
+            .map_or_else(|| name.to_string(), |(name, _)| name.clone());
+        values.insert(name.to_ascii_lowercase(), (name, value));
+    }
+
+    /// Returns the paths of all keys, parents first.
+    pub fn keys(&self) -> Vec<String> {
+        self.keys.borrow().values().map(|k| k.path.clone()).collect()
+    }
+
+    /// Deletes `key` with all of its subkeys and values.
+    pub fn remove_key(&self, key: &str) {
+        let key = key.trim_matches('\\').to_ascii_lowercase();
+        let prefix = format!("{}\\", key);
+        self.keys
+            .borrow_mut()
+            .retain(|path, _| *path != key && !path.starts_with(&prefix));
+    }
+
+    /// Deletes the value `name` of `key`.
+    pub fn remove_value(&self, key: &str, name: &str) {
+        if let Some(k) = self
+            .keys
+            .borrow_mut()
+            .get_mut(&key.trim_matches('\\').to_ascii_lowercase())
+        {
+            k.values.remove(&name.to_ascii_lowercase());
+        }
+    }
+
+    /// Returns the names and values of `key`.
+    pub fn values(&self, key: &str) -> Vec<(String, RegistryValue)> {
+        self.keys
+            .borrow()
+            .get(&key.trim_matches('\\').to_ascii_lowercase())
+            .map_or_else(Vec::new, |k| k.values.values().cloned().collect())
+    }
+}
+
+impl Registry for MemoryRegistry {
+    fn subkeys(&self, key: &str) -> Result<Vec<String>, Error> {
+        let prefix = format!("{}\\", key.trim_matches('\\').to_ascii_lowercase());
+        Ok(self
+            .keys
+            .borrow()
+            .iter()
+            .filter(|&(path, _)| path.starts_with(&prefix) && !path[prefix.len()..].contains('\\'))
+            .map(|(_, k)| k.path[prefix.len()..].to_string())
+            .collect())
+    }
+
+    fn value(&self, key: &str, name: &str) -> Result<Option<RegistryValue>, Error> {
+        Ok(self
+            .keys
+            .borrow()
+            .get(&key.trim_matches('\\').to_ascii_lowercase())
+            .and_then(|k| k.values.get(&name.to_ascii_lowercase()))
+            .map(|(_, value)| value.clone()))
+    }
+
+    fn create_key(&self, key: &str) -> Result<(), Error> {
+        self.insert_key(key);
+        Ok(())
+    }
+
+    fn delete_key(&self, key: &str) -> Result<(), Error> {
+        self.remove_key(key);
+        Ok(())
+    }
+
+    fn set_value(&self, key: &str, name: &str, value: &RegistryValue) -> Result<(), Error> {
+        self.insert_value(key, name, value.clone());
+        Ok(())
+    }
+
+    fn delete_value(&self, key: &str, name: &str) -> Result<(), Error> {
+        self.remove_value(key, name);
+        Ok(())
+    }
+}
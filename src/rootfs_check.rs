@@ -0,0 +1,290 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use failure::Error;
+use tar::{Archive, EntryType};
+use archive::{self, SourceFormat};
+
+/// How many symbolic links are followed before a path is taken as a loop, as Linux does
+const MAX_SYMLINK_HOPS: usize = 40;
+
+/// How bad a `Problem` is. `register` stops on errors and goes on after warnings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Severity::Error => write!(f, "Error"),
+            Severity::Warning => write!(f, "Warning"),
+        }
+    }
+}
+
+/// Something in a rootfs that breaks registering it or the WSL distro made from it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Problem {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Kind {
+    File,
+    Dir,
+    Symlink(String),
+    Device,
+}
+
+/// Where `RootfsCheck::resolve` ends up.
+enum Resolved {
+    Path(String),
+    Loop,
+}
+
+/// Collects the entries of a rootfs one by one and reports the problems in them.
+#[derive(Default)]
+struct RootfsCheck {
+    entries: HashMap<String, Kind>,
+    /// Each lowercased path with the first path that has it
+    lowercase: HashMap<String, String>,
+    all_under_rootfs: bool,
+    problems: Vec<Problem>,
+}
+
+/// Tells whether `path` is absolute or has `..`, which can write outside of the rootfs.
+fn escapes(path: &str) -> bool {
+    path.starts_with('/') || path.split('/').any(|component| component == "..")
+}
+
+impl RootfsCheck {
+    fn new() -> RootfsCheck {
+        RootfsCheck {
+            all_under_rootfs: true,
+            ..RootfsCheck::default()
+        }
+    }
+
+    fn report(&mut self, severity: Severity, message: String) {
+        self.problems.push(Problem { severity, message });
+    }
+
+    fn add(&mut self, raw_path: &str, kind: Kind, hard_link: Option<&str>) {
+        if escapes(raw_path) {
+            self.report(
+                Severity::Error,
+                format!("{} is absolute or has .., which points outside of the rootfs", raw_path),
+            );
+            return;
+        }
+        if let Some(target) = hard_link {
+            if escapes(target) {
+                self.report(
+                    Severity::Error,
+                    format!(
+                        "{} is a hard link to {}, which is outside of the rootfs",
+                        raw_path, target
+                    ),
+                );
+            }
+        }
+        // Absolute paths are reported above, so the leading `/` that this strips is never there.
+        let path = archive::normalize_path(raw_path);
+        if path.is_empty() {
+            return;
+        }
+        if path != "rootfs" && !path.starts_with("rootfs/") {
+            self.all_under_rootfs = false;
+        }
+        if kind == Kind::Device {
+            self.report(
+                Severity::Warning,
+                format!("{} is a device node, which WSL1 rejects", path),
+            );
+        }
+        let lowercase = path.to_lowercase();
+        match self.lowercase.get(&lowercase) {
+            Some(other) if *other != path => self.report(
+                Severity::Warning,
+                format!(
+                    "{} and {} differ only in case, which breaks on NTFS unless the folder is case \
+                     sensitive",
+                    other, path
+                ),
+            ),
+            Some(_) => {}
+            None => {
+                self.lowercase.insert(lowercase, path.clone());
+            }
+        }
+        self.entries.insert(path, kind);
+    }
+
+    /// Follows the symbolic links in `path` like Linux does inside the rootfs.
+    fn resolve(&self, path: &str) -> Resolved {
+        let mut pending = path.split('/').map(str::to_string).collect::<VecDeque<_>>();
+        let mut resolved: Vec<String> = vec![];
+        let mut hops = 0;
+        while let Some(component) = pending.pop_front() {
+            match component.as_str() {
+                "" | "." => continue,
+                ".." => {
+                    resolved.pop();
+                    continue;
+                }
+                _ => resolved.push(component),
+            }
+            if let Some(Kind::Symlink(target)) = self.entries.get(&resolved.join("/")) {
+                hops += 1;
+                if hops > MAX_SYMLINK_HOPS {
+                    return Resolved::Loop;
+                }
+                resolved.pop();
+                if target.starts_with('/') {
+                    resolved.clear();
+                }
+                for component in target.split('/').rev() {
+                    pending.push_front(component.to_string());
+                }
+            }
+        }
+        Resolved::Path(resolved.join("/"))
+    }
+
+    /// Tells whether `path` resolves to something other than a folder.
+    fn has_file(&self, path: &str) -> bool {
+        match self.resolve(path) {
+            Resolved::Path(path) => self.entries.get(&path).is_some_and(|kind| *kind != Kind::Dir),
+            Resolved::Loop => false,
+        }
+    }
+
+    fn finish(mut self) -> Vec<Problem> {
+        if self.entries.is_empty() {
+            self.report(Severity::Error, "the rootfs is empty".to_string());
+            return self.problems;
+        }
+        if self.all_under_rootfs {
+            self.report(
+                Severity::Error,
+                "every file is under rootfs/, but WSL expects the files of rootfs at the top"
+                    .to_string(),
+            );
+        }
+        for path in &["bin/sh", "etc/passwd"] {
+            if !self.has_file(path) {
+                self.report(Severity::Error, format!("/{} is missing", path));
+            }
+        }
+        let mut loops = self
+            .entries
+            .iter()
+            .filter(|&(path, kind)| match *kind {
+                Kind::Symlink(_) => match self.resolve(path) {
+                    Resolved::Loop => true,
+                    Resolved::Path(_) => false,
+                },
+                _ => false,
+            })
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+        loops.sort();
+        for path in loops {
+            self.report(
+                Severity::Warning,
+                format!("{} is a loop of symbolic links", path),
+            );
+        }
+        self.problems
+    }
+}
+
+/// Checks the `.tar` archive that `reader` reads.
+pub fn check_tar<R: Read>(reader: R) -> Result<Vec<Problem>, Error> {
+    let mut check = RootfsCheck::new();
+    for entry in Archive::new(reader).entries()? {
+        let entry = entry?;
+        // `Entry::path` drops `..` and the leading `/`, so the raw bytes are checked instead.
+        let path = String::from_utf8_lossy(&entry.path_bytes()).into_owned();
+        let link_name = entry
+            .link_name_bytes()
+            .map(|name| String::from_utf8_lossy(&name).into_owned());
+        let entry_type = entry.header().entry_type();
+        let kind = match entry_type {
+            EntryType::Directory => Kind::Dir,
+            EntryType::Symlink => Kind::Symlink(link_name.clone().unwrap_or_default()),
+            EntryType::Char | EntryType::Block => Kind::Device,
+            EntryType::XGlobalHeader => continue,
+            _ => Kind::File,
+        };
+        let hard_link = if entry_type.is_hard_link() {
+            link_name.as_deref()
+        } else {
+            None
+        };
+        check.add(&path, kind, hard_link);
+    }
+    Ok(check.finish())
+}
+
+fn add_dir(check: &mut RootfsCheck, root: &Path, dir: &Path) -> Result<(), Error> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let relative = path
+            .strip_prefix(root)?
+            .to_string_lossy()
+            .replace('\\', "/");
+        let file_type = entry.file_type()?;
+        let kind = if file_type.is_dir() {
+            Kind::Dir
+        } else if file_type.is_symlink() {
+            Kind::Symlink(fs::read_link(&path)?.to_string_lossy().into_owned())
+        } else if is_device(&file_type) {
+            Kind::Device
+        } else {
+            Kind::File
+        };
+        check.add(&relative, kind, None);
+        if file_type.is_dir() {
+            add_dir(check, root, &path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_device(file_type: &fs::FileType) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    file_type.is_char_device() || file_type.is_block_device()
+}
+
+#[cfg(not(unix))]
+fn is_device(_: &fs::FileType) -> bool {
+    false
+}
+
+/// Checks the rootfs in `path`, which is a folder or an archive that `register` accepts.
+pub fn check_rootfs<P: AsRef<Path>>(path: P) -> Result<Vec<Problem>, Error> {
+    let path = path.as_ref();
+    match SourceFormat::detect(path)? {
+        SourceFormat::Directory => {
+            let mut check = RootfsCheck::new();
+            add_dir(&mut check, path, path)?;
+            Ok(check.finish())
+        }
+        format => check_tar(archive::open_tar(path, format)?),
+    }
+}
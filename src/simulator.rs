@@ -0,0 +1,312 @@
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use failure::Error;
+use flate2::read::GzDecoder;
+use tar::Archive;
+use backend::WslBackend;
+use distro::{DistroConfiguration, DistroFlags};
+use process::{self, HostProcess, LaunchStdio};
+use wsl_error::WslError;
+
+const STATE_FILE_NAME: &str = "state";
+const ROOTFS_DIR_NAME: &str = "rootfs";
+
+type Responder = Box<dyn Fn(&SimulatedLaunch) -> Command>;
+
+/// A launch that `SimulatorBackend` has to carry out.
+pub struct SimulatedLaunch<'a> {
+    pub configuration: &'a DistroConfiguration,
+    pub rootfs: &'a Path,
+    pub command: &'a str,
+    pub use_cwd: bool,
+}
+
+/// A `WslBackend` that simulates registered distros on disk.
+///
+/// Each distro is a folder under the simulator root that contains a `state` file and the
+/// unpacked `rootfs`. Launched commands run in a `chroot` of the `rootfs` when the current
+/// process is root, or through a responder that turns each launch into a host command.
+pub struct SimulatorBackend {
+    root: PathBuf,
+    responder: Option<Responder>,
+}
+
+impl SimulatorBackend {
+    pub fn new<P: AsRef<Path>>(root: P) -> Result<SimulatorBackend, Error> {
+        let root = root.as_ref();
+        if let Err(e) = fs::create_dir_all(root) {
+            return Err(format_err!("SimulatorBackend::new {}", e));
+        }
+        Ok(SimulatorBackend {
+            root: root.to_path_buf(),
+            responder: None,
+        })
+    }
+
+    /// Runs launches with the command that `responder` returns instead of `chroot`.
+    pub fn with_responder<F>(mut self, responder: F) -> SimulatorBackend
+    where
+        F: Fn(&SimulatedLaunch) -> Command + 'static,
+    {
+        self.responder = Some(Box::new(responder));
+        self
+    }
+
+    /// Runs launches as `<program> <rootfs> <command>` instead of `chroot`.
+    ///
+    /// The distro name, the default UID and whether the current working directory is used are
+    /// passed as `YOWSL_DISTRO_NAME`, `YOWSL_DEFAULT_UID` and `YOWSL_USE_CWD`.
+    pub fn with_responder_program<P: AsRef<OsStr>>(self, program: P) -> SimulatorBackend {
+        let program = program.as_ref().to_os_string();
+        self.with_responder(move |launch| {
+            let mut command = Command::new(&program);
+            command
+                .arg(launch.rootfs)
+                .arg(launch.command)
+                .env("YOWSL_DISTRO_NAME", &launch.configuration.name)
+                .env(
+                    "YOWSL_DEFAULT_UID",
+                    launch.configuration.default_uid.to_string(),
+                )
+                .env("YOWSL_USE_CWD", if launch.use_cwd { "1" } else { "0" });
+            command
+        })
+    }
+
+    /// Returns the folder that holds a distro.
+    pub fn distro_path(&self, distro_name: &str) -> PathBuf {
+        self.root.join(distro_name)
+    }
+
+    /// Returns the folder that a distro's archive is unpacked into.
+    pub fn rootfs_path(&self, distro_name: &str) -> PathBuf {
+        self.distro_path(distro_name).join(ROOTFS_DIR_NAME)
+    }
+
+    fn state_path(&self, distro_name: &str) -> PathBuf {
+        self.distro_path(distro_name).join(STATE_FILE_NAME)
+    }
+
+    fn validate_name(distro_name: &str) -> Result<(), Error> {
+        if distro_name.is_empty() || distro_name == "." || distro_name == ".."
+            || distro_name.contains(&['/', '\\', '\n'][..])
+        {
+            Err(WslError::InvalidArgument.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn read_state(&self, distro_name: &str) -> Result<DistroConfiguration, Error> {
+        Self::validate_name(distro_name)?;
+        let file = match File::open(self.state_path(distro_name)) {
+            Ok(file) => file,
+            Err(_) => return Err(WslError::InvalidArgument.into()),
+        };
+        let mut distro_configuration = DistroConfiguration {
+            name: distro_name.to_string(),
+            version: 1,
+            default_uid: 0,
+            flags: DistroFlags::default(),
+            default_environment_variables: vec![],
+        };
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let (key, value) = match line.find(" = ") {
+                Some(i) => (&line[..i], &line[i + 3..]),
+                None => return Err(format_err!("invalid simulator state line: {}", line)),
+            };
+            match key {
+                "name" => distro_configuration.name = value.to_string(),
+                "version" => distro_configuration.version = value.parse()?,
+                "default_uid" => distro_configuration.default_uid = value.parse()?,
+                "flags" => {
+                    distro_configuration.flags = DistroFlags::from_raw(value.parse()?)
+                }
+                "env" => distro_configuration
+                    .default_environment_variables
+                    .push(value.to_string()),
+                _ => return Err(format_err!("invalid simulator state key: {}", key)),
+            }
+        }
+        Ok(distro_configuration)
+    }
+
+    fn write_state(&self, distro_configuration: &DistroConfiguration) -> Result<(), Error> {
+        let mut file = File::create(self.state_path(&distro_configuration.name))?;
+        writeln!(file, "name = {}", distro_configuration.name)?;
+        writeln!(file, "version = {}", distro_configuration.version)?;
+        writeln!(file, "default_uid = {}", distro_configuration.default_uid)?;
+        writeln!(file, "flags = {}", distro_configuration.flags.bits())?;
+        for variable in &distro_configuration.default_environment_variables {
+            writeln!(file, "env = {}", variable)?;
+        }
+        Ok(())
+    }
+
+    fn unpack(&self, distro_name: &str, tar_gz_filename: &str) -> Result<(), Error> {
+        let rootfs = self.rootfs_path(distro_name);
+        fs::create_dir_all(&rootfs)?;
+        let mut archive = Archive::new(GzDecoder::new(File::open(tar_gz_filename)?));
+        archive.set_preserve_permissions(true);
+        archive.unpack(&rootfs)?;
+        Ok(())
+    }
+
+    fn command(&self, launch: &SimulatedLaunch) -> Result<Command, Error> {
+        match self.responder {
+            Some(ref responder) => Ok(responder(launch)),
+            None => chroot_command(launch),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn chroot_command(launch: &SimulatedLaunch) -> Result<Command, Error> {
+    use std::os::unix::fs::MetadataExt;
+
+    if fs::metadata("/proc/self")?.uid() != 0 {
+        return Err(format_err!(
+            "the simulator needs root to chroot into \"{}\"; use a responder instead",
+            launch.rootfs.display()
+        ));
+    }
+    let mut command = Command::new("chroot");
+    let default_uid = launch.configuration.default_uid;
+    if default_uid != 0 {
+        let default_gid = primary_gid(launch.rootfs, default_uid).unwrap_or(default_uid);
+        command.arg(format!("--userspec={}:{}", default_uid, default_gid));
+    }
+    command.arg(launch.rootfs).arg("/bin/sh");
+    if !launch.command.is_empty() {
+        command.arg("-c").arg(launch.command);
+    }
+    command.env_clear();
+    for variable in &launch.configuration.default_environment_variables {
+        if let Some(i) = variable.find('=') {
+            command.env(&variable[..i], &variable[i + 1..]);
+        }
+    }
+    Ok(command)
+}
+
+#[cfg(not(unix))]
+fn chroot_command(launch: &SimulatedLaunch) -> Result<Command, Error> {
+    Err(format_err!(
+        "the simulator cannot chroot into \"{}\" on this platform; use a responder instead",
+        launch.rootfs.display()
+    ))
+}
+
+/// Looks up the primary group of `uid` in `<rootfs>/etc/passwd`.
+#[cfg(unix)]
+fn primary_gid(rootfs: &Path, uid: u32) -> Option<u32> {
+    let file = File::open(rootfs.join("etc/passwd")).ok()?;
+    BufReader::new(file)
+        .lines()
+        .map_while(|line| line.ok())
+        .filter_map(|line| {
+            let fields = line.split(':').collect::<Vec<_>>();
+            if fields.len() < 4 || fields[2].parse() != Ok(uid) {
+                return None;
+            }
+            fields[3].parse().ok()
+        })
+        .next()
+}
+
+impl WslBackend for SimulatorBackend {
+    type Process = HostProcess;
+
+    fn register_distro(&self, distro_name: &str, tar_gz_filename: &str) -> Result<(), Error> {
+        Self::validate_name(distro_name)?;
+        if self.distro_path(distro_name).exists() {
+            return Err(WslError::AlreadyExists.into());
+        }
+        if let Err(e) = self.unpack(distro_name, tar_gz_filename) {
+            let _ = fs::remove_dir_all(self.distro_path(distro_name));
+            return Err(format_err!("SimulatorBackend::register_distro {}", e));
+        }
+        self.write_state(&DistroConfiguration {
+            name: distro_name.to_string(),
+            version: 1,
+            default_uid: 0,
+            flags: DistroFlags::all(),
+            default_environment_variables: DistroConfiguration::default_environment_variables(),
+        })
+    }
+
+    fn unregister_distro(&self, distro_name: &str) -> Result<(), Error> {
+        self.read_state(distro_name)?;
+        match fs::remove_dir_all(self.distro_path(distro_name)) {
+            Ok(()) => Ok(()),
+            Err(e) => Err(format_err!("SimulatorBackend::unregister_distro {}", e)),
+        }
+    }
+
+    fn get_distro_configuration(
+        &self,
+        distro_name: &str,
+    ) -> Result<DistroConfiguration, Error> {
+        self.read_state(distro_name)
+    }
+
+    fn configure_distro(&self, distro_configuration: &DistroConfiguration) -> Result<(), Error> {
+        let mut state = self.read_state(&distro_configuration.name)?;
+        state.default_uid = distro_configuration.default_uid;
+        state.flags = distro_configuration.flags;
+        self.write_state(&state)
+    }
+
+    fn launch(&self, distro_name: &str, command: &str, use_cwd: bool) -> Result<u32, Error> {
+        let configuration = self.read_state(distro_name)?;
+        let rootfs = self.rootfs_path(distro_name);
+        let mut command = self.command(&SimulatedLaunch {
+            configuration: &configuration,
+            rootfs: &rootfs,
+            command,
+            use_cwd,
+        })?;
+        match command.status() {
+            Ok(status) => Ok(process::exit_code(status)),
+            Err(e) => Err(format_err!("SimulatorBackend::launch {}", e)),
+        }
+    }
+
+    fn spawn(
+        &self,
+        distro_name: &str,
+        command: &str,
+        use_cwd: bool,
+        stdio: LaunchStdio,
+    ) -> Result<HostProcess, Error> {
+        let configuration = self.read_state(distro_name)?;
+        let rootfs = self.rootfs_path(distro_name);
+        let mut command = self.command(&SimulatedLaunch {
+            configuration: &configuration,
+            rootfs: &rootfs,
+            command,
+            use_cwd,
+        })?;
+        if let Some(stdin) = stdio.stdin {
+            command.stdin(Stdio::from(stdin));
+        }
+        if let Some(stdout) = stdio.stdout {
+            command.stdout(Stdio::from(stdout));
+        }
+        if let Some(stderr) = stdio.stderr {
+            command.stderr(Stdio::from(stderr));
+        }
+        match command.spawn() {
+            Ok(child) => Ok(HostProcess::new(child)),
+            Err(e) => Err(format_err!("SimulatorBackend::spawn {}", e)),
+        }
+    }
+
+    fn is_distribution_registered(&self, distro_name: &str) -> Result<bool, Error> {
+        Ok(Self::validate_name(distro_name).is_ok() && self.state_path(distro_name).is_file())
+    }
+}
@@ -0,0 +1,148 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::mem;
+use std::os::raw::c_void;
+use std::os::windows::ffi::OsStrExt;
+use std::os::windows::fs::OpenOptionsExt;
+use std::os::windows::io::AsRawHandle;
+use std::path::Path;
+use std::ptr;
+
+type BOOL = i32;
+type DWORD = u32;
+#[allow(non_camel_case_types)]
+type FILE_INFO_BY_HANDLE_CLASS = i32;
+type HANDLE = *mut c_void;
+type LPCWSTR = *const u16;
+
+const FILE_CASE_SENSITIVE_INFO: FILE_INFO_BY_HANDLE_CLASS = 23;
+const FILE_CS_FLAG_CASE_SENSITIVE_DIR: DWORD = 1;
+/// Needed to open a folder instead of a file
+const FILE_FLAG_BACKUP_SEMANTICS: DWORD = 0x0200_0000;
+const FILE_READ_ATTRIBUTES: DWORD = 0x0080;
+const FILE_WRITE_ATTRIBUTES: DWORD = 0x0100;
+
+#[repr(C)]
+struct FileCaseSensitiveInfo {
+    flags: DWORD,
+}
+
+#[repr(C)]
+#[allow(dead_code)]
+struct ByHandleFileInformation {
+    file_attributes: DWORD,
+    /// `FILETIME`s, which are pairs of `DWORD`s
+    creation_time: [DWORD; 2],
+    last_access_time: [DWORD; 2],
+    last_write_time: [DWORD; 2],
+    volume_serial_number: DWORD,
+    file_size_high: DWORD,
+    file_size_low: DWORD,
+    number_of_links: DWORD,
+    file_index_high: DWORD,
+    file_index_low: DWORD,
+}
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn CreateDirectoryExW(
+        template_directory: LPCWSTR,
+        new_directory: LPCWSTR,
+        security_attributes: *mut c_void,
+    ) -> BOOL;
+    fn GetFileInformationByHandle(file: HANDLE, info: *mut ByHandleFileInformation) -> BOOL;
+    fn GetFileInformationByHandleEx(
+        file: HANDLE,
+        class: FILE_INFO_BY_HANDLE_CLASS,
+        info: *mut c_void,
+        info_len: DWORD,
+    ) -> BOOL;
+    fn SetFileInformationByHandle(
+        file: HANDLE,
+        class: FILE_INFO_BY_HANDLE_CLASS,
+        info: *mut c_void,
+        info_len: DWORD,
+    ) -> BOOL;
+}
+
+fn to_vec_u16(path: &Path) -> Vec<u16> {
+    path.as_os_str().encode_wide().chain(Some(0)).collect()
+}
+
+fn open_dir(path: &Path, access: DWORD) -> io::Result<File> {
+    OpenOptions::new()
+        .access_mode(access)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+        .open(path)
+}
+
+/// Returns whether the folder `path` is case sensitive. Windows before 10 version 1803 cannot
+/// tell, and has no case sensitive folders either.
+fn is_case_sensitive(path: &Path) -> bool {
+    let dir = match open_dir(path, FILE_READ_ATTRIBUTES) {
+        Ok(dir) => dir,
+        Err(_) => return false,
+    };
+    let mut info = FileCaseSensitiveInfo { flags: 0 };
+    let succeeded = unsafe {
+        GetFileInformationByHandleEx(
+            dir.as_raw_handle() as HANDLE,
+            FILE_CASE_SENSITIVE_INFO,
+            &mut info as *mut FileCaseSensitiveInfo as *mut c_void,
+            mem::size_of::<FileCaseSensitiveInfo>() as DWORD,
+        )
+    };
+    succeeded != 0 && info.flags & FILE_CS_FLAG_CASE_SENSITIVE_DIR != 0
+}
+
+/// Returns the volume serial number and the file index of the file `path`, which its hard links
+/// share, or `None` if it has no other hard link.
+pub fn hard_link_id(path: &Path) -> io::Result<Option<(u64, u64)>> {
+    let file = OpenOptions::new().access_mode(FILE_READ_ATTRIBUTES).open(path)?;
+    let mut info: ByHandleFileInformation = unsafe { mem::zeroed() };
+    if unsafe { GetFileInformationByHandle(file.as_raw_handle() as HANDLE, &mut info) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if info.number_of_links < 2 {
+        return Ok(None);
+    }
+    let index = (u64::from(info.file_index_high) << 32) | u64::from(info.file_index_low);
+    Ok(Some((u64::from(info.volume_serial_number), index)))
+}
+
+/// Creates the folder `path` like `template`.
+///
+/// `CreateDirectoryExW` copies the attributes and the extended attributes of `template`, where
+/// WSL keeps the owner and the mode of a folder in `rootfs`. The case sensitivity, which lets a
+/// folder have names that differ only in case, is copied separately.
+pub fn create_dir_like(template: &Path, path: &Path) -> io::Result<()> {
+    let created = unsafe {
+        CreateDirectoryExW(
+            to_vec_u16(template).as_ptr(),
+            to_vec_u16(path).as_ptr(),
+            ptr::null_mut(),
+        )
+    };
+    if created == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if !is_case_sensitive(template) {
+        return Ok(());
+    }
+    let dir = open_dir(path, FILE_WRITE_ATTRIBUTES)?;
+    let mut info = FileCaseSensitiveInfo {
+        flags: FILE_CS_FLAG_CASE_SENSITIVE_DIR,
+    };
+    let succeeded = unsafe {
+        SetFileInformationByHandle(
+            dir.as_raw_handle() as HANDLE,
+            FILE_CASE_SENSITIVE_INFO,
+            &mut info as *mut FileCaseSensitiveInfo as *mut c_void,
+            mem::size_of::<FileCaseSensitiveInfo>() as DWORD,
+        )
+    };
+    if succeeded == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
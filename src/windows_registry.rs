@@ -0,0 +1,286 @@
+use std::os::raw::c_void;
+use std::ptr;
+use failure::Error;
+use registry::{Registry, RegistryValue};
+use wide_chars;
+use wsl_error::WslError;
+
+type DWORD = u32;
+type HKEY = *mut c_void;
+type LONG = i32;
+type LPCWSTR = *const u16;
+type LPWSTR = *mut u16;
+type REGSAM = u32;
+
+const ERROR_SUCCESS: LONG = 0;
+const ERROR_FILE_NOT_FOUND: LONG = 2;
+const ERROR_MORE_DATA: LONG = 234;
+const ERROR_NO_MORE_ITEMS: LONG = 259;
+// The predefined keys are negative `LONG`s sign-extended into handles, as in the Windows SDK.
+const HKEY_CLASSES_ROOT: HKEY = 0x8000_0000u32 as i32 as isize as HKEY;
+const HKEY_CURRENT_USER: HKEY = 0x8000_0001u32 as i32 as isize as HKEY;
+const HKEY_LOCAL_MACHINE: HKEY = 0x8000_0002u32 as i32 as isize as HKEY;
+const HKEY_USERS: HKEY = 0x8000_0003u32 as i32 as isize as HKEY;
+const KEY_READ: REGSAM = 0x0002_0019;
+const KEY_WRITE: REGSAM = 0x0002_0006;
+/// The longest key name in characters, including the terminating null
+const MAX_KEY_NAME_LEN: usize = 256;
+const REG_OPTION_NON_VOLATILE: DWORD = 0;
+
+#[link(name = "advapi32")]
+extern "system" {
+    fn RegCloseKey(key: HKEY) -> LONG;
+    fn RegCreateKeyExW(
+        key: HKEY,
+        sub_key: LPCWSTR,
+        reserved: DWORD,
+        class: LPWSTR,
+        options: DWORD,
+        sam_desired: REGSAM,
+        security_attributes: *mut c_void,
+        result: *mut HKEY,
+        disposition: *mut DWORD,
+    ) -> LONG;
+    fn RegDeleteKeyW(key: HKEY, sub_key: LPCWSTR) -> LONG;
+    fn RegDeleteTreeW(key: HKEY, sub_key: LPCWSTR) -> LONG;
+    fn RegDeleteValueW(key: HKEY, value_name: LPCWSTR) -> LONG;
+    fn RegEnumKeyExW(
+        key: HKEY,
+        index: DWORD,
+        name: LPWSTR,
+        name_len: *mut DWORD,
+        reserved: *mut DWORD,
+        class: LPWSTR,
+        class_len: *mut DWORD,
+        last_write_time: *mut c_void,
+    ) -> LONG;
+    fn RegOpenKeyExW(
+        key: HKEY,
+        sub_key: LPCWSTR,
+        options: DWORD,
+        sam_desired: REGSAM,
+        result: *mut HKEY,
+    ) -> LONG;
+    fn RegQueryValueExW(
+        key: HKEY,
+        value_name: LPCWSTR,
+        reserved: *mut DWORD,
+        value_type: *mut DWORD,
+        data: *mut u8,
+        data_len: *mut DWORD,
+    ) -> LONG;
+    fn RegSetValueExW(
+        key: HKEY,
+        value_name: LPCWSTR,
+        reserved: DWORD,
+        value_type: DWORD,
+        data: *const u8,
+        data_len: DWORD,
+    ) -> LONG;
+}
+
+/// Converts a Win32 error code into a `WslError`, as `HRESULT_FROM_WIN32` does.
+fn win32_error(code: LONG) -> Error {
+    WslError::from_hresult((0x8007_0000 | (code as u32 & 0xFFFF)) as i32).into()
+}
+
+/// Splits `path` into its root key and the path under it.
+fn split_root(path: &str) -> Result<(HKEY, &str), Error> {
+    let path = path.trim_matches('\\');
+    let (root, sub_key) = match path.find('\\') {
+        Some(i) => (&path[..i], &path[i + 1..]),
+        None => (path, ""),
+    };
+    let root = match &root.to_ascii_uppercase()[..] {
+        "HKEY_CLASSES_ROOT" | "HKCR" => HKEY_CLASSES_ROOT,
+        "HKEY_CURRENT_USER" | "HKCU" => HKEY_CURRENT_USER,
+        "HKEY_LOCAL_MACHINE" | "HKLM" => HKEY_LOCAL_MACHINE,
+        "HKEY_USERS" | "HKU" => HKEY_USERS,
+        _ => return Err(format_err!("\"{}\" is not a registry root key", root)),
+    };
+    Ok((root, sub_key))
+}
+
+/// An open registry key that is closed when dropped.
+struct Key(HKEY);
+
+impl Key {
+    /// Opens `path` with `sam_desired` access, or returns `None` if it does not exist.
+    fn open(path: &str, sam_desired: REGSAM) -> Result<Option<Key>, Error> {
+        let (root, sub_key) = split_root(path)?;
+        let mut key = ptr::null_mut();
+        match unsafe {
+            RegOpenKeyExW(
+                root,
+                wide_chars::to_vec_u16(sub_key).as_ptr(),
+                0,
+                sam_desired,
+                &mut key,
+            )
+        } {
+            ERROR_SUCCESS => Ok(Some(Key(key))),
+            ERROR_FILE_NOT_FOUND => Ok(None),
+            code => Err(win32_error(code)),
+        }
+    }
+
+    /// Opens `path` for writing, creating it and its parents unless they exist.
+    fn create(path: &str) -> Result<Key, Error> {
+        let (root, sub_key) = split_root(path)?;
+        let mut key = ptr::null_mut();
+        match unsafe {
+            RegCreateKeyExW(
+                root,
+                wide_chars::to_vec_u16(sub_key).as_ptr(),
+                0,
+                ptr::null_mut(),
+                REG_OPTION_NON_VOLATILE,
+                KEY_READ | KEY_WRITE,
+                ptr::null_mut(),
+                &mut key,
+                ptr::null_mut(),
+            )
+        } {
+            ERROR_SUCCESS => Ok(Key(key)),
+            code => Err(win32_error(code)),
+        }
+    }
+}
+
+impl Drop for Key {
+    fn drop(&mut self) {
+        unsafe { RegCloseKey(self.0) };
+    }
+}
+
+/// The registry of Windows, accessed with `advapi32.dll`.
+#[derive(Debug, Default)]
+pub struct WindowsRegistry;
+
+impl WindowsRegistry {
+    pub fn new() -> WindowsRegistry {
+        WindowsRegistry
+    }
+}
+
+impl Registry for WindowsRegistry {
+    fn subkeys(&self, key: &str) -> Result<Vec<String>, Error> {
+        let key = match Key::open(key, KEY_READ)? {
+            Some(key) => key,
+            None => return Ok(vec![]),
+        };
+        let mut subkeys = vec![];
+        for index in 0.. {
+            let mut name = [0u16; MAX_KEY_NAME_LEN];
+            let mut name_len = name.len() as DWORD;
+            match unsafe {
+                RegEnumKeyExW(
+                    key.0,
+                    index,
+                    name.as_mut_ptr(),
+                    &mut name_len,
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                )
+            } {
+                ERROR_SUCCESS => {
+                    subkeys.push(String::from_utf16_lossy(&name[..name_len as usize]))
+                }
+                ERROR_NO_MORE_ITEMS => break,
+                code => return Err(win32_error(code)),
+            }
+        }
+        Ok(subkeys)
+    }
+
+    fn value(&self, key: &str, name: &str) -> Result<Option<RegistryValue>, Error> {
+        let key = match Key::open(key, KEY_READ)? {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        let name = wide_chars::to_vec_u16(name);
+        let mut data = vec![0u8; 256];
+        loop {
+            let mut value_type = 0;
+            let mut data_len = data.len() as DWORD;
+            match unsafe {
+                RegQueryValueExW(
+                    key.0,
+                    name.as_ptr(),
+                    ptr::null_mut(),
+                    &mut value_type,
+                    data.as_mut_ptr(),
+                    &mut data_len,
+                )
+            } {
+                ERROR_SUCCESS => {
+                    data.truncate(data_len as usize);
+                    return Ok(Some(RegistryValue::from_raw(value_type, data)));
+                }
+                // The value may grow between the calls, so ask again with the new length.
+                ERROR_MORE_DATA => data.resize(data_len as usize, 0),
+                ERROR_FILE_NOT_FOUND => return Ok(None),
+                code => return Err(win32_error(code)),
+            }
+        }
+    }
+
+    fn create_key(&self, key: &str) -> Result<(), Error> {
+        Key::create(key).map(|_| ())
+    }
+
+    fn delete_key(&self, key: &str) -> Result<(), Error> {
+        let key = key.trim_matches('\\');
+        let (parent, name) = match key.rfind('\\') {
+            Some(i) => (&key[..i], &key[i + 1..]),
+            None => return Err(format_err!("\"{}\" is a root key", key)),
+        };
+        let parent = match Key::open(parent, KEY_READ | KEY_WRITE)? {
+            Some(parent) => parent,
+            None => return Ok(()),
+        };
+        let name = wide_chars::to_vec_u16(name);
+        // RegDeleteTreeW empties the key, and RegDeleteKeyW deletes the empty key itself.
+        for result in &[
+            unsafe { RegDeleteTreeW(parent.0, name.as_ptr()) },
+            unsafe { RegDeleteKeyW(parent.0, name.as_ptr()) },
+        ] {
+            match *result {
+                ERROR_SUCCESS | ERROR_FILE_NOT_FOUND => {}
+                code => return Err(win32_error(code)),
+            }
+        }
+        Ok(())
+    }
+
+    fn set_value(&self, key: &str, name: &str, value: &RegistryValue) -> Result<(), Error> {
+        let key = Key::create(key)?;
+        let data = value.raw_data();
+        match unsafe {
+            RegSetValueExW(
+                key.0,
+                wide_chars::to_vec_u16(name).as_ptr(),
+                0,
+                value.raw_type(),
+                data.as_ptr(),
+                data.len() as DWORD,
+            )
+        } {
+            ERROR_SUCCESS => Ok(()),
+            code => Err(win32_error(code)),
+        }
+    }
+
+    fn delete_value(&self, key: &str, name: &str) -> Result<(), Error> {
+        let key = match Key::open(key, KEY_READ | KEY_WRITE)? {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+        match unsafe { RegDeleteValueW(key.0, wide_chars::to_vec_u16(name).as_ptr()) } {
+            ERROR_SUCCESS | ERROR_FILE_NOT_FOUND => Ok(()),
+            code => Err(win32_error(code)),
+        }
+    }
+}
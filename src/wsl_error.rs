@@ -0,0 +1,168 @@
+use std::error;
+use std::fmt;
+
+#[allow(clippy::upper_case_acronyms)]
+type HRESULT = i32;
+
+const FACILITY_WIN32: u16 = 7;
+
+/// An error `HRESULT` returned by the Windows Subsystem for Linux.
+///
+/// Known `HRESULT`s are decoded into named variants. Any other `HRESULT` is kept as it is in
+/// `WslError::Other`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WslError {
+    /// `E_ILLEGAL_STATE_CHANGE` (`0x8000000D`)
+    IllegalStateChange,
+    /// `E_UNEXPECTED` (`0x8000FFFF`)
+    Unexpected,
+    /// `REGDB_E_CLASSNOTREG` (`0x80040154`)
+    ClassNotRegistered,
+    /// `ERROR_FILE_NOT_FOUND` (`0x80070002`)
+    FileNotFound,
+    /// `ERROR_PATH_NOT_FOUND` (`0x80070003`)
+    PathNotFound,
+    /// `ERROR_ACCESS_DENIED` (`0x80070005`)
+    AccessDenied,
+    /// `ERROR_SHARING_VIOLATION` (`0x80070020`)
+    SharingViolation,
+    /// `E_INVALIDARG` (`0x80070057`)
+    InvalidArgument,
+    /// `ERROR_ALREADY_EXISTS` (`0x800700B7`)
+    AlreadyExists,
+    /// `ERROR_LINUX_SUBSYSTEM_NOT_PRESENT` (`0x8007019E`)
+    SubsystemNotPresent,
+    /// `ERROR_LINUX_SUBSYSTEM_UPDATE_REQUIRED` (`0x800701BC`)
+    SubsystemUpdateRequired,
+    /// `ERROR_SERVICE_DISABLED` (`0x80070422`)
+    ServiceDisabled,
+    /// `HCS_E_HYPERV_NOT_INSTALLED` (`0x80370102`)
+    VirtualMachinePlatformNotEnabled,
+    /// Any other `HRESULT`
+    Other(HRESULT),
+}
+
+const KNOWN_ERRORS: [(WslError, u32); 13] = [
+    (WslError::IllegalStateChange, 0x8000_000D),
+    (WslError::Unexpected, 0x8000_FFFF),
+    (WslError::ClassNotRegistered, 0x8004_0154),
+    (WslError::FileNotFound, 0x8007_0002),
+    (WslError::PathNotFound, 0x8007_0003),
+    (WslError::AccessDenied, 0x8007_0005),
+    (WslError::SharingViolation, 0x8007_0020),
+    (WslError::InvalidArgument, 0x8007_0057),
+    (WslError::AlreadyExists, 0x8007_00B7),
+    (WslError::SubsystemNotPresent, 0x8007_019E),
+    (WslError::SubsystemUpdateRequired, 0x8007_01BC),
+    (WslError::ServiceDisabled, 0x8007_0422),
+    (WslError::VirtualMachinePlatformNotEnabled, 0x8037_0102),
+];
+
+impl WslError {
+    pub fn from_hresult(hresult: HRESULT) -> WslError {
+        KNOWN_ERRORS
+            .iter()
+            .find(|&&(_, known)| known as HRESULT == hresult)
+            .map(|&(wsl_error, _)| wsl_error)
+            .unwrap_or(WslError::Other(hresult))
+    }
+
+    /// Returns the raw `HRESULT`.
+    pub fn hresult(&self) -> HRESULT {
+        match *self {
+            WslError::Other(hresult) => hresult,
+            wsl_error => {
+                KNOWN_ERRORS
+                    .iter()
+                    .find(|&&(known, _)| known == wsl_error)
+                    .unwrap()
+                    .1 as HRESULT
+            }
+        }
+    }
+
+    /// Returns the facility of the `HRESULT`, e.g. 7 for Win32 errors.
+    pub fn facility(&self) -> u16 {
+        ((self.hresult() >> 16) & 0x1FFF) as u16
+    }
+
+    /// Returns the code of the `HRESULT`, e.g. the Win32 error code for Win32 errors.
+    pub fn code(&self) -> u16 {
+        (self.hresult() & 0xFFFF) as u16
+    }
+
+    /// Explains the error in a human readable way.
+    pub fn explanation(&self) -> String {
+        let explanation = match *self {
+            WslError::IllegalStateChange => "The WSL distro is being installed or uninstalled",
+            WslError::Unexpected => "An unexpected failure occurred",
+            WslError::ClassNotRegistered => "The WSL service is not registered",
+            WslError::FileNotFound => "The file cannot be found",
+            WslError::PathNotFound => "The path cannot be found",
+            WslError::AccessDenied => "Access is denied",
+            WslError::SharingViolation => "The file is being used by another process",
+            WslError::InvalidArgument => {
+                "An argument is invalid. The WSL distro may not be registered"
+            }
+            WslError::AlreadyExists => "The WSL distro already exists",
+            WslError::SubsystemNotPresent => {
+                "The Windows Subsystem for Linux has not been enabled"
+            }
+            WslError::SubsystemUpdateRequired => {
+                "The Windows Subsystem for Linux requires an update"
+            }
+            WslError::ServiceDisabled => "The WSL service is disabled",
+            WslError::VirtualMachinePlatformNotEnabled => {
+                "The virtual machine cannot be started because a required feature is not \
+                 installed"
+            }
+            WslError::Other(_) if self.facility() == FACILITY_WIN32 => {
+                return format!("Win32 error {}", self.code())
+            }
+            WslError::Other(_) => {
+                return format!(
+                    "Unknown error (facility {}, code {})",
+                    self.facility(),
+                    self.code()
+                )
+            }
+        };
+        explanation.to_string()
+    }
+
+    /// Returns what a user can do about the error if anything is known.
+    pub fn hint(&self) -> Option<&'static str> {
+        match *self {
+            WslError::SubsystemNotPresent | WslError::ClassNotRegistered => Some(
+                "Enable the Microsoft-Windows-Subsystem-Linux feature in PowerShell as \
+                 Administrator: Enable-WindowsOptionalFeature -Online -FeatureName \
+                 Microsoft-Windows-Subsystem-Linux",
+            ),
+            WslError::SubsystemUpdateRequired => {
+                Some("Update the WSL kernel component and try again")
+            }
+            WslError::ServiceDisabled => Some("Enable and start the LxssManager service"),
+            WslError::VirtualMachinePlatformNotEnabled => Some(
+                "Enable the VirtualMachinePlatform feature and virtualization in the BIOS",
+            ),
+            WslError::AccessDenied => Some("Check the permissions of the files and folders"),
+            WslError::SharingViolation => Some("Close processes that use the WSL distro"),
+            WslError::IllegalStateChange => Some("Wait for the operation to finish"),
+            WslError::AlreadyExists => Some("Choose another WSL distro name"),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for WslError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} (HRESULT == {:#010X})",
+            self.explanation(),
+            self.hresult()
+        )
+    }
+}
+
+impl error::Error for WslError {}
@@ -1,17 +1,25 @@
-use std::fmt;
+use std::io;
 use std::os::raw::{c_char, c_void};
-use std::ffi::CStr;
+use std::os::windows::io::AsRawHandle;
+use std::ptr;
 use failure::Error;
-use libloading::{Library, Symbol};
-use libloading::Result as LibloadingResult;
+use libloading::Library;
+use backend::WslBackend;
+use co_task_mem::{CoTaskMemArray, CoTaskMemFree, CoTaskMemString};
+use distro::{DistroConfiguration, DistroFlags};
+use process::{LaunchStdio, WslProcess};
 use wide_chars;
+use wsl_error::WslError;
 
+type BOOL = i32;
 type DWORD = u32;
+type HANDLE = *mut c_void;
 type HRESULT = LONG;
 type LONG = i32;
-type LPVOID = *const c_void;
+type LPVOID = *mut c_void;
 type PCWSTR = *mut u16;
 type PSTR = *mut c_char;
+type UINT = u32;
 type ULONG = u32;
 #[allow(non_camel_case_types)]
 type WSL_DISTRIBUTION_FLAGS = u32;
@@ -21,82 +29,75 @@ type RegisterDistributionFn = unsafe extern "system" fn(PCWSTR, PCWSTR) -> HRESU
 type UnregisterDistributionFn = unsafe extern "system" fn(PCWSTR) -> HRESULT;
 type GetDistributionConfigurationFn = unsafe extern "system" fn(
     PCWSTR,
-    &ULONG,
-    &ULONG,
-    &WSL_DISTRIBUTION_FLAGS,
-    *const *const PSTR,
-    &ULONG,
+    *mut ULONG,
+    *mut ULONG,
+    *mut WSL_DISTRIBUTION_FLAGS,
+    *mut *mut PSTR,
+    *mut ULONG,
 ) -> HRESULT;
 type ConfigureDistributionFn = unsafe extern "system" fn(PCWSTR, ULONG, WSL_DISTRIBUTION_FLAGS)
     -> HRESULT;
-type LaunchInteractiveFn = unsafe extern "system" fn(PCWSTR, PCWSTR, bool, *const DWORD) -> HRESULT;
-type IsDistributionRegisteredFn = unsafe extern "system" fn(PCWSTR) -> bool;
-
-bitflags! {
-    #[derive(Default)]
-    pub struct DistroFlags: WSL_DISTRIBUTION_FLAGS {
-        // const NONE = 0;
-        const ENABLE_INTEROP = 1;
-        const APPEND_NT_PATH = 2;
-        const ENABLE_DRIVE_MOUNTING = 4;
-    }
-}
+type LaunchInteractiveFn = unsafe extern "system" fn(PCWSTR, PCWSTR, BOOL, *mut DWORD) -> HRESULT;
+type LaunchFn = unsafe extern "system" fn(PCWSTR, PCWSTR, BOOL, HANDLE, HANDLE, HANDLE, *mut HANDLE)
+    -> HRESULT;
+type IsDistributionRegisteredFn = unsafe extern "system" fn(PCWSTR) -> BOOL;
 
-impl fmt::Display for DistroFlags {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut v = vec![];
-        if self.is_empty() {
-            return write!(f, "NONE (0)");
-        }
-        if self.contains(DistroFlags::ENABLE_INTEROP) {
-            v.push("ENABLE_INTEROP (1)");
-        }
-        if self.contains(DistroFlags::APPEND_NT_PATH) {
-            v.push("APPEND_NT_PATH (2)");
-        }
-        if self.contains(DistroFlags::ENABLE_DRIVE_MOUNTING) {
-            v.push("ENABLE_DRIVE_MOUNTING (4)");
-        }
-        write!(f, "{}", &v[..].join(" | "))
-    }
+const INFINITE: DWORD = 0xFFFF_FFFF;
+const WAIT_OBJECT_0: DWORD = 0;
+const WAIT_TIMEOUT: DWORD = 0x0000_0102;
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn CloseHandle(object: HANDLE) -> BOOL;
+    fn GetExitCodeProcess(process: HANDLE, exit_code: *mut DWORD) -> BOOL;
+    fn TerminateProcess(process: HANDLE, exit_code: UINT) -> BOOL;
+    fn WaitForSingleObject(handle: HANDLE, milliseconds: DWORD) -> DWORD;
 }
 
-pub struct DistroConfiguration {
-    pub name: String,
-    pub version: u32,
-    pub default_uid: u32,
-    pub flags: DistroFlags,
-    pub default_environment_variables: Vec<String>,
+/// An optional `wslapi.dll` export that older Windows builds may lack.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Capability {
+    /// `WslLaunch`
+    Launch,
 }
 
-impl DistroConfiguration {
-    pub fn to_toml(&self) -> String {
-        let flags_bits = self.flags.bits();
-        format!(
-            "[{}]
-version = {}
-default_uid = {}
-# flags: {:#03b}
-# {}
-flags = {}
-default_environment_values = [{}]",
-            self.name,
-            self.version,
-            self.default_uid,
-            flags_bits,
-            self.flags,
-            flags_bits,
-            &self.default_environment_variables[..].join(", ")
-        )
-    }
+/// Functions that `Wslapi::new` resolves from `ole32.dll` and `wslapi.dll`.
+struct Functions {
+    co_task_mem_free: CoTaskMemFreeFn,
+    register_distribution: RegisterDistributionFn,
+    unregister_distribution: UnregisterDistributionFn,
+    get_distribution_configuration: GetDistributionConfigurationFn,
+    configure_distribution: ConfigureDistributionFn,
+    launch_interactive: LaunchInteractiveFn,
+    is_distribution_registered: IsDistributionRegisteredFn,
+    launch: Option<LaunchFn>,
 }
 
 pub struct Wslapi {
-    ole32: Library,
-    wslapi: Library,
+    // The libraries must outlive the functions resolved from them.
+    _ole32: Library,
+    _wslapi: Library,
+    functions: Functions,
+}
+
+/// Resolves `name` from `library`, remembering it in `missing` if it is not exported.
+fn resolve<T: Copy>(library: &Library, name: &'static str, missing: &mut Vec<&'static str>)
+    -> Option<T> {
+    let symbol = unsafe { library.get::<T>(name.as_bytes()) };
+    match symbol {
+        Ok(symbol) => Some(*symbol),
+        Err(_) => {
+            missing.push(name);
+            None
+        }
+    }
 }
 
 impl Wslapi {
+    /// Loads `wslapi.dll` and resolves all of its exports that `Wslapi` uses.
+    ///
+    /// This fails if any required export is missing. Optional exports can be probed with
+    /// `has_capability`.
     pub fn new() -> Result<Wslapi, Error> {
         let ole32 = match Library::new("ole32") {
             Ok(library) => library,
@@ -106,72 +107,98 @@ impl Wslapi {
             Ok(library) => library,
             Err(e) => return Err(format_err!("Wslapi::new {}", e)),
         };
+        let mut missing = vec![];
+        let co_task_mem_free = resolve(&ole32, "CoTaskMemFree", &mut missing);
+        let register_distribution = resolve(&wslapi, "WslRegisterDistribution", &mut missing);
+        let unregister_distribution = resolve(&wslapi, "WslUnregisterDistribution", &mut missing);
+        let get_distribution_configuration =
+            resolve(&wslapi, "WslGetDistributionConfiguration", &mut missing);
+        let configure_distribution = resolve(&wslapi, "WslConfigureDistribution", &mut missing);
+        let launch_interactive = resolve(&wslapi, "WslLaunchInteractive", &mut missing);
+        let is_distribution_registered =
+            resolve(&wslapi, "WslIsDistributionRegistered", &mut missing);
+        if !missing.is_empty() {
+            return Err(format_err!(
+                "Wslapi::new {} cannot be found. This Windows build may be too old",
+                missing.join(", ")
+            ));
+        }
+        let launch = resolve(&wslapi, "WslLaunch", &mut missing);
+        let functions = Functions {
+            co_task_mem_free: co_task_mem_free.unwrap(),
+            register_distribution: register_distribution.unwrap(),
+            unregister_distribution: unregister_distribution.unwrap(),
+            get_distribution_configuration: get_distribution_configuration.unwrap(),
+            configure_distribution: configure_distribution.unwrap(),
+            launch_interactive: launch_interactive.unwrap(),
+            is_distribution_registered: is_distribution_registered.unwrap(),
+            launch,
+        };
         Ok(Wslapi {
-            ole32: ole32,
-            wslapi: wslapi,
+            _ole32: ole32,
+            _wslapi: wslapi,
+            functions,
         })
     }
 
-    fn raw_co_task_mem_free(&self, v: LPVOID) -> LibloadingResult<()> {
-        let raw_fn: Symbol<CoTaskMemFreeFn> = unsafe { self.ole32.get(b"CoTaskMemFree\0")? };
-        unsafe { raw_fn(v) };
-        Ok(())
+    /// Returns whether `wslapi.dll` exports the optional function for `capability`.
+    pub fn has_capability(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::Launch => self.functions.launch.is_some(),
+        }
     }
 
-    fn raw_register_distribution(
-        &self,
-        distro_name: PCWSTR,
-        tar_gz_filename: PCWSTR,
-    ) -> LibloadingResult<HRESULT> {
-        let raw_fn: Symbol<RegisterDistributionFn> =
-            unsafe { self.wslapi.get(b"WslRegisterDistribution\0")? };
-        Ok(unsafe { raw_fn(distro_name, tar_gz_filename) })
+    fn raw_register_distribution(&self, distro_name: PCWSTR, tar_gz_filename: PCWSTR) -> HRESULT {
+        unsafe { (self.functions.register_distribution)(distro_name, tar_gz_filename) }
     }
 
-    fn raw_unregister_distribution(&self, distro_name: PCWSTR) -> LibloadingResult<HRESULT> {
-        let raw_fn: Symbol<UnregisterDistributionFn> =
-            unsafe { self.wslapi.get(b"WslUnregisterDistribution\0")? };
-        Ok(unsafe { raw_fn(distro_name) })
+    fn raw_unregister_distribution(&self, distro_name: PCWSTR) -> HRESULT {
+        unsafe { (self.functions.unregister_distribution)(distro_name) }
     }
 
     fn raw_get_distribution_configuration(
         &self,
         distro_name: PCWSTR,
-    ) -> LibloadingResult<
-        (
-            HRESULT,
-            ULONG,
-            ULONG,
-            WSL_DISTRIBUTION_FLAGS,
-            *const *const PSTR,
-            ULONG,
-        ),
-    > {
-        let version = 0;
-        let default_uid = 0;
-        let wsl_flags = 0;
-        let default_environment_variables_array = Box::into_raw(Box::new(0)) as *const *const PSTR;
-        let default_environment_variables_count = 0;
-        let raw_fn: Symbol<GetDistributionConfigurationFn> =
-            unsafe { self.wslapi.get(b"WslGetDistributionConfiguration\0")? };
+    ) -> (
+        HRESULT,
+        ULONG,
+        ULONG,
+        WSL_DISTRIBUTION_FLAGS,
+        Vec<CoTaskMemString<'_, Wslapi>>,
+    ) {
+        let mut version = 0;
+        let mut default_uid = 0;
+        let mut wsl_flags = 0;
+        let mut default_environment_variables_array = ptr::null_mut();
+        let mut default_environment_variables_count = 0;
         let hresult = unsafe {
-            raw_fn(
+            (self.functions.get_distribution_configuration)(
                 distro_name,
-                &version,
-                &default_uid,
-                &wsl_flags,
-                default_environment_variables_array,
-                &default_environment_variables_count,
+                &mut version,
+                &mut default_uid,
+                &mut wsl_flags,
+                &mut default_environment_variables_array,
+                &mut default_environment_variables_count,
             )
         };
-        Ok((
+        let default_environment_variables = if hresult == 0 {
+            unsafe {
+                CoTaskMemArray::from_raw(
+                    default_environment_variables_array,
+                    default_environment_variables_count as usize,
+                    self,
+                ).into_strings()
+            }
+        } else {
+            vec![]
+        };
+        (
             hresult,
             version,
             default_uid,
             wsl_flags,
-            default_environment_variables_array,
-            default_environment_variables_count,
-        ))
+            default_environment_variables,
+        )
     }
 
     fn raw_configure_distribution(
@@ -179,10 +206,8 @@ impl Wslapi {
         distro_name: PCWSTR,
         default_uid: ULONG,
         wsl_flags: WSL_DISTRIBUTION_FLAGS,
-    ) -> LibloadingResult<HRESULT> {
-        let raw_fn: Symbol<ConfigureDistributionFn> =
-            unsafe { self.wslapi.get(b"WslConfigureDistribution\0")? };
-        Ok(unsafe { raw_fn(distro_name, default_uid, wsl_flags) })
+    ) -> HRESULT {
+        unsafe { (self.functions.configure_distribution)(distro_name, default_uid, wsl_flags) }
     }
 
     fn raw_launch_interactive(
@@ -190,125 +215,222 @@ impl Wslapi {
         distro_name: PCWSTR,
         command: PCWSTR,
         use_current_working_directory: bool,
-    ) -> LibloadingResult<(HRESULT, DWORD)> {
-        let exit_code = 0;
-        let raw_fn: Symbol<LaunchInteractiveFn> =
-            unsafe { self.wslapi.get(b"WslLaunchInteractive\0")? };
+    ) -> (HRESULT, DWORD) {
+        let mut exit_code: DWORD = 0;
+        let hresult = unsafe {
+            (self.functions.launch_interactive)(
+                distro_name,
+                command,
+                use_current_working_directory as BOOL,
+                &mut exit_code,
+            )
+        };
+        (hresult, exit_code)
+    }
+
+    fn raw_launch(
+        &self,
+        distro_name: PCWSTR,
+        command: PCWSTR,
+        use_current_working_directory: bool,
+        std_in: HANDLE,
+        std_out: HANDLE,
+        std_err: HANDLE,
+    ) -> Result<(HRESULT, HANDLE), Error> {
+        let raw_fn = match self.functions.launch {
+            Some(raw_fn) => raw_fn,
+            None => {
+                return Err(format_err!(
+                    "Wslapi::spawn WslLaunch cannot be found. This Windows build may be too old"
+                ))
+            }
+        };
+        let mut process = ptr::null_mut();
         let hresult = unsafe {
             raw_fn(
                 distro_name,
                 command,
-                use_current_working_directory,
-                &exit_code,
+                use_current_working_directory as BOOL,
+                std_in,
+                std_out,
+                std_err,
+                &mut process,
             )
         };
-        Ok((hresult, exit_code))
+        Ok((hresult, process))
     }
 
-    fn raw_is_distribution_registered(&self, distro_name: PCWSTR) -> LibloadingResult<bool> {
-        let raw_fn: Symbol<IsDistributionRegisteredFn> =
-            unsafe { self.wslapi.get(b"WslIsDistributionRegistered\0")? };
-        Ok(unsafe { raw_fn(distro_name) })
+    fn raw_is_distribution_registered(&self, distro_name: PCWSTR) -> bool {
+        unsafe { (self.functions.is_distribution_registered)(distro_name) != 0 }
     }
+}
+
+/// A process launched by `Wslapi::spawn`.
+pub struct WslapiProcess {
+    handle: HANDLE,
+    exit_code: Option<u32>,
+}
+
+impl WslapiProcess {
+    fn wait_for(&mut self, milliseconds: DWORD) -> Result<Option<u32>, Error> {
+        match unsafe { WaitForSingleObject(self.handle, milliseconds) } {
+            WAIT_OBJECT_0 => {
+                let mut exit_code = 0;
+                if unsafe { GetExitCodeProcess(self.handle, &mut exit_code) } == 0 {
+                    return Err(io::Error::last_os_error().into());
+                }
+                self.exit_code = Some(exit_code);
+                Ok(Some(exit_code))
+            }
+            WAIT_TIMEOUT => Ok(None),
+            _ => Err(io::Error::last_os_error().into()),
+        }
+    }
+}
+
+impl WslProcess for WslapiProcess {
+    fn wait(&mut self) -> Result<u32, Error> {
+        match self.wait_for(INFINITE)? {
+            Some(exit_code) => Ok(exit_code),
+            None => Err(format_err!("WslapiProcess::wait timed out")),
+        }
+    }
+
+    fn try_wait(&mut self) -> Result<Option<u32>, Error> {
+        self.wait_for(0)
+    }
+
+    fn exit_code(&self) -> Option<u32> {
+        self.exit_code
+    }
+
+    fn kill(&mut self) -> Result<(), Error> {
+        if unsafe { TerminateProcess(self.handle, 1) } == 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(())
+    }
+}
 
-    pub fn register_distro(&self, distro_name: &str, tar_gz_filename: &str) -> Result<(), Error> {
+impl Drop for WslapiProcess {
+    fn drop(&mut self) {
+        unsafe { CloseHandle(self.handle) };
+    }
+}
+
+impl CoTaskMemFree for Wslapi {
+    unsafe fn co_task_mem_free(&self, p: LPVOID) {
+        (self.functions.co_task_mem_free)(p);
+    }
+}
+
+impl WslBackend for Wslapi {
+    type Process = WslapiProcess;
+
+    fn register_distro(&self, distro_name: &str, tar_gz_filename: &str) -> Result<(), Error> {
         match self.raw_register_distribution(
             wide_chars::to_vec_u16(distro_name).as_mut_ptr(),
             wide_chars::to_vec_u16(tar_gz_filename).as_mut_ptr(),
         ) {
-            Ok(0) => Ok(()),
-            Ok(hresult) => Err(format_err!("HRESULT == {:#08X}", hresult)),
-            Err(e) => Err(format_err!("Wslapi::register_distro {}", e)),
+            0 => Ok(()),
+            hresult => Err(WslError::from_hresult(hresult).into()),
         }
     }
 
-    pub fn unregister_distro(&self, distro_name: &str) -> Result<(), Error> {
+    fn unregister_distro(&self, distro_name: &str) -> Result<(), Error> {
         match self.raw_unregister_distribution(wide_chars::to_vec_u16(distro_name).as_mut_ptr()) {
-            Ok(0) => Ok(()),
-            Ok(hresult) => Err(format_err!("HRESULT == {:#08X}", hresult)),
-            Err(e) => Err(format_err!("Wslapi::unregister_distro {}", e)),
+            0 => Ok(()),
+            hresult => Err(WslError::from_hresult(hresult).into()),
         }
     }
 
-    pub fn get_distro_configuration(
+    fn get_distro_configuration(
         &self,
         distro_name: &str,
     ) -> Result<DistroConfiguration, Error> {
         match self.raw_get_distribution_configuration(
             wide_chars::to_vec_u16(distro_name).as_mut_ptr(),
         ) {
-            Ok((
-                0,
-                version,
-                default_uid,
-                wsl_flags,
-                default_environment_variables_array,
-                default_environment_variables_count,
-            )) => {
-                let original_array = unsafe { *default_environment_variables_array } as *const PSTR;
-                let p_vec = (0..default_environment_variables_count)
-                    .map(|i| unsafe { *original_array.offset(i as isize) })
-                    .collect::<Vec<PSTR>>();
-                let string_vec = p_vec
-                    .iter()
-                    .map(|p| {
-                        format!(
-                            "\"{}\"",
-                            unsafe { CStr::from_ptr(*p as *const c_char) }
-                                .to_str()
-                                .unwrap()
-                        )
-                    })
-                    .collect::<Vec<String>>();
-                for p in p_vec {
-                    self.raw_co_task_mem_free(p as LPVOID).unwrap();
-                }
-                self.raw_co_task_mem_free(original_array as LPVOID).unwrap();
+            (0, version, default_uid, wsl_flags, default_environment_variables) => {
                 Ok(DistroConfiguration {
                     name: distro_name.to_string(),
-                    version: version,
-                    default_uid: default_uid,
-                    flags: DistroFlags::from_bits(wsl_flags as WSL_DISTRIBUTION_FLAGS).unwrap(),
-                    default_environment_variables: string_vec,
+                    version,
+                    default_uid,
+                    flags: DistroFlags::from_raw(wsl_flags as WSL_DISTRIBUTION_FLAGS),
+                    default_environment_variables: default_environment_variables
+                        .iter()
+                        .map(|s| s.to_string_lossy().into_owned())
+                        .collect(),
                 })
             }
-            Ok((hresult, ..)) => Err(format_err!("HRESULT == {:#08X}", hresult)),
-            Err(e) => Err(format_err!("Wslapi::get_distro_configuration {}", e)),
+            (hresult, ..) => Err(WslError::from_hresult(hresult).into()),
         }
     }
 
-    pub fn configure_distro(
+    fn configure_distro(
         &self,
         distro_configuration: &DistroConfiguration,
     ) -> Result<(), Error> {
         match self.raw_configure_distribution(
             wide_chars::to_vec_u16(&distro_configuration.name[..]).as_mut_ptr(),
             distro_configuration.default_uid,
-            distro_configuration.flags.bits,
+            distro_configuration.flags.bits(),
         ) {
-            Ok(0) => Ok(()),
-            Ok(hresult) => Err(format_err!("HRESULT == {:#08X}", hresult)),
-            Err(e) => Err(format_err!("Wslapi::configure_distro {}", e)),
+            0 => Ok(()),
+            hresult => Err(WslError::from_hresult(hresult).into()),
         }
     }
 
-    pub fn launch(&self, distro_name: &str, command: &str, use_cwd: bool) -> Result<DWORD, Error> {
+    fn launch(&self, distro_name: &str, command: &str, use_cwd: bool) -> Result<DWORD, Error> {
         match self.raw_launch_interactive(
             wide_chars::to_vec_u16(distro_name).as_mut_ptr(),
             wide_chars::to_vec_u16(command).as_mut_ptr(),
             use_cwd,
         ) {
-            Ok((0, exit_code)) => Ok(exit_code),
-            Ok((hresult, _)) => Err(format_err!("HRESULT == {:#08X}", hresult)),
-            Err(e) => Err(format_err!("Wslapi::launch {}", e)),
+            (0, exit_code) => Ok(exit_code),
+            (hresult, _) => Err(WslError::from_hresult(hresult).into()),
         }
     }
 
-    pub fn is_distribution_registered(&self, distro_name: &str) -> Result<bool, Error> {
-        match self.raw_is_distribution_registered(wide_chars::to_vec_u16(distro_name).as_mut_ptr())
-        {
-            Ok(result) => Ok(result),
-            Err(e) => Err(format_err!("Wslapi::is_distribution_registered {}", e)),
+    fn spawn(
+        &self,
+        distro_name: &str,
+        command: &str,
+        use_cwd: bool,
+        stdio: LaunchStdio,
+    ) -> Result<WslapiProcess, Error> {
+        // WslLaunch duplicates the handles, so `stdio` can be closed once it returns.
+        let std_in = match stdio.stdin {
+            Some(ref file) => file.as_raw_handle(),
+            None => io::stdin().as_raw_handle(),
+        };
+        let std_out = match stdio.stdout {
+            Some(ref file) => file.as_raw_handle(),
+            None => io::stdout().as_raw_handle(),
+        };
+        let std_err = match stdio.stderr {
+            Some(ref file) => file.as_raw_handle(),
+            None => io::stderr().as_raw_handle(),
+        };
+        match self.raw_launch(
+            wide_chars::to_vec_u16(distro_name).as_mut_ptr(),
+            wide_chars::to_vec_u16(command).as_mut_ptr(),
+            use_cwd,
+            std_in as HANDLE,
+            std_out as HANDLE,
+            std_err as HANDLE,
+        )? {
+            (0, handle) => Ok(WslapiProcess {
+                handle,
+                exit_code: None,
+            }),
+            (hresult, _) => Err(WslError::from_hresult(hresult).into()),
         }
     }
+
+    fn is_distribution_registered(&self, distro_name: &str) -> Result<bool, Error> {
+        Ok(self.raw_is_distribution_registered(
+            wide_chars::to_vec_u16(distro_name).as_mut_ptr(),
+        ))
+    }
 }
@@ -0,0 +1,177 @@
+extern crate flate2;
+extern crate tar;
+extern crate yowsl;
+
+mod common;
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use flate2::read::GzDecoder;
+use tar::EntryType;
+use common::{temp_dir, write_tar, Item};
+use yowsl::{Additions, FileAddition, SourceFormat, UserAddition};
+
+const PASSWD: &str = "root:x:0:0:root:/root:/bin/bash\nsync:x:4:65534:sync:/bin:/bin/sync\n";
+const GROUP: &str = "root:x:0:\nadm:x:4:syslog\nsudo:x:27:\n";
+const SHADOW: &str = "root:*:19000:0:99999:7:::\nsync:*:19000:0:99999:7:::\n";
+
+/// Writes a `.tar` archive of a small rootfs to `path`.
+fn write_rootfs(path: &Path) {
+    write_tar(
+        path,
+        &[
+            Item::FileMode("./etc/passwd", PASSWD, 0o644),
+            Item::FileMode("./etc/group", GROUP, 0o644),
+            Item::FileMode("./etc/shadow", SHADOW, 0o640),
+            Item::FileMode("./etc/wsl.conf", "[automount]\n", 0o644),
+            Item::File("./bin/bash", "#!"),
+        ],
+    );
+}
+
+/// Reads a `.tar.gz` archive into its paths with their modes, owners and contents.
+fn read_tar_gz(path: &Path) -> BTreeMap<String, (u32, u64, u64, String)> {
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(path).unwrap()));
+    let mut files = BTreeMap::new();
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let path = entry.path().unwrap().to_string_lossy().into_owned();
+        let header = entry.header().clone();
+        let mut s = String::new();
+        if header.entry_type() == EntryType::Directory {
+            s = "/".to_string();
+        } else {
+            entry.read_to_string(&mut s).unwrap();
+        }
+        let (uid, gid) = (header.uid().unwrap(), header.gid().unwrap());
+        assert!(files.insert(path, (header.mode().unwrap(), uid, gid, s)).is_none());
+    }
+    files
+}
+
+#[test]
+fn file_addition_parses_host_paths_with_colons() {
+    let file = "C:\\wsl.conf:/etc/wsl.conf".parse::<FileAddition>().unwrap();
+    assert_eq!(file.host_path, PathBuf::from("C:\\wsl.conf"));
+    assert_eq!(file.guest_path, "etc/wsl.conf");
+    assert_eq!((file.mode, file.uid, file.gid), (0o644, 0, 0));
+    let file = "C:/certs/ca.crt:/usr/local/share/ca-certificates/ca.crt:600:1000:1000"
+        .parse::<FileAddition>()
+        .unwrap();
+    assert_eq!(file.host_path, PathBuf::from("C:/certs/ca.crt"));
+    assert_eq!(file.guest_path, "usr/local/share/ca-certificates/ca.crt");
+    assert_eq!((file.mode, file.uid, file.gid), (0o600, 1000, 1000));
+    let file = "/tmp/motd:/etc/motd:755".parse::<FileAddition>().unwrap();
+    assert_eq!(file.host_path, PathBuf::from("/tmp/motd"));
+    assert_eq!(file.mode, 0o755);
+    for s in &["motd", "motd:etc/motd", ":/etc/motd", "motd:/", "motd:/../etc", "motd:/x:9"] {
+        assert!(s.parse::<FileAddition>().is_err(), "{}", s);
+    }
+}
+
+#[test]
+fn user_addition_parses_groups() {
+    assert_eq!(
+        "alice:1000:sudo,adm".parse::<UserAddition>().unwrap(),
+        UserAddition {
+            name: "alice".to_string(),
+            uid: 1000,
+            groups: vec!["sudo".to_string(), "adm".to_string()],
+        }
+    );
+    assert!("bob:1001".parse::<UserAddition>().unwrap().groups.is_empty());
+    for s in &["alice", "Alice:1000", "alice:x", "alice:1000:sudo:adm", "1alice:1000"] {
+        assert!(s.parse::<UserAddition>().is_err(), "{}", s);
+    }
+}
+
+#[test]
+fn add_to_tar_gz_adds_files_and_users() {
+    let dir = temp_dir("additions");
+    let src = dir.join("rootfs.tar");
+    write_rootfs(&src);
+    let wsl_conf = dir.join("wsl.conf");
+    fs::write(&wsl_conf, "[user]\ndefault=alice\n").unwrap();
+    let additions = Additions {
+        files: vec![
+            format!("{}:/etc/wsl.conf", wsl_conf.display()).parse().unwrap(),
+            format!("{}:/opt/app/app.conf:600:1000:1000", wsl_conf.display()).parse().unwrap(),
+        ],
+        users: vec!["alice:1000:sudo,adm".parse().unwrap()],
+    };
+    let dest = dir.join("rootfs.tar.gz");
+    yowsl::add_to_tar_gz(&src, SourceFormat::Tar, &additions, &dest).unwrap();
+    let files = read_tar_gz(&dest);
+    assert_eq!(
+        files["etc/passwd"].3,
+        format!("{}alice:x:1000:1000::/home/alice:/bin/bash\n", PASSWD)
+    );
+    assert_eq!(
+        files["etc/group"].3,
+        "root:x:0:\nadm:x:4:syslog,alice\nsudo:x:27:alice\nalice:x:1000:\n"
+    );
+    let shadow = &files["etc/shadow"];
+    assert_eq!(shadow.0, 0o640);
+    assert!(shadow.3.starts_with(SHADOW));
+    assert!(shadow.3.ends_with(":0:99999:7:::\n"));
+    assert!(shadow.3[SHADOW.len()..].starts_with("alice:!:"));
+    assert_eq!(files["home/alice"], (0o750, 1000, 1000, "/".to_string()));
+    assert_eq!(files["etc/wsl.conf"], (0o644, 0, 0, "[user]\ndefault=alice\n".to_string()));
+    assert_eq!(files["opt"], (0o755, 0, 0, "/".to_string()));
+    assert_eq!(files["opt/app"], (0o755, 0, 0, "/".to_string()));
+    assert_eq!(files["opt/app/app.conf"].0, 0o600);
+    assert_eq!(files["opt/app/app.conf"].1, 1000);
+    assert!(!files.contains_key("etc/gshadow"));
+    for user in &["root:1000", "carol:0", "carol:1000:wheel"] {
+        let additions = Additions {
+            files: vec![],
+            users: vec![user.parse().unwrap()],
+        };
+        assert!(
+            yowsl::add_to_tar_gz(&src, SourceFormat::Tar, &additions, &dest).is_err(),
+            "{}",
+            user
+        );
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn add_to_tar_gz_adds_users_to_added_account_files() {
+    let dir = temp_dir("additions-accounts");
+    let src = dir.join("rootfs.tar");
+    write_rootfs(&src);
+    let passwd = dir.join("passwd");
+    fs::write(&passwd, "root:x:0:0:root:/root:/bin/sh\n").unwrap();
+    let gshadow = dir.join("gshadow");
+    fs::write(&gshadow, "root:*::\nsudo:*::\n").unwrap();
+    let additions = Additions {
+        files: vec![
+            format!("{}:/etc/passwd:600", passwd.display()).parse().unwrap(),
+            format!("{}:/etc/gshadow:640", gshadow.display()).parse().unwrap(),
+        ],
+        users: vec!["alice:1000:sudo".parse().unwrap()],
+    };
+    let dest = dir.join("rootfs.tar.gz");
+    yowsl::add_to_tar_gz(&src, SourceFormat::Tar, &additions, &dest).unwrap();
+    let files = read_tar_gz(&dest);
+    assert_eq!(
+        files["etc/passwd"],
+        (
+            0o600,
+            0,
+            0,
+            "root:x:0:0:root:/root:/bin/sh\nalice:x:1000:1000::/home/alice:/bin/bash\n"
+                .to_string()
+        )
+    );
+    assert_eq!(files["etc/gshadow"].0, 0o640);
+    assert_eq!(files["etc/gshadow"].3, "root:*::\nsudo:*::alice\nalice:!::\n");
+    assert_eq!(
+        files["etc/group"].3,
+        "root:x:0:\nadm:x:4:syslog\nsudo:x:27:alice\nalice:x:1000:\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
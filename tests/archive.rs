@@ -0,0 +1,110 @@
+extern crate bzip2;
+extern crate flate2;
+extern crate lzma_rs;
+extern crate tar;
+extern crate yowsl;
+
+mod common;
+
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use bzip2::write::BzEncoder;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use common::temp_dir;
+use yowsl::SourceFormat;
+
+/// Returns a `.tar` archive that contains `etc/hostname`.
+fn tar() -> Vec<u8> {
+    let mut builder = tar::Builder::new(vec![]);
+    let mut header = tar::Header::new_gnu();
+    header.set_size(7);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, "etc/hostname", &b"ubuntu\n"[..])
+        .unwrap();
+    builder.into_inner().unwrap()
+}
+
+/// Wraps `data` in a zstd frame of one uncompressed block, as there is no zstd encoder at hand.
+fn zstd_raw_frame(data: &[u8]) -> Vec<u8> {
+    assert!(data.len() >= 256 && data.len() < 65_536 + 256);
+    // Single segment with a 2-byte frame content size, which is stored minus 256
+    let mut frame = vec![0x28, 0xB5, 0x2F, 0xFD, 0x60];
+    frame.extend(&((data.len() - 256) as u16).to_le_bytes());
+    // The last block, raw, with its size
+    let block_header = (data.len() as u32) << 3 | 1;
+    frame.extend(&block_header.to_le_bytes()[..3]);
+    frame.extend(data);
+    frame
+}
+
+/// Writes `tar()` into `dir` in every format and returns the paths with their formats.
+fn sources(dir: &Path) -> Vec<(PathBuf, SourceFormat)> {
+    let tar = tar();
+    let mut gz = GzEncoder::new(vec![], flate2::Compression::default());
+    gz.write_all(&tar).unwrap();
+    let mut xz = vec![];
+    lzma_rs::xz_compress(&mut &tar[..], &mut xz).unwrap();
+    let mut bz2 = BzEncoder::new(vec![], bzip2::Compression::default());
+    bz2.write_all(&tar).unwrap();
+    let files = vec![
+        ("rootfs.tar", tar.clone(), SourceFormat::Tar),
+        ("rootfs.tar.gz", gz.finish().unwrap(), SourceFormat::TarGz),
+        ("rootfs.tar.xz", xz, SourceFormat::TarXz),
+        ("rootfs.tar.zst", zstd_raw_frame(&tar), SourceFormat::TarZst),
+        ("rootfs.tar.bz2", bz2.finish().unwrap(), SourceFormat::TarBz2),
+    ];
+    let mut sources = files
+        .into_iter()
+        .map(|(name, data, format)| {
+            let path = dir.join(name);
+            fs::write(&path, data).unwrap();
+            (path, format)
+        })
+        .collect::<Vec<_>>();
+    let rootfs = dir.join("rootfs");
+    fs::create_dir_all(rootfs.join("etc")).unwrap();
+    fs::write(rootfs.join("etc/hostname"), "ubuntu\n").unwrap();
+    sources.push((rootfs, SourceFormat::Directory));
+    sources
+}
+
+#[test]
+fn detect_reads_magic_bytes() {
+    let dir = temp_dir("detect");
+    for (path, format) in sources(&dir) {
+        assert_eq!(SourceFormat::detect(&path).unwrap(), format, "{}", path.display());
+    }
+    let junk = dir.join("junk");
+    fs::write(&junk, "junk").unwrap();
+    assert!(SourceFormat::detect(&junk).is_err());
+    assert!(SourceFormat::detect(dir.join("missing")).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn write_tar_gz_reencodes_every_format() {
+    let dir = temp_dir("write-tar-gz");
+    let out = temp_dir("write-tar-gz-out");
+    for (path, format) in sources(&dir) {
+        let tar_gz = out.join("rootfs.tar.gz");
+        yowsl::write_tar_gz(&path, format, &tar_gz).unwrap();
+        let mut archive = tar::Archive::new(GzDecoder::new(File::open(&tar_gz).unwrap()));
+        let mut hostname = None;
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            if entry.path().unwrap().ends_with("etc/hostname") {
+                let mut s = String::new();
+                entry.read_to_string(&mut s).unwrap();
+                hostname = Some(s);
+            }
+        }
+        assert_eq!(hostname, Some("ubuntu\n".to_string()), "{}", path.display());
+    }
+    assert!(yowsl::write_tar_gz(&dir, SourceFormat::Directory, dir.join("x.tar.gz")).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+    fs::remove_dir_all(&out).unwrap();
+}
@@ -0,0 +1,90 @@
+extern crate yowsl;
+
+use std::cell::RefCell;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+use yowsl::{CoTaskMemArray, CoTaskMemFree, CoTaskMemString};
+
+extern "C" {
+    fn malloc(size: usize) -> *mut c_void;
+    fn free(p: *mut c_void);
+}
+
+/// Stands in for `CoTaskMemAlloc` and `CoTaskMemFree` with `malloc` and `free`.
+#[derive(Default)]
+struct Allocator {
+    freed: RefCell<Vec<usize>>,
+}
+
+impl Allocator {
+    fn alloc_string(&self, s: &str) -> *mut c_char {
+        unsafe {
+            let p = malloc(s.len() + 1) as *mut u8;
+            ptr::copy_nonoverlapping(s.as_ptr(), p, s.len());
+            *p.add(s.len()) = 0;
+            p as *mut c_char
+        }
+    }
+
+    fn alloc_array<T: Copy>(&self, elements: &[T]) -> *mut T {
+        unsafe {
+            let p = malloc(std::mem::size_of_val(elements)) as *mut T;
+            ptr::copy_nonoverlapping(elements.as_ptr(), p, elements.len());
+            p
+        }
+    }
+}
+
+impl CoTaskMemFree for Allocator {
+    unsafe fn co_task_mem_free(&self, p: *mut c_void) {
+        assert!(!self.freed.borrow().contains(&(p as usize)));
+        self.freed.borrow_mut().push(p as usize);
+        free(p);
+    }
+}
+
+#[test]
+fn string_is_freed_on_drop() {
+    let allocator = Allocator::default();
+    let p = allocator.alloc_string("HOSTTYPE=x86_64");
+    {
+        let s = unsafe { CoTaskMemString::from_raw(p, &allocator) };
+        assert_eq!(s.to_string_lossy(), "HOSTTYPE=x86_64");
+        assert!(allocator.freed.borrow().is_empty());
+    }
+    assert_eq!(*allocator.freed.borrow(), vec![p as usize]);
+}
+
+#[test]
+fn null_string_is_empty_and_not_freed() {
+    let allocator = Allocator::default();
+    {
+        let s = unsafe { CoTaskMemString::from_raw(ptr::null_mut(), &allocator) };
+        assert_eq!(s.to_bytes(), b"");
+    }
+    assert!(allocator.freed.borrow().is_empty());
+}
+
+#[test]
+fn array_is_freed_on_drop() {
+    let allocator = Allocator::default();
+    let p = allocator.alloc_array(&[1u32, 2, 3]);
+    {
+        let array = unsafe { CoTaskMemArray::from_raw(p, 3, &allocator) };
+        assert_eq!(array.as_slice(), &[1, 2, 3]);
+    }
+    assert_eq!(*allocator.freed.borrow(), vec![p as usize]);
+}
+
+#[test]
+fn strings_outlive_their_array() {
+    let allocator = Allocator::default();
+    let strings = [allocator.alloc_string("LANG=C"), allocator.alloc_string("TERM=xterm")];
+    let p = allocator.alloc_array(&strings);
+    let strings = unsafe { CoTaskMemArray::from_raw(p, 2, &allocator).into_strings() };
+    assert_eq!(*allocator.freed.borrow(), vec![p as usize]);
+    assert_eq!(strings[0].to_string_lossy(), "LANG=C");
+    assert_eq!(strings[1].to_string_lossy(), "TERM=xterm");
+    drop(strings);
+    assert_eq!(allocator.freed.borrow().len(), 3);
+}
@@ -0,0 +1,91 @@
+// Helpers shared by the integration tests. Each test crate uses only some of them.
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process;
+use flate2::read::GzDecoder;
+use tar::{EntryType, Header};
+
+/// Returns a fresh temporary folder for the test `name`.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("yowsl-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).unwrap();
+    path
+}
+
+/// An entry of an archive that `tar` writes.
+pub enum Item {
+    Dir(&'static str),
+    File(&'static str, &'static str),
+    /// A file with a mode other than 0o755
+    FileMode(&'static str, &'static str, u32),
+    Symlink(&'static str, &'static str),
+    HardLink(&'static str, &'static str),
+    Char(&'static str),
+}
+
+/// Returns a `.tar` archive of `items`, writing their paths as they are even if they are
+/// absolute or have `..`.
+pub fn tar(items: &[Item]) -> Vec<u8> {
+    let mut builder = tar::Builder::new(vec![]);
+    for item in items {
+        let mut header = Header::new_gnu();
+        header.set_mode(0o755);
+        header.set_uid(0);
+        header.set_gid(0);
+        let (path, entry_type, link, data) = match *item {
+            Item::Dir(path) => (path, EntryType::Directory, None, ""),
+            Item::File(path, data) => (path, EntryType::Regular, None, data),
+            Item::FileMode(path, data, mode) => {
+                header.set_mode(mode);
+                (path, EntryType::Regular, None, data)
+            }
+            Item::Symlink(path, target) => (path, EntryType::Symlink, Some(target), ""),
+            Item::HardLink(path, target) => (path, EntryType::Link, Some(target), ""),
+            Item::Char(path) => (path, EntryType::Char, None, ""),
+        };
+        header.set_entry_type(entry_type);
+        header.set_size(data.len() as u64);
+        header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+        if let Some(target) = link {
+            header.as_old_mut().linkname[..target.len()].copy_from_slice(target.as_bytes());
+        }
+        header.set_cksum();
+        builder.append(&header, data.as_bytes()).unwrap();
+    }
+    builder.into_inner().unwrap()
+}
+
+/// Writes `tar(items)` to `path`.
+pub fn write_tar(path: &Path, items: &[Item]) {
+    fs::write(path, tar(items)).unwrap();
+}
+
+/// Reads a `.tar.gz` archive into its paths with their contents, `/` for folders, `=> target`
+/// for hard links and `-> target` for symbolic links.
+pub fn read_tar_gz(path: &Path) -> BTreeMap<String, String> {
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(path).unwrap()));
+    let mut files = BTreeMap::new();
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let path = entry.path().unwrap().to_string_lossy().into_owned();
+        let mut s = String::new();
+        match entry.header().entry_type() {
+            EntryType::Link => s = format!("=> {}", entry.link_name().unwrap().unwrap().display()),
+            EntryType::Symlink => {
+                s = format!("-> {}", entry.link_name().unwrap().unwrap().display())
+            }
+            EntryType::Directory => s = "/".to_string(),
+            _ => {
+                entry.read_to_string(&mut s).unwrap();
+            }
+        }
+        files.insert(path, s);
+    }
+    files
+}
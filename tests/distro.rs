@@ -0,0 +1,114 @@
+extern crate yowsl;
+
+use yowsl::{DistroConfiguration, DistroFlags};
+
+fn distro_configuration(name: &str) -> DistroConfiguration {
+    DistroConfiguration {
+        name: name.to_string(),
+        version: 1,
+        default_uid: 1000,
+        flags: DistroFlags::ENABLE_INTEROP | DistroFlags::ENABLE_DRIVE_MOUNTING,
+        default_environment_variables: DistroConfiguration::default_environment_variables(),
+    }
+}
+
+#[test]
+fn toml_round_trips() {
+    let mut distro_configuration = distro_configuration("Ubuntu [18.04] \"LTS\"");
+    distro_configuration
+        .default_environment_variables
+        .push("PS1=\"\\u@\\h\" # 'quoted'\n".to_string());
+    let toml = distro_configuration.to_toml();
+    assert!(toml.starts_with("schema_version = 1\n"));
+    assert_eq!(
+        DistroConfiguration::from_toml(&toml).unwrap(),
+        distro_configuration
+    );
+    assert_eq!(
+        toml.parse::<DistroConfiguration>().unwrap(),
+        distro_configuration
+    );
+}
+
+#[test]
+fn toml_round_trips_several_distros() {
+    let distro_configurations = vec![
+        distro_configuration("Debian"),
+        distro_configuration("Ubuntu"),
+    ];
+    let toml = DistroConfiguration::to_toml_all(&distro_configurations);
+    assert_eq!(
+        DistroConfiguration::from_toml_all(&toml).unwrap(),
+        distro_configurations
+    );
+    assert!(DistroConfiguration::from_toml(&toml).is_err());
+}
+
+#[test]
+fn from_toml_rejects_an_unknown_schema_version() {
+    let toml = distro_configuration("Ubuntu")
+        .to_toml()
+        .replace("schema_version = 1", "schema_version = 2");
+    assert!(DistroConfiguration::from_toml(&toml).is_err());
+    let toml = distro_configuration("Ubuntu")
+        .to_toml()
+        .replace("schema_version = 1", "");
+    assert!(DistroConfiguration::from_toml(&toml).is_err());
+}
+
+#[test]
+fn from_toml_rejects_unknown_keys() {
+    let toml = distro_configuration("Ubuntu")
+        .to_toml()
+        .replace("default_environment_variables", "default_environment_values");
+    assert!(DistroConfiguration::from_toml(&toml).is_err());
+}
+
+#[test]
+fn flags_parse_from_names() {
+    let all = DistroFlags::all();
+    assert_eq!(
+        "ENABLE_INTEROP|APPEND_NT_PATH|ENABLE_DRIVE_MOUNTING"
+            .parse::<DistroFlags>()
+            .unwrap(),
+        all
+    );
+    assert_eq!("interop, NtPath,mount".parse::<DistroFlags>().unwrap(), all);
+    assert_eq!(
+        "none".parse::<DistroFlags>().unwrap(),
+        DistroFlags::empty()
+    );
+    assert!("interop|bogus".parse::<DistroFlags>().is_err());
+    assert!("".parse::<DistroFlags>().is_err());
+}
+
+#[test]
+fn flags_parse_from_numbers() {
+    assert_eq!(
+        "5".parse::<DistroFlags>().unwrap(),
+        DistroFlags::ENABLE_INTEROP | DistroFlags::ENABLE_DRIVE_MOUNTING
+    );
+    assert_eq!("0x7".parse::<DistroFlags>().unwrap(), DistroFlags::all());
+    assert_eq!("0".parse::<DistroFlags>().unwrap(), DistroFlags::empty());
+    assert_eq!(
+        "0xB".parse::<DistroFlags>().unwrap(),
+        DistroFlags::from_raw(0xB)
+    );
+    assert!("-1".parse::<DistroFlags>().is_err());
+}
+
+#[test]
+fn unknown_flags_are_kept() {
+    let mut flags = DistroFlags::from_raw(0x8 | 0x1);
+    assert_eq!(flags.unknown_bits(), 0x8);
+    assert_eq!(flags.to_string(), "ENABLE_INTEROP (1) | UNKNOWN (0x8)");
+    flags.insert(DistroFlags::APPEND_NT_PATH);
+    flags.remove(DistroFlags::ENABLE_INTEROP);
+    assert_eq!(flags.bits(), 0x8 | 0x2);
+    let mut distro_configuration = distro_configuration("Ubuntu");
+    distro_configuration.flags = flags;
+    assert_eq!(
+        DistroConfiguration::from_toml(&distro_configuration.to_toml()).unwrap(),
+        distro_configuration
+    );
+}
@@ -0,0 +1,105 @@
+extern crate yowsl;
+
+use yowsl::{DistroConfiguration, DistroFlags, Format};
+
+fn distro_configurations() -> Vec<DistroConfiguration> {
+    let mut tricky_variables = DistroConfiguration::default_environment_variables();
+    tricky_variables.extend(vec![
+        "PS1='\\u@\\h' \"$ \"".to_string(),
+        "MULTI=line 1\nline 2\ttabbed".to_string(),
+        "GREETING=こんにちは # not a comment".to_string(),
+        "EMPTY=".to_string(),
+    ]);
+    vec![
+        DistroConfiguration {
+            name: "Debian".to_string(),
+            version: 1,
+            default_uid: 0,
+            flags: DistroFlags::empty(),
+            default_environment_variables: vec![],
+        },
+        DistroConfiguration {
+            name: "Ubuntu 18.04 [it's \"LTS\"]".to_string(),
+            version: 2,
+            default_uid: 1000,
+            flags: DistroFlags::from_raw(0x8) | DistroFlags::ENABLE_INTEROP,
+            default_environment_variables: tricky_variables,
+        },
+    ]
+}
+
+fn assert_round_trips(format: Format, distro_configurations: &[DistroConfiguration]) {
+    let s = format.serialize(distro_configurations).unwrap();
+    assert_eq!(format.deserialize(&s).unwrap(), distro_configurations);
+    let s = format.serialize(&[]).unwrap();
+    assert_eq!(format.deserialize(&s).unwrap(), vec![]);
+    let default_users = vec![(distro_configurations[0].name.clone(), "root".to_string())]
+        .into_iter()
+        .collect();
+    let s = format
+        .serialize_with_default_users(distro_configurations, &default_users)
+        .unwrap();
+    assert!(s.contains("root"), "{}", s);
+    assert_eq!(format.deserialize(&s).unwrap(), distro_configurations);
+}
+
+#[test]
+fn toml_round_trips() {
+    assert_round_trips(Format::Toml, &distro_configurations());
+}
+
+#[test]
+fn json_round_trips() {
+    assert_round_trips(Format::Json, &distro_configurations());
+}
+
+#[test]
+fn yaml_round_trips() {
+    assert_round_trips(Format::Yaml, &distro_configurations());
+}
+
+#[test]
+fn env_round_trips() {
+    assert_round_trips(Format::Env, &distro_configurations()[1..]);
+}
+
+#[test]
+fn env_is_shell_assignments() {
+    let s = Format::Env.serialize(&distro_configurations()[1..]).unwrap();
+    assert!(s.starts_with("YOWSL_SCHEMA_VERSION=1\n"));
+    assert!(s.contains("\nYOWSL_NAME='Ubuntu 18.04 [it'\\''s \"LTS\"]'\n"));
+    assert!(s.contains("\nYOWSL_DEFAULT_UID=1000\n"));
+    assert!(s.contains("\nYOWSL_FLAGS=9\n"));
+    assert!(s.contains("\nYOWSL_DEFAULT_ENVIRONMENT_VARIABLE_0=HOSTTYPE=x86_64\n"));
+}
+
+#[test]
+fn env_rejects_unexpected_variables() {
+    let s = Format::Env.serialize(&distro_configurations()[1..]).unwrap();
+    assert!(Format::Env.deserialize(&(s.clone() + "YOWSL_BOGUS=1\n")).is_err());
+    assert!(Format::Env
+        .deserialize(&(s.clone() + "YOWSL_DEFAULT_ENVIRONMENT_VARIABLE_99=X=1\n"))
+        .is_err());
+    assert!(Format::Env.deserialize(&s.replace("YOWSL_FLAGS=9\n", "")).is_err());
+    assert!(Format::Env
+        .deserialize(&s.replace("YOWSL_SCHEMA_VERSION=1", "YOWSL_SCHEMA_VERSION=2"))
+        .is_err());
+}
+
+#[test]
+fn env_holds_only_one_distro() {
+    assert!(Format::Env.serialize(&distro_configurations()).is_err());
+    let debian = Format::Env.serialize(&distro_configurations()[..1]).unwrap();
+    let ubuntu = Format::Env.serialize(&distro_configurations()[1..]).unwrap();
+    let both = debian + ubuntu.trim_start_matches("YOWSL_SCHEMA_VERSION=1\n");
+    assert!(Format::Env.deserialize(&both).is_err());
+}
+
+#[test]
+fn formats_parse_from_names() {
+    for name in &Format::NAMES {
+        assert_eq!(&name.parse::<Format>().unwrap().to_string(), name);
+    }
+    assert_eq!("JSON".parse::<Format>().unwrap(), Format::Json);
+    assert!("xml".parse::<Format>().is_err());
+}
@@ -0,0 +1,149 @@
+extern crate yowsl;
+
+use yowsl::{DistroFlags, DistroState, MemoryRegistry, Registry, RegistryValue, LXSS_KEY};
+
+const UBUNTU_GUID: &str = "{0a1b2c3d-0000-0000-0000-000000000001}";
+const DEBIAN_GUID: &str = "{0a1b2c3d-0000-0000-0000-000000000002}";
+
+fn registry() -> MemoryRegistry {
+    let registry = MemoryRegistry::new();
+    registry.insert_value(
+        LXSS_KEY,
+        "DefaultDistribution",
+        RegistryValue::Sz(DEBIAN_GUID.to_uppercase()),
+    );
+    let ubuntu = format!("{}\\{}", LXSS_KEY, UBUNTU_GUID);
+    registry.insert_value(&ubuntu, "DistributionName", RegistryValue::Sz("Ubuntu".to_string()));
+    registry.insert_value(
+        &ubuntu,
+        "BasePath",
+        RegistryValue::Sz("C:\\WSL\\Ubuntu".to_string()),
+    );
+    registry.insert_value(&ubuntu, "State", RegistryValue::Dword(1));
+    registry.insert_value(&ubuntu, "Version", RegistryValue::Dword(2));
+    registry.insert_value(&ubuntu, "DefaultUid", RegistryValue::Dword(1000));
+    registry.insert_value(&ubuntu, "Flags", RegistryValue::Dword(0xF));
+    registry.insert_value(
+        &ubuntu,
+        "DefaultEnvironment",
+        RegistryValue::MultiSz(vec!["HOSTTYPE=x86_64".to_string()]),
+    );
+    let debian = format!("{}\\{}", LXSS_KEY, DEBIAN_GUID);
+    registry.insert_value(&debian, "DistributionName", RegistryValue::Sz("Debian".to_string()));
+    registry.insert_value(
+        &debian,
+        "BasePath",
+        RegistryValue::Sz("C:\\WSL\\Debian".to_string()),
+    );
+    registry.insert_value(&debian, "State", RegistryValue::Dword(3));
+    registry.insert_key(&format!("{}\\AppxInstallerCache", LXSS_KEY));
+    registry
+}
+
+#[test]
+fn memory_registry_is_case_insensitive() {
+    let registry = registry();
+    let ubuntu = format!("{}\\{}", LXSS_KEY, UBUNTU_GUID).to_uppercase();
+    assert_eq!(
+        registry.value(&ubuntu, "defaultuid").unwrap(),
+        Some(RegistryValue::Dword(1000))
+    );
+    assert_eq!(registry.value(&ubuntu, "Missing").unwrap(), None);
+    assert_eq!(
+        registry.subkeys("hkey_current_user\\software").unwrap(),
+        vec!["Microsoft"]
+    );
+    assert!(registry.subkeys("HKEY_LOCAL_MACHINE").unwrap().is_empty());
+}
+
+#[test]
+fn list_distros_reads_every_distro() {
+    let distros = yowsl::list_distros(&registry()).unwrap();
+    assert_eq!(distros.len(), 2);
+    let ubuntu = &distros[0];
+    assert_eq!(ubuntu.guid, UBUNTU_GUID);
+    assert_eq!(ubuntu.configuration.name, "Ubuntu");
+    assert_eq!(ubuntu.base_path, "C:\\WSL\\Ubuntu");
+    assert_eq!(ubuntu.state, DistroState::Installed);
+    assert_eq!(ubuntu.configuration.version, 2);
+    assert_eq!(ubuntu.configuration.default_uid, 1000);
+    assert_eq!(ubuntu.configuration.flags.unknown_bits(), 0x8);
+    assert_eq!(
+        ubuntu.configuration.default_environment_variables,
+        vec!["HOSTTYPE=x86_64"]
+    );
+    assert!(!ubuntu.is_default);
+    let debian = &distros[1];
+    assert_eq!(debian.state, DistroState::Installing);
+    assert_eq!(debian.configuration.version, 1);
+    assert_eq!(debian.configuration.default_uid, 0);
+    assert_eq!(debian.configuration.flags, DistroFlags::all());
+    assert!(debian.configuration.default_environment_variables.is_empty());
+    assert!(debian.is_default);
+}
+
+#[test]
+fn find_distro_ignores_case() {
+    let registry = registry();
+    let distro = yowsl::find_distro(&registry, "ubuntu").unwrap().unwrap();
+    assert_eq!(distro.guid, UBUNTU_GUID);
+    assert!(yowsl::find_distro(&registry, "Alpine").unwrap().is_none());
+}
+
+#[test]
+fn list_distros_rejects_unexpected_types() {
+    let registry = registry();
+    registry.insert_value(
+        &format!("{}\\{}", LXSS_KEY, UBUNTU_GUID),
+        "DefaultUid",
+        RegistryValue::Sz("1000".to_string()),
+    );
+    assert!(yowsl::list_distros(&registry).is_err());
+}
+
+#[test]
+fn read_distros_goes_on_after_a_distro_that_cannot_be_read() {
+    let registry = registry();
+    registry.insert_key(&format!("{}\\{{0a1b2c3d-0000-0000-0000-000000000003}}", LXSS_KEY));
+    let distros = yowsl::read_distros(&registry).unwrap();
+    assert_eq!(distros.len(), 3);
+    let names = distros
+        .iter()
+        .filter_map(|distro| distro.as_ref().ok())
+        .map(|distro| distro.configuration.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["Ubuntu", "Debian"]);
+    assert!(yowsl::list_distros(&registry).is_err());
+}
+
+#[test]
+fn set_default_distro_writes_guid() {
+    let registry = registry();
+    let distro = yowsl::default_distro(&registry).unwrap().unwrap();
+    assert_eq!(distro.guid, DEBIAN_GUID);
+    let distro = yowsl::set_default_distro(&registry, "UBUNTU").unwrap();
+    assert_eq!(distro.guid, UBUNTU_GUID);
+    assert!(distro.is_default);
+    assert_eq!(
+        registry.value(LXSS_KEY, "DefaultDistribution").unwrap(),
+        Some(RegistryValue::Sz(UBUNTU_GUID.to_string()))
+    );
+    assert!(yowsl::set_default_distro(&registry, "Alpine").is_err());
+    registry.remove_value(LXSS_KEY, "DefaultDistribution");
+    assert!(yowsl::default_distro(&registry).unwrap().is_none());
+}
+
+#[test]
+fn rename_distro_changes_only_the_name() {
+    let registry = registry();
+    let distro = yowsl::rename_distro(&registry, "ubuntu", "Ubuntu-18.04").unwrap();
+    assert_eq!(distro.guid, UBUNTU_GUID);
+    assert_eq!(distro.configuration.name, "Ubuntu-18.04");
+    let renamed = yowsl::find_distro(&registry, "Ubuntu-18.04").unwrap().unwrap();
+    assert_eq!(renamed, distro);
+    assert!(yowsl::find_distro(&registry, "Ubuntu").unwrap().is_none());
+    assert!(yowsl::rename_distro(&registry, "Ubuntu-18.04", "DEBIAN").is_err());
+    assert!(yowsl::rename_distro(&registry, "Ubuntu-18.04", "Ubuntu 18.04").is_err());
+    assert!(yowsl::rename_distro(&registry, "Alpine", "Alpine-3.8").is_err());
+    yowsl::rename_distro(&registry, "debian", "Debian").unwrap();
+}
@@ -0,0 +1,138 @@
+extern crate flate2;
+extern crate tar;
+extern crate yowsl;
+
+mod common;
+
+use std::fs;
+use common::temp_dir;
+use yowsl::{MemoryRegistry, Registry, RegistryValue, LXSS_KEY};
+
+const GUID: &str = "{0a1b2c3d-0000-0000-0000-000000000001}";
+
+fn registry(base_path: &str) -> MemoryRegistry {
+    let registry = MemoryRegistry::new();
+    let key = format!("{}\\{}", LXSS_KEY, GUID);
+    registry.insert_value(&key, "DistributionName", RegistryValue::Sz("Ubuntu".to_string()));
+    registry.insert_value(&key, "BasePath", RegistryValue::Sz(base_path.to_string()));
+    registry
+}
+
+#[test]
+fn move_distro_copies_files_and_updates_base_path() {
+    let root = temp_dir("move-distro");
+    let src = root.join("src");
+    fs::create_dir_all(src.join("rootfs/etc")).unwrap();
+    fs::create_dir_all(src.join("temp")).unwrap();
+    fs::write(src.join("rootfs/etc/hostname"), "ubuntu\n").unwrap();
+    fs::write(src.join("Ubuntu.exe"), "launcher").unwrap();
+    let registry = registry(src.to_str().unwrap());
+    let dest = root.join("dest");
+    let mut reports = vec![];
+    let distro = yowsl::move_distro(&registry, "ubuntu", &dest, |progress| {
+        reports.push(*progress)
+    }).unwrap();
+    let dest = fs::canonicalize(&dest).unwrap();
+    assert_eq!(distro.base_path, dest.to_str().unwrap());
+    assert_eq!(
+        registry.value(&format!("{}\\{}", LXSS_KEY, GUID), "BasePath").unwrap(),
+        Some(RegistryValue::Sz(distro.base_path.clone()))
+    );
+    assert_eq!(fs::read_to_string(dest.join("rootfs/etc/hostname")).unwrap(), "ubuntu\n");
+    assert!(dest.join("temp").is_dir());
+    let last = reports.last().unwrap();
+    assert_eq!((last.copied_files, last.total_files), (2, 2));
+    assert_eq!((last.copied_bytes, last.total_bytes), (15, 15));
+    yowsl::verify_tree(&src, &dest).unwrap();
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn move_distro_rolls_back() {
+    let root = temp_dir("move-distro-rollback");
+    let src = root.join("src");
+    fs::create_dir_all(src.join("rootfs")).unwrap();
+    let registry = registry(src.to_str().unwrap());
+    assert!(yowsl::move_distro(&registry, "Ubuntu", &src.join("rootfs/dest"), |_| {}).is_err());
+    assert!(!src.join("rootfs/dest").exists());
+    let dest = root.join("dest");
+    fs::create_dir(&dest).unwrap();
+    fs::write(dest.join("file"), "").unwrap();
+    assert!(yowsl::move_distro(&registry, "Ubuntu", &dest, |_| {}).is_err());
+    assert!(dest.join("file").exists());
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn move_distro_rolls_back_a_failed_copy() {
+    use std::os::unix::net::UnixListener;
+
+    let root = temp_dir("move-distro-failed-copy");
+    let src = root.join("src");
+    fs::create_dir_all(src.join("rootfs/run")).unwrap();
+    fs::write(src.join("rootfs/file"), "").unwrap();
+    // `fs::copy` cannot copy a socket.
+    let _listener = UnixListener::bind(src.join("rootfs/run/socket")).unwrap();
+    let registry = registry(src.to_str().unwrap());
+    let dest = root.join("dest");
+    fs::create_dir(&dest).unwrap();
+    assert!(yowsl::move_distro(&registry, "Ubuntu", &dest, |_| {}).is_err());
+    assert!(dest.is_dir());
+    assert!(fs::read_dir(&dest).unwrap().next().is_none());
+    assert_eq!(
+        registry.value(&format!("{}\\{}", LXSS_KEY, GUID), "BasePath").unwrap(),
+        Some(RegistryValue::Sz(src.to_str().unwrap().to_string()))
+    );
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn move_distro_keeps_the_modes_of_folders() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let root = temp_dir("move-distro-modes");
+    let src = root.join("src");
+    let modes = [("rootfs/tmp", 0o1777), ("rootfs/root", 0o700), ("rootfs/usr", 0o555)];
+    for &(path, mode) in &modes {
+        fs::create_dir_all(src.join(path)).unwrap();
+        fs::write(src.join(path).join("file"), "").unwrap();
+        fs::set_permissions(src.join(path), fs::Permissions::from_mode(mode)).unwrap();
+    }
+    let registry = registry(src.to_str().unwrap());
+    let dest = root.join("dest");
+    yowsl::move_distro(&registry, "Ubuntu", &dest, |_| {}).unwrap();
+    for &(path, mode) in &modes {
+        let metadata = fs::metadata(dest.join(path)).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o7777, mode, "{}", path);
+        assert!(dest.join(path).join("file").is_file());
+    }
+    fs::set_permissions(src.join("rootfs/usr"), fs::Permissions::from_mode(0o755)).unwrap();
+    fs::set_permissions(dest.join("rootfs/usr"), fs::Permissions::from_mode(0o755)).unwrap();
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn copy_tree_keeps_hard_links() {
+    use std::os::unix::fs::MetadataExt;
+
+    let root = temp_dir("move-distro-hard-links");
+    let src = root.join("src");
+    fs::create_dir_all(src.join("bin")).unwrap();
+    fs::create_dir_all(src.join("usr/bin")).unwrap();
+    fs::write(src.join("usr/bin/busybox"), "busybox").unwrap();
+    fs::hard_link(src.join("usr/bin/busybox"), src.join("bin/sh")).unwrap();
+    fs::write(src.join("bin/ash"), "ash").unwrap();
+    let dest = root.join("dest");
+    fs::create_dir(&dest).unwrap();
+    let progress = yowsl::copy_tree(&src, &dest, |_| {}).unwrap();
+    assert_eq!(progress.copied_files, 3);
+    let ino = |path: &str| fs::metadata(dest.join(path)).unwrap().ino();
+    assert_eq!(ino("bin/sh"), ino("usr/bin/busybox"));
+    assert_ne!(ino("bin/sh"), ino("bin/ash"));
+    assert_eq!(fs::read_to_string(dest.join("bin/sh")).unwrap(), "busybox");
+    yowsl::verify_tree(&src, &dest).unwrap();
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,80 @@
+extern crate yowsl;
+
+use yowsl::{FakeBackend, NewPassword, NewUser, WslBackend};
+
+const PASSWD: &str = "root:x:0:0:root:/root:/bin/bash\nalice:x:1000:1000::/home/alice:/bin/bash\n";
+
+fn alice(password: NewPassword, sudo: bool) -> NewUser {
+    NewUser {
+        name: "alice".to_string(),
+        password,
+        sudo,
+    }
+}
+
+#[test]
+fn create_user_runs_every_step() {
+    let backend = FakeBackend::new();
+    backend.register_distro("Ubuntu", "rootfs.tar.gz").unwrap();
+    backend.set_launch_output(PASSWD.as_bytes(), b"");
+    let user = alice(NewPassword::Hash("$6$salt$hash".to_string()), true);
+    let report = yowsl::create_user(&backend, "Ubuntu", &user);
+    assert!(report.failed.is_none());
+    assert_eq!(
+        report.done,
+        vec![
+            "creating the user \"alice\"",
+            "setting the password of \"alice\"",
+            "adding \"alice\" to sudo or wheel",
+            "making \"alice\" the default user",
+        ]
+    );
+    let launches = backend.launches();
+    let commands = launches
+        .iter()
+        .map(|launch| launch.command.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(commands.len(), 4);
+    assert!(commands[0].starts_with("sh -c ") && commands[0].ends_with(" sh alice"));
+    assert!(commands[0].contains("useradd -m"));
+    assert_eq!(commands[1], "chpasswd -e");
+    assert_eq!(launches[1].stdin, b"alice:$6$salt$hash\n");
+    assert!(launches.iter().all(|launch| !launch.command.contains("$6$")));
+    assert!(commands[2].contains("usermod -a -G"));
+    assert_eq!(commands[3], "cat /etc/passwd");
+    assert_eq!(backend.get_distro_configuration("Ubuntu").unwrap().default_uid, 1000);
+}
+
+#[test]
+fn create_user_stops_at_the_first_failure() {
+    let backend = FakeBackend::new();
+    backend.register_distro("Ubuntu", "rootfs.tar.gz").unwrap();
+    backend.set_launch_exit_code(1);
+    backend.set_launch_output(b"", b"useradd: user 'alice' already exists\n");
+    let report = yowsl::create_user(&backend, "Ubuntu", &alice(NewPassword::Empty, false));
+    assert!(report.done.is_empty());
+    let (failed, e) = report.failed.unwrap();
+    assert_eq!(failed, "creating the user \"alice\"");
+    assert!(e.to_string().contains("already exists"));
+    assert_eq!(
+        report.not_done,
+        vec!["removing the password of \"alice\"", "making \"alice\" the default user"]
+    );
+    assert_eq!(backend.launches().len(), 1);
+    assert_eq!(backend.get_distro_configuration("Ubuntu").unwrap().default_uid, 0);
+}
+
+#[test]
+fn create_user_fails_without_the_user_in_passwd() {
+    let backend = FakeBackend::new();
+    backend.register_distro("Ubuntu", "rootfs.tar.gz").unwrap();
+    backend.set_launch_output(b"root:x:0:0:root:/root:/bin/bash\n", b"");
+    let report = yowsl::create_user(&backend, "Ubuntu", &alice(NewPassword::Prompt, false));
+    assert_eq!(report.done.len(), 2);
+    assert_eq!(backend.launches()[1].command, "passwd alice");
+    assert_eq!(report.failed.unwrap().0, "making \"alice\" the default user");
+    assert!(yowsl::validate_password_hash("$6$salt$hash").is_ok());
+    for hash in &["", "a:b", "a b"] {
+        assert!(yowsl::validate_password_hash(hash).is_err(), "{}", hash);
+    }
+}
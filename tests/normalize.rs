@@ -0,0 +1,145 @@
+extern crate flate2;
+extern crate tar;
+extern crate yowsl;
+
+mod common;
+
+use std::fs;
+use common::{read_tar_gz, temp_dir, write_tar, Item};
+use yowsl::{Severity, SourceFormat};
+
+#[test]
+fn normalize_tar_gz_fixes_a_rootfs() {
+    let dir = temp_dir("normalize");
+    let src = dir.join("rootfs.tar");
+    write_tar(
+        &src,
+        &[
+            Item::Dir("./rootfs/"),
+            Item::Dir("./rootfs/bin/"),
+            Item::File("./rootfs/bin/sh", "#!"),
+            Item::File("./rootfs/etc/passwd", "root:x:0:0::/root:/bin/sh\n"),
+            Item::Char("./rootfs/dev/null"),
+            Item::Symlink("./rootfs/usr/bin/sh", "/bin/sh"),
+            Item::HardLink("./rootfs/usr/bin/dash", "/rootfs/bin/sh"),
+            Item::HardLink("./rootfs/usr/bin/bash", "/host/bin/bash"),
+            Item::File("../escape", "x"),
+            Item::File("./rootfs/usr/share/Doc/README", "upper"),
+            Item::File("./rootfs/usr/share/doc/README", "lower"),
+            Item::File("./rootfs/usr/share/doc/readme", "lowest"),
+        ],
+    );
+    let dest = dir.join("normalized.tar.gz");
+    let changes = yowsl::normalize_tar_gz(&src, SourceFormat::Tar, &dest).unwrap();
+    assert_eq!(
+        changes,
+        vec![
+            "removed the folder rootfs that wraps the rootfs",
+            "renamed usr/share/doc to usr/share/doc~1, as it differs from another path only in \
+             case",
+            "renamed usr/share/doc/readme to usr/share/doc~1/readme~1, as it differs from \
+             another path only in case",
+            "dropped the device node dev/null, which WSL1 rejects",
+            "dropped the hard link usr/bin/bash to /host/bin/bash, which is outside of the \
+             archive",
+            "dropped ../escape, which is outside of the archive",
+        ]
+    );
+    let files = read_tar_gz(&dest);
+    let expected = vec![
+        ("bin", "/"),
+        ("bin/sh", "#!"),
+        ("etc/passwd", "root:x:0:0::/root:/bin/sh\n"),
+        ("usr/bin/dash", "=> bin/sh"),
+        ("usr/bin/sh", "-> /bin/sh"),
+        ("usr/share/Doc/README", "upper"),
+        ("usr/share/doc~1/README", "lower"),
+        ("usr/share/doc~1/readme~1", "lowest"),
+    ];
+    assert_eq!(
+        files,
+        expected
+            .into_iter()
+            .map(|(path, s)| (path.to_string(), s.to_string()))
+            .collect()
+    );
+    let problems = yowsl::check_rootfs(&dest).unwrap();
+    assert!(problems.iter().all(|problem| problem.severity != Severity::Error));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn normalize_tar_gz_drops_hard_links_outside_of_the_wrapping_folder() {
+    let dir = temp_dir("normalize-outside");
+    let src = dir.join("rootfs.tar");
+    write_tar(
+        &src,
+        &[
+            Item::File("ubuntu-rootfs/bin/sh", "#!"),
+            Item::File("ubuntu-rootfs/etc/passwd", "root:x:0:0::/root:/bin/sh\n"),
+            Item::HardLink("ubuntu-rootfs/bin/bash", "sh"),
+            Item::HardLink("ubuntu-rootfs/bin/dash", "ubuntu-rootfsx/bin/sh"),
+        ],
+    );
+    let dest = dir.join("normalized.tar.gz");
+    let changes = yowsl::normalize_tar_gz(&src, SourceFormat::Tar, &dest).unwrap();
+    assert_eq!(
+        changes,
+        vec![
+            "removed the folder ubuntu-rootfs that wraps the rootfs",
+            "dropped the hard link bin/bash to sh, which is outside of the archive",
+            "dropped the hard link bin/dash to ubuntu-rootfsx/bin/sh, which is outside of the \
+             archive",
+        ]
+    );
+    let files = read_tar_gz(&dest);
+    assert_eq!(files.keys().collect::<Vec<_>>(), vec!["bin/sh", "etc/passwd"]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn normalize_tar_gz_writes_hard_links_after_their_targets() {
+    let dir = temp_dir("normalize-later");
+    let src = dir.join("rootfs.tar");
+    write_tar(
+        &src,
+        &[
+            Item::HardLink("./bin/ash", "./bin/sh"),
+            Item::HardLink("./bin/sh", "./usr/bin/busybox"),
+            Item::File("./usr/bin/busybox", "busybox"),
+            Item::Char("./dev/null"),
+            Item::HardLink("./dev/zero", "./dev/null"),
+        ],
+    );
+    let dest = dir.join("normalized.tar.gz");
+    let changes = yowsl::normalize_tar_gz(&src, SourceFormat::Tar, &dest).unwrap();
+    assert_eq!(
+        changes,
+        vec![
+            "dropped the device node dev/null, which WSL1 rejects",
+            "dropped the hard link dev/zero to ./dev/null, whose target is dropped",
+        ]
+    );
+    let files = read_tar_gz(&dest);
+    assert_eq!(files["usr/bin/busybox"], "busybox");
+    assert_eq!(files["bin/sh"], "=> usr/bin/busybox");
+    assert_eq!(files["bin/ash"], "=> bin/sh");
+    assert_eq!(files.len(), 3);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn normalize_tar_gz_keeps_an_unwrapped_rootfs() {
+    let dir = temp_dir("normalize-unwrapped");
+    let src = dir.join("rootfs.tar");
+    write_tar(
+        &src,
+        &[Item::Dir("./"), Item::File("./opt/app/bin/run", "run")],
+    );
+    let dest = dir.join("normalized.tar.gz");
+    let changes = yowsl::normalize_tar_gz(&src, SourceFormat::Tar, &dest).unwrap();
+    assert!(changes.is_empty(), "{:?}", changes);
+    assert_eq!(read_tar_gz(&dest)["opt/app/bin/run"], "run");
+    assert!(yowsl::normalize_tar_gz(&dir, SourceFormat::Directory, &dest).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
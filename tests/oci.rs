@@ -0,0 +1,212 @@
+extern crate flate2;
+extern crate tar;
+extern crate yowsl;
+
+mod common;
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use flate2::write::GzEncoder;
+use tar::Header;
+use common::{read_tar_gz, tar, temp_dir, Item};
+use yowsl::{ImageConfig, ImageSelector, OciImage};
+
+const PASSWD: &str = "root:x:0:0:root:/root:/bin/sh\nalice:x:1000:1000::/home/alice:/bin/sh\n";
+const CONFIG: &str = r#"{
+    "architecture": "amd64",
+    "os": "linux",
+    "config": {
+        "Env": ["PATH=/usr/bin:/bin", "LANG=C.UTF-8"],
+        "User": "alice",
+        "WorkingDir": "/home/alice"
+    }
+}"#;
+const ARM64_CONFIG: &str = r#"{"architecture": "arm64", "os": "linux", "config": null}"#;
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(vec![], flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn base_layer() -> Vec<u8> {
+    tar(&[
+        Item::Dir("./etc/"),
+        Item::File("./etc/passwd", PASSWD),
+        Item::File("./etc/hostname", "base\n"),
+        Item::Dir("./opt/"),
+        Item::File("./opt/keep/a", "a"),
+        Item::File("./opt/gone/b", "b"),
+        Item::File("./var/cache/x", "x"),
+        Item::File("./var/cache/y", "y"),
+        Item::File("./bin/sh", "#!"),
+        Item::HardLink("./usr/bin/sh", "./bin/sh"),
+        Item::File("./bin/perl", "perl 5"),
+        Item::HardLink("./bin/perl5", "./bin/perl"),
+    ])
+}
+
+fn top_layer() -> Vec<u8> {
+    gzip(&tar(&[
+        Item::File("etc/hostname", "top\n"),
+        Item::File("bin/perl", "perl 7"),
+        Item::File("opt/.wh.gone", ""),
+        Item::Dir("var/cache/"),
+        Item::File("var/cache/.wh..wh..opq", ""),
+        Item::File("var/cache/z", "z"),
+    ]))
+}
+
+/// Writes a `docker save` archive of one image with `base_layer()` and `top_layer()`.
+fn docker_archive(path: &Path) {
+    let manifest = r#"[{
+        "Config": "0123abcd.json",
+        "RepoTags": ["team/distro:1.0"],
+        "Layers": ["l0/layer.tar", "l1/layer.tar"]
+    }]"#;
+    let mut builder = tar::Builder::new(File::create(path).unwrap());
+    let files = vec![
+        ("manifest.json", manifest.as_bytes().to_vec()),
+        ("0123abcd.json", CONFIG.as_bytes().to_vec()),
+        ("l0/layer.tar", base_layer()),
+        ("l1/layer.tar", top_layer()),
+    ];
+    for (name, data) in files {
+        let mut header = Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        builder.append_data(&mut header, name, &data[..]).unwrap();
+    }
+    builder.finish().unwrap();
+}
+
+/// Writes an OCI image layout with a linux/amd64 and a linux/arm64 image under one index.
+fn oci_layout(dir: &Path) {
+    let blobs = dir.join("blobs/sha256");
+    fs::create_dir_all(&blobs).unwrap();
+    let manifest = |config: &str, layers: &[&str]| {
+        format!(
+            r#"{{"schemaVersion": 2, "config": {{"digest": "sha256:{}"}}, "layers": [{}]}}"#,
+            config,
+            layers
+                .iter()
+                .map(|layer| format!(r#"{{"digest": "sha256:{}"}}"#, layer))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    };
+    let files = vec![
+        ("c0", CONFIG.as_bytes().to_vec()),
+        ("c1", ARM64_CONFIG.as_bytes().to_vec()),
+        ("l0", base_layer()),
+        ("l1", top_layer()),
+        ("l2", gzip(&tar(&[Item::File("etc/arch", "arm64\n")]))),
+        ("m0", manifest("c0", &["l0", "l1"]).into_bytes()),
+        ("m1", manifest("c1", &["l0", "l1", "l2"]).into_bytes()),
+        (
+            "i0",
+            br#"{"schemaVersion": 2, "manifests": [
+                {"digest": "sha256:m0", "platform": {"os": "linux", "architecture": "amd64"}},
+                {"digest": "sha256:m1", "platform": {"os": "linux", "architecture": "arm64"}},
+                {"digest": "sha256:m0", "platform": {"os": "unknown", "architecture": "unknown"}}
+            ]}"#.to_vec(),
+        ),
+    ];
+    for (name, data) in files {
+        fs::write(blobs.join(name), data).unwrap();
+    }
+    fs::write(dir.join("oci-layout"), r#"{"imageLayoutVersion": "1.0.0"}"#).unwrap();
+    fs::write(
+        dir.join("index.json"),
+        r#"{"schemaVersion": 2, "manifests": [{
+            "mediaType": "application/vnd.oci.image.index.v1+json",
+            "digest": "sha256:i0",
+            "annotations": {"org.opencontainers.image.ref.name": "1.0"}
+        }]}"#,
+    ).unwrap();
+}
+
+fn assert_flattened(files: &BTreeMap<String, String>) {
+    assert_eq!(files["etc/hostname"], "top\n");
+    assert_eq!(files["etc/passwd"], PASSWD);
+    assert_eq!(files["opt/keep/a"], "a");
+    assert_eq!(files["usr/bin/sh"], "=> bin/sh");
+    // The upper layer replaces the target of the hard link, which keeps the file of its layer.
+    assert_eq!(files["bin/perl"], "perl 7");
+    assert_eq!(files["bin/perl5"], "perl 5");
+    assert_eq!(files["var/cache/z"], "z");
+    for path in &["opt/gone/b", "opt/.wh.gone", "var/cache/x", "var/cache/.wh..wh..opq"] {
+        assert!(!files.contains_key(*path), "{}", path);
+    }
+}
+
+#[test]
+fn docker_archive_is_flattened() {
+    let dir = temp_dir("oci-docker");
+    let image = dir.join("image.tar");
+    docker_archive(&image);
+    let tar_gz = dir.join("rootfs.tar.gz");
+    let found = yowsl::write_oci_tar_gz(&image, &ImageSelector::default(), &tar_gz).unwrap();
+    assert_flattened(&read_tar_gz(&tar_gz));
+    assert_eq!(
+        found.config,
+        ImageConfig {
+            env: vec!["PATH=/usr/bin:/bin".to_string(), "LANG=C.UTF-8".to_string()],
+            user: Some("alice".to_string()),
+            working_dir: Some("/home/alice".to_string()),
+        }
+    );
+    assert_eq!(found.default_uid().unwrap(), Some(1000));
+    let selector = ImageSelector {
+        tag: Some("2.0".to_string()),
+        platform: None,
+    };
+    assert!(yowsl::write_oci_tar_gz(&image, &selector, &tar_gz).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn oci_layout_picks_a_platform() {
+    let dir = temp_dir("oci-layout");
+    let layout = dir.join("layout");
+    oci_layout(&layout);
+    let tar_gz = dir.join("rootfs.tar.gz");
+    let selector = ImageSelector {
+        tag: Some("1.0".to_string()),
+        platform: None,
+    };
+    yowsl::write_oci_tar_gz(&layout, &selector, &tar_gz).unwrap();
+    let files = read_tar_gz(&tar_gz);
+    assert_flattened(&files);
+    assert!(!files.contains_key("etc/arch"));
+    let selector = ImageSelector {
+        tag: None,
+        platform: Some("linux/arm64".to_string()),
+    };
+    let found = yowsl::write_oci_tar_gz(&layout, &selector, &tar_gz).unwrap();
+    assert_eq!(read_tar_gz(&tar_gz)["etc/arch"], "arm64\n");
+    assert_eq!(found.config, ImageConfig::default());
+    let selector = ImageSelector {
+        tag: None,
+        platform: Some("linux/riscv64".to_string()),
+    };
+    assert!(yowsl::write_oci_tar_gz(&layout, &selector, &tar_gz).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn default_uid_reads_user() {
+    let image = |user: &str| OciImage {
+        config: ImageConfig {
+            user: Some(user.to_string()),
+            ..ImageConfig::default()
+        },
+        passwd: Some(PASSWD.to_string()),
+    };
+    assert_eq!(image("alice:staff").default_uid().unwrap(), Some(1000));
+    assert_eq!(image("1001:1001").default_uid().unwrap(), Some(1001));
+    assert_eq!(image("").default_uid().unwrap(), None);
+    assert!(image("bob").default_uid().is_err());
+}
@@ -0,0 +1,79 @@
+extern crate flate2;
+extern crate tar;
+extern crate yowsl;
+
+mod common;
+
+use std::fs::{self, File};
+use std::io::Write;
+use flate2::write::GzEncoder;
+use tar::Header;
+use common::temp_dir;
+use yowsl::{DistroConfiguration, DistroFlags, FakeBackend, PasswdUser};
+
+const PASSWD: &str = "# users\nroot:x:0:0:root:/root:/bin/bash\nbroken\n\
+                      alice:x:1000:1000::/home/alice:/bin/bash\nbob:x:nan:0::/:/bin/sh\n";
+
+#[test]
+fn parse_passwd_skips_broken_lines() {
+    assert_eq!(
+        yowsl::parse_passwd(PASSWD),
+        vec![
+            PasswdUser {
+                name: "root".to_string(),
+                uid: 0,
+            },
+            PasswdUser {
+                name: "alice".to_string(),
+                uid: 1000,
+            },
+        ]
+    );
+    assert_eq!(yowsl::uid_of(PASSWD, "alice"), Some(1000));
+    assert_eq!(yowsl::uid_of(PASSWD, "bob"), None);
+    assert_eq!(yowsl::user_name_of(PASSWD, 0), Some("root".to_string()));
+    assert_eq!(yowsl::user_name_of(PASSWD, 1001), None);
+}
+
+#[test]
+fn read_passwd_runs_cat() {
+    let backend = FakeBackend::new();
+    backend.insert_distro(DistroConfiguration {
+        name: "Ubuntu".to_string(),
+        version: 1,
+        default_uid: 0,
+        flags: DistroFlags::empty(),
+        default_environment_variables: vec![],
+    });
+    backend.set_launch_output(PASSWD.as_bytes(), b"");
+    assert_eq!(yowsl::read_passwd(&backend, "Ubuntu").unwrap(), PASSWD);
+    assert_eq!(backend.launches()[0].command, "cat /etc/passwd");
+    backend.set_launch_exit_code(1);
+    backend.set_launch_output(b"", b"cat: /etc/passwd: No such file or directory\n");
+    assert!(yowsl::read_passwd(&backend, "Ubuntu").is_err());
+    assert!(yowsl::read_passwd(&backend, "Debian").is_err());
+}
+
+#[test]
+fn read_rootfs_passwd_reads_archives_and_folders() {
+    let dir = temp_dir("passwd");
+    let mut builder = tar::Builder::new(GzEncoder::new(vec![], flate2::Compression::default()));
+    for &(path, data) in &[("./etc/passwd", "root:x:0:0::/:/bin/sh\n"), ("./etc/passwd", PASSWD)] {
+        let mut header = Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        builder.append_data(&mut header, path, data.as_bytes()).unwrap();
+    }
+    let tar_gz = dir.join("rootfs.tar.gz");
+    File::create(&tar_gz)
+        .unwrap()
+        .write_all(&builder.into_inner().unwrap().finish().unwrap())
+        .unwrap();
+    assert_eq!(yowsl::read_rootfs_passwd(&tar_gz).unwrap(), Some(PASSWD.to_string()));
+    let rootfs = dir.join("rootfs");
+    fs::create_dir_all(rootfs.join("etc")).unwrap();
+    assert_eq!(yowsl::read_rootfs_passwd(&rootfs).unwrap(), None);
+    fs::write(rootfs.join("etc/passwd"), PASSWD).unwrap();
+    assert_eq!(yowsl::read_rootfs_passwd(&rootfs).unwrap(), Some(PASSWD.to_string()));
+    fs::remove_dir_all(&dir).unwrap();
+}
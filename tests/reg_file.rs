@@ -0,0 +1,141 @@
+extern crate yowsl;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use yowsl::{DistroState, MemoryRegistry, RegFile, Registry, RegistryValue, LXSS_KEY};
+
+const UBUNTU_GUID: &str = "{b5a3c6e1-4f2d-4c8e-9a17-3e5d2f0c7a11}";
+const DEBIAN_GUID: &str = "{0d7f9e24-8b31-47a6-b2c5-91e4a6d3f852}";
+
+fn fixture() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/lxss.reg")
+}
+
+/// Copies the fixture to a temporary file that the test can change.
+fn temp_fixture(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("yowsl-{}-{}.reg", name, process::id()));
+    fs::copy(fixture(), &path).unwrap();
+    path
+}
+
+fn assert_same_registry(a: &MemoryRegistry, b: &MemoryRegistry) {
+    for key in a.keys() {
+        assert_eq!(a.values(&key), b.values(&key), "{}", key);
+    }
+    for key in b.keys() {
+        assert_eq!(a.values(&key), b.values(&key), "{}", key);
+    }
+}
+
+#[test]
+fn fixture_lists_distros() {
+    let registry = RegFile::open(fixture()).unwrap();
+    let distros = yowsl::list_distros(&registry).unwrap();
+    assert_eq!(distros.len(), 2);
+    let debian = &distros[0];
+    assert_eq!(debian.guid, DEBIAN_GUID);
+    assert_eq!(debian.configuration.name, "Debian");
+    assert_eq!(debian.state, DistroState::Installed);
+    assert_eq!(debian.configuration.default_uid, 1000);
+    assert_eq!(debian.configuration.flags.bits(), 0xF);
+    assert!(!debian.is_default);
+    let ubuntu = &distros[1];
+    assert_eq!(ubuntu.guid, UBUNTU_GUID);
+    assert_eq!(ubuntu.configuration.name, "Ubuntu-18.04");
+    assert_eq!(ubuntu.base_path, "D:\\WSL\\Ubuntu-18.04");
+    assert_eq!(
+        ubuntu.configuration.default_environment_variables[2],
+        "PATH=/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin:/usr/games:\
+         /usr/local/games"
+    );
+    assert!(ubuntu.is_default);
+}
+
+#[test]
+fn format_reg_round_trips() {
+    let registry = MemoryRegistry::new();
+    let key = "HKEY_CURRENT_USER\\Software\\yowsl \"test\"";
+    let values = vec![
+        ("", RegistryValue::Sz("default".to_string())),
+        ("Quoted", RegistryValue::Sz("C:\\\"quoted\"\\".to_string())),
+        ("Lines", RegistryValue::Sz("line 1\r\nline 2".to_string())),
+        ("Expand", RegistryValue::ExpandSz("%USERPROFILE%\\WSL".to_string())),
+        ("Binary", RegistryValue::Binary((0..100).collect())),
+        ("Dword", RegistryValue::Dword(0xDEAD_BEEF)),
+        ("Qword", RegistryValue::Qword(0x0123_4567_89AB_CDEF)),
+        ("Empty", RegistryValue::MultiSz(vec![])),
+        (
+            "Multi",
+            RegistryValue::MultiSz(vec!["a=1".to_string(), "こんにちは".to_string()]),
+        ),
+        ("Other", RegistryValue::Other(0x42, vec![1, 2, 3])),
+    ];
+    for (name, value) in values {
+        registry.insert_value(key, name, value);
+    }
+    registry.insert_key("HKEY_CURRENT_USER\\Software\\yowsl \"test\"\\Empty");
+    let reg = yowsl::format_reg(&registry);
+    assert!(reg.starts_with("Windows Registry Editor Version 5.00\r\n\r\n"));
+    assert!(reg.contains("\r\n\"Dword\"=dword:deadbeef\r\n"));
+    assert!(reg.lines().all(|line| line.len() <= 80));
+    let parsed = MemoryRegistry::new();
+    yowsl::parse_reg(&reg, &parsed).unwrap();
+    assert_same_registry(&registry, &parsed);
+    assert_eq!(
+        parsed.subkeys("HKEY_CURRENT_USER\\Software\\yowsl \"test\"").unwrap(),
+        vec!["Empty"]
+    );
+}
+
+#[test]
+fn parse_reg_deletes_keys_and_values() {
+    let registry = MemoryRegistry::new();
+    yowsl::parse_reg(
+        "Windows Registry Editor Version 5.00\n\
+         \n\
+         [HKEY_CURRENT_USER\\A\\B]\n\
+         \"X\"=dword:00000001\n\
+         \"Y\"=\"y\"\n\
+         ; A comment\n\
+         [HKEY_CURRENT_USER\\A\\B]\n\
+         \"X\"=-\n\
+         [HKEY_CURRENT_USER\\A\\C]\n\
+         [-HKEY_CURRENT_USER\\A\\C]\n",
+        &registry,
+    ).unwrap();
+    assert_eq!(registry.value("HKEY_CURRENT_USER\\A\\B", "X").unwrap(), None);
+    assert_eq!(
+        registry.value("HKEY_CURRENT_USER\\A\\B", "Y").unwrap(),
+        Some(RegistryValue::Sz("y".to_string()))
+    );
+    assert_eq!(registry.subkeys("HKEY_CURRENT_USER\\A").unwrap(), vec!["B"]);
+    assert!(yowsl::parse_reg("[HKEY_CURRENT_USER\\A]\n", &registry).is_err());
+}
+
+#[test]
+fn reg_file_writes_changes_back() {
+    let path = temp_fixture("reg-file-writes");
+    let registry = RegFile::open(&path).unwrap();
+    let ubuntu = format!("{}\\{}", LXSS_KEY, UBUNTU_GUID);
+    registry
+        .set_value(&ubuntu, "DefaultUid", &RegistryValue::Dword(0))
+        .unwrap();
+    registry.delete_value(LXSS_KEY, "DefaultDistribution").unwrap();
+    registry
+        .delete_key(&format!("{}\\{}", LXSS_KEY, DEBIAN_GUID))
+        .unwrap();
+    let bytes = fs::read(&path).unwrap();
+    assert_eq!(&bytes[..2], &[0xFF, 0xFE]);
+    let reopened = RegFile::open(&path).unwrap();
+    let distros = yowsl::list_distros(&reopened).unwrap();
+    assert_eq!(distros.len(), 1);
+    assert_eq!(distros[0].configuration.default_uid, 0);
+    assert!(!distros[0].is_default);
+    assert_eq!(
+        reopened.subkeys(LXSS_KEY).unwrap(),
+        vec!["AppxInstallerCache", UBUNTU_GUID]
+    );
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,108 @@
+extern crate flate2;
+extern crate tar;
+extern crate yowsl;
+
+mod common;
+
+use std::fs;
+use std::io::Write;
+use flate2::write::GzEncoder;
+use common::{temp_dir, tar, Item};
+use yowsl::{Problem, Severity};
+
+fn check(items: &[Item]) -> Vec<Problem> {
+    yowsl::check_tar(&tar(items)[..]).unwrap()
+}
+
+fn messages(problems: &[Problem], severity: Severity) -> Vec<&str> {
+    problems
+        .iter()
+        .filter(|problem| problem.severity == severity)
+        .map(|problem| problem.message.as_str())
+        .collect()
+}
+
+#[test]
+fn check_tar_accepts_a_merged_usr() {
+    let problems = check(&[
+        Item::Dir("./"),
+        Item::Symlink("./bin", "usr/bin"),
+        Item::Dir("./usr/bin/"),
+        Item::File("./usr/bin/bash", ""),
+        Item::Symlink("./usr/bin/sh", "/bin/bash"),
+        Item::File("./etc/passwd", ""),
+        Item::Symlink("./etc/mtab", "../proc/self/mounts"),
+    ]);
+    assert_eq!(problems, vec![]);
+}
+
+#[test]
+fn check_tar_reports_problems() {
+    let problems = check(&[
+        Item::Dir("etc/"),
+        Item::File("/etc/shadow", ""),
+        Item::File("usr/../../evil", ""),
+        Item::HardLink("usr/passwd", "../etc/passwd"),
+        Item::Char("dev/null"),
+        Item::Symlink("loop/a", "b"),
+        Item::Symlink("loop/b", "./a"),
+        Item::File("usr/README", ""),
+        Item::File("usr/readme", ""),
+    ]);
+    assert_eq!(
+        messages(&problems, Severity::Error),
+        vec![
+            "/etc/shadow is absolute or has .., which points outside of the rootfs",
+            "usr/../../evil is absolute or has .., which points outside of the rootfs",
+            "usr/passwd is a hard link to ../etc/passwd, which is outside of the rootfs",
+            "/bin/sh is missing",
+            "/etc/passwd is missing",
+        ]
+    );
+    assert_eq!(
+        messages(&problems, Severity::Warning),
+        vec![
+            "dev/null is a device node, which WSL1 rejects",
+            "usr/README and usr/readme differ only in case, which breaks on NTFS unless the \
+             folder is case sensitive",
+            "loop/a is a loop of symbolic links",
+            "loop/b is a loop of symbolic links",
+        ]
+    );
+}
+
+#[test]
+fn check_tar_reports_a_rootfs_prefix() {
+    let problems = check(&[
+        Item::Dir("./rootfs/"),
+        Item::File("./rootfs/bin/sh", ""),
+        Item::File("./rootfs/etc/passwd", ""),
+    ]);
+    assert_eq!(
+        messages(&problems, Severity::Error)[0],
+        "every file is under rootfs/, but WSL expects the files of rootfs at the top"
+    );
+    assert_eq!(
+        messages(&check(&[]), Severity::Error),
+        vec!["the rootfs is empty"]
+    );
+}
+
+#[test]
+fn check_rootfs_reads_archives_and_folders() {
+    let dir = temp_dir("check-rootfs");
+    let tar = tar(&[Item::File("bin/sh", ""), Item::File("etc/passwd", "")]);
+    let mut gz = GzEncoder::new(vec![], flate2::Compression::default());
+    gz.write_all(&tar).unwrap();
+    fs::write(dir.join("rootfs.tar.gz"), gz.finish().unwrap()).unwrap();
+    assert_eq!(yowsl::check_rootfs(dir.join("rootfs.tar.gz")).unwrap(), vec![]);
+    let rootfs = dir.join("rootfs");
+    fs::create_dir_all(rootfs.join("bin")).unwrap();
+    fs::write(rootfs.join("bin/sh"), "").unwrap();
+    let problems = yowsl::check_rootfs(&rootfs).unwrap();
+    assert_eq!(
+        messages(&problems, Severity::Error),
+        vec!["/etc/passwd is missing"]
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,95 @@
+extern crate flate2;
+extern crate tar;
+extern crate yowsl;
+
+mod common;
+
+use std::fs::{self, File};
+use std::path::Path;
+use std::process::Command;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use common::temp_dir;
+use yowsl::{SimulatorBackend, WslBackend, WslError};
+
+fn write_tar_gz(path: &Path) {
+    let mut builder = tar::Builder::new(GzEncoder::new(
+        File::create(path).unwrap(),
+        Compression::default(),
+    ));
+    let data = b"root:x:0:0:root:/root:/bin/sh\n";
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, "etc/passwd", &data[..])
+        .unwrap();
+    builder.into_inner().unwrap().finish().unwrap();
+}
+
+fn simulator(root: &Path) -> SimulatorBackend {
+    SimulatorBackend::new(root.join("simulator"))
+        .unwrap()
+        .with_responder(|launch| {
+            let mut command = Command::new("sh");
+            command.current_dir(launch.rootfs).arg("-c").arg(launch.command);
+            command
+        })
+}
+
+#[test]
+fn registers_configures_and_unregisters() {
+    let root = temp_dir("simulator-register");
+    let tar_gz = root.join("rootfs.tar.gz");
+    write_tar_gz(&tar_gz);
+    let simulator = simulator(&root);
+    simulator
+        .register_distro("Test", tar_gz.to_str().unwrap())
+        .unwrap();
+    assert!(simulator.is_distribution_registered("Test").unwrap());
+    assert!(simulator.rootfs_path("Test").join("etc/passwd").is_file());
+    let error = simulator
+        .register_distro("Test", tar_gz.to_str().unwrap())
+        .unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<WslError>(),
+        Some(&WslError::AlreadyExists)
+    );
+
+    let mut distro_configuration = simulator.get_distro_configuration("Test").unwrap();
+    distro_configuration.default_uid = 1000;
+    simulator.configure_distro(&distro_configuration).unwrap();
+    let simulator = self::simulator(&root);
+    assert_eq!(
+        simulator.get_distro_configuration("Test").unwrap(),
+        distro_configuration
+    );
+
+    simulator.unregister_distro("Test").unwrap();
+    assert!(!simulator.is_distribution_registered("Test").unwrap());
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn captures_output() {
+    let root = temp_dir("simulator-capture");
+    let tar_gz = root.join("rootfs.tar.gz");
+    write_tar_gz(&tar_gz);
+    let simulator = simulator(&root);
+    simulator
+        .register_distro("Test", tar_gz.to_str().unwrap())
+        .unwrap();
+    let output = simulator
+        .run_capture("Test", "cut -d: -f1 etc/passwd; echo oops >&2; exit 3")
+        .unwrap();
+    assert_eq!(output.exit_code, 3);
+    assert_eq!(output.stdout, b"root\n");
+    assert_eq!(output.stderr, b"oops\n");
+    let output = simulator
+        .run_with_input("Test", "tr a-z A-Z", b"alice:$6$salt$hash\n")
+        .unwrap();
+    assert_eq!(output.exit_code, 0);
+    assert_eq!(output.stdout, b"ALICE:$6$SALT$HASH\n");
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,56 @@
+extern crate yowsl;
+
+use yowsl::WslError;
+
+#[test]
+fn decodes_known_hresults() {
+    let wsl_error = WslError::from_hresult(0x8007_019E_u32 as i32);
+    assert_eq!(wsl_error, WslError::SubsystemNotPresent);
+    assert_eq!(wsl_error.hresult(), 0x8007_019E_u32 as i32);
+    assert_eq!(wsl_error.facility(), 7);
+    assert_eq!(wsl_error.code(), 0x019E);
+    assert!(wsl_error.hint().unwrap().contains("Microsoft-Windows-Subsystem-Linux"));
+    assert_eq!(
+        WslError::from_hresult(0x8007_00B7_u32 as i32),
+        WslError::AlreadyExists
+    );
+    assert_eq!(
+        WslError::from_hresult(0x8007_0005_u32 as i32),
+        WslError::AccessDenied
+    );
+}
+
+#[test]
+fn keeps_unknown_hresults() {
+    let wsl_error = WslError::from_hresult(0x8007_0539_u32 as i32);
+    assert_eq!(wsl_error, WslError::Other(0x8007_0539_u32 as i32));
+    assert_eq!(wsl_error.hresult(), 0x8007_0539_u32 as i32);
+    assert_eq!(wsl_error.explanation(), "Win32 error 1337");
+    assert_eq!(wsl_error.hint(), None);
+    let wsl_error = WslError::from_hresult(0x8004_0001_u32 as i32);
+    assert_eq!(wsl_error.facility(), 4);
+    assert_eq!(wsl_error.code(), 1);
+    assert_eq!(wsl_error.explanation(), "Unknown error (facility 4, code 1)");
+}
+
+#[test]
+fn displays_explanation_and_hresult() {
+    assert_eq!(
+        WslError::AccessDenied.to_string(),
+        "Access is denied (HRESULT == 0x80070005)"
+    );
+}
+
+#[test]
+fn round_trips_every_variant() {
+    for hresult in [
+        0x8000_000D_u32, 0x8000_FFFF, 0x8004_0154, 0x8007_0002, 0x8007_0003, 0x8007_0005,
+        0x8007_0020, 0x8007_0057, 0x8007_00B7, 0x8007_019E, 0x8007_01BC, 0x8007_0422,
+        0x8037_0102,
+    ].iter()
+    {
+        let wsl_error = WslError::from_hresult(*hresult as i32);
+        assert_ne!(wsl_error, WslError::Other(*hresult as i32));
+        assert_eq!(wsl_error.hresult(), *hresult as i32);
+    }
+}